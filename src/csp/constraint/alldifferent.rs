@@ -0,0 +1,422 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: AllDifferent global constraint, the variables of its scope take pairwise distinct values
+    - Gac: maximum matching variables / values (augmenting paths), then Régin's filtering:
+      a value outside the matching is removed unless it lies on an alternating cycle (same SCC
+      of the residual graph) or on an even alternating path from a free value
+    - Bounds: Hall intervals over the ordered values of the scope, the bounds of the variables
+      overlapping a Hall interval are pushed out of it (to the fixpoint)
+***************************************/
+
+/**************************************
+            AllDifferent
+***************************************/
+use std::collections::BTreeSet;
+use std::fmt;
+use std::rc::Rc;
+use petgraph::algo::tarjan_scc;
+use petgraph::graph::{DiGraph, NodeIndex};
+use crate::csp::constraint::constraint::{Changed, Constraint, Cost, Wipeout};
+use crate::csp::domain::domain::{Domain, OrdT};
+use crate::csp::variable::extvar::ExVar;
+use crate::csp::variable::vvalue::VValue;
+use crate::instrumentation::monitor::Monitor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filtering {
+    Gac,
+    Bounds,
+}
+
+#[derive(Debug)]
+pub struct AllDifferent<T: OrdT> {
+    scope: Vec<Rc<ExVar<T>>>,
+    filtering: Filtering,
+}
+
+impl<T: OrdT> AllDifferent<T> {
+    //generalized arc consistency (matching and SCCs)
+    pub fn new(scp: Vec<Rc<ExVar<T>>>) -> Self {
+        Self { scope: scp, filtering: Filtering::Gac }
+    }
+
+    //bounds consistency (Hall intervals), weaker and cheaper
+    pub fn bounds(scp: Vec<Rc<ExVar<T>>>) -> Self {
+        Self { scope: scp, filtering: Filtering::Bounds }
+    }
+
+    pub fn filtering(&self) -> Filtering { self.filtering }
+
+    fn snapshot(&self) -> Self {
+        let scope = self.scope.iter()
+            .map(|v| Rc::new(v.deep_clone()))
+            .collect();
+        Self { scope, filtering: self.filtering }
+    }
+
+    //no assignment left for the i-th variable: its values are removed
+    fn wipeout(&self, i: usize, level: usize, monitor: &mut dyn Monitor) -> Wipeout {
+        let x = &self.scope[i];
+        for a in x.valid_values() {
+            monitor.on_value_deleted();
+            x.dom_mut().remove_value(&a, level);
+        }
+        Wipeout { variable: x.label().clone() }
+    }
+
+    fn gac(&self, level: usize, monitor: &mut dyn Monitor) -> Result<Changed, Wipeout> {
+        let domains: Vec<Vec<T>> = self.scope.iter().map(|x| x.valid_values()).collect();
+        let values = union(&domains);
+        let adjacency: Vec<Vec<usize>> = domains.iter()
+            .map(|d| d.iter().map(|a| values.binary_search(a).unwrap()).collect())
+            .collect();
+        let mate = matching(&adjacency, values.len());
+        if let Some(i) = mate.iter().position(Option::is_none) {
+            return Err(self.wipeout(i, level, monitor));
+        }
+
+        //residual graph: variables 0..n, values n..n+m, sink n+m
+        //matched value -> variable, variable -> unmatched value, free value -> sink -> matched value
+        let (n, m) = (self.scope.len(), values.len());
+        let mut graph = DiGraph::<(), ()>::new();
+        let nodes: Vec<NodeIndex> = (0..=n + m).map(|_| graph.add_node(())).collect();
+        let mut matched = vec![false; m];
+        for (i, a) in mate.iter().enumerate() {
+            let a = a.unwrap();
+            matched[a] = true;
+            graph.add_edge(nodes[n + a], nodes[i], ());
+        }
+        for (i, adj) in adjacency.iter().enumerate() {
+            for &a in adj.iter().filter(|a| mate[i] != Some(**a)) {
+                graph.add_edge(nodes[i], nodes[n + a], ());
+            }
+        }
+        for (a, &is_matched) in matched.iter().enumerate() {
+            if is_matched {
+                graph.add_edge(nodes[n + m], nodes[n + a], ());
+            } else {
+                graph.add_edge(nodes[n + a], nodes[n + m], ());
+            }
+        }
+        let mut component = vec![0; n + m + 1];
+        for (k, scc) in tarjan_scc(&graph).iter().enumerate() {
+            for node in scc {
+                component[node.index()] = k;
+            }
+        }
+
+        let mut changed = Changed::Unchanged;
+        for (i, x) in self.scope.iter().enumerate() {
+            for &a in adjacency[i].iter().filter(|a| mate[i] != Some(**a) && component[i] != component[n + **a]) {
+                monitor.on_value_deleted();
+                x.dom_mut().remove_value(&values[a], level);
+                changed = Changed::Reduced;
+            }
+        }
+        Ok(changed)
+    }
+
+    fn bounds_filter(&self, level: usize, monitor: &mut dyn Monitor) -> Result<Changed, Wipeout> {
+        let mut changed = Changed::Unchanged;
+        loop {
+            let domains: Vec<Vec<T>> = self.scope.iter().map(|x| x.valid_values()).collect();
+            if let Some(i) = domains.iter().position(|d| d.is_empty()) {
+                return Err(Wipeout { variable: self.scope[i].label().clone() });
+            }
+            //ranks of the bounds among the values of the scope
+            let values = union(&domains);
+            let rank = |a: &T| values.binary_search(a).unwrap();
+            let ranges: Vec<(usize, usize)> = domains.iter()
+                .map(|d| (rank(&d[0]), rank(&d[d.len() - 1])))
+                .collect();
+
+            let mut reduced = false;
+            for &(lo, _) in &ranges {
+                for &(_, hi) in ranges.iter().filter(|(_, hi)| *hi >= lo) {
+                    let inside: Vec<usize> = (0..ranges.len())
+                        .filter(|i| ranges[*i].0 >= lo && ranges[*i].1 <= hi)
+                        .collect();
+                    if inside.len() > hi - lo + 1 {
+                        return Err(self.wipeout(inside[0], level, monitor));
+                    }
+                    if inside.len() < hi - lo + 1 {
+                        continue;
+                    }
+                    //Hall interval [lo, hi]: taken by the variables inside
+                    for (i, x) in self.scope.iter().enumerate().filter(|(i, _)| !inside.contains(i)) {
+                        let (min, max) = ranges[i];
+                        let out: Vec<T> = x.valid_values().into_iter()
+                            .filter(|a| {
+                                let r = rank(a);
+                                (min >= lo && min <= hi && r <= hi) || (max >= lo && max <= hi && r >= lo)
+                            })
+                            .collect();
+                        for a in out {
+                            monitor.on_value_deleted();
+                            x.dom_mut().remove_value(&a, level);
+                            reduced = true;
+                        }
+                    }
+                }
+            }
+            if !reduced {
+                return Ok(changed);
+            }
+            changed = Changed::Reduced;
+        }
+    }
+}
+
+//sorted values of the domains
+fn union<T: OrdT>(domains: &[Vec<T>]) -> Vec<T> {
+    domains.iter().flatten().cloned().collect::<BTreeSet<T>>().into_iter().collect()
+}
+
+//maximum matching variable -> value index (Kuhn's augmenting paths), None for the unmatched variables
+fn matching(adjacency: &[Vec<usize>], m: usize) -> Vec<Option<usize>> {
+    let mut owner: Vec<Option<usize>> = vec![None; m];
+    for i in 0..adjacency.len() {
+        augment(i, adjacency, &mut owner, &mut vec![false; m]);
+    }
+    let mut mate = vec![None; adjacency.len()];
+    for (a, i) in owner.iter().enumerate() {
+        if let Some(i) = i {
+            mate[*i] = Some(a);
+        }
+    }
+    mate
+}
+
+fn augment(i: usize, adjacency: &[Vec<usize>], owner: &mut Vec<Option<usize>>, visited: &mut Vec<bool>) -> bool {
+    for &a in &adjacency[i] {
+        if visited[a] {
+            continue;
+        }
+        visited[a] = true;
+        let free = match owner[a] {
+            None => true,
+            Some(j) => augment(j, adjacency, owner, visited),
+        };
+        if free {
+            owner[a] = Some(i);
+            return true;
+        }
+    }
+    false
+}
+
+impl<T: OrdT + 'static> Constraint<T> for AllDifferent<T> {
+    fn deep_clone(&self) -> Rc<dyn Constraint<T>> {
+        Rc::new(self.snapshot())
+    }
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "alldifferent({})", self.scope.iter()
+            .map(|x| x.label().clone())
+            .collect::<Vec<_>>()
+            .join(", "))
+    }
+
+    //the assigned variables of the scope take distinct values
+    fn apply(&self, asn: &Vec<VValue<T>>) -> bool {
+        let values: Vec<&T> = self.scope.iter()
+            .filter_map(|x| asn.iter().find(|vv| &vv.label == x.label()).map(|vv| &vv.value))
+            .collect();
+        values.iter().collect::<BTreeSet<_>>().len() == values.len()
+    }
+
+    fn scp(&self) -> &[Rc<ExVar<T>>] { &self.scope }
+
+    //---- Overriding -----------
+    //may share its scope with binary constraints
+    fn label(&self) -> String {
+        format!("alldiff_{}", self.scope.iter().map(|v| v.label())
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(""))
+    }
+
+    fn cost(&self) -> Cost {
+        match self.filtering {
+            Filtering::Gac => Cost::Quadratic,
+            Filtering::Bounds => Cost::Linear,
+        }
+    }
+
+    fn idempotent(&self) -> bool { true }
+    fn has_filter(&self) -> bool { true }
+
+    fn filter(&self, level: usize, monitor: &mut dyn Monitor) -> Result<Changed, Wipeout> {
+        match self.filtering {
+            Filtering::Gac => self.gac(level, monitor),
+            Filtering::Bounds => self.bounds_filter(level, monitor),
+        }
+    }
+}
+
+/**************************************
+            Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::Expr;
+    use crate::csp::ast::pred::Pred;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use crate::{neq, var};
+    use crate::csp::constraint::alldifferent::{AllDifferent, Filtering};
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::event::reduced;
+    use crate::csp::truth::Truth;
+    use crate::csp::variable::extvar::{generate_variables, ExVar};
+    use crate::csp::variable::vvalue::VValue;
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::propagator::PropagatorOriented;
+    use crate::solver::consistency::propagator::fixtures::{filtered_by_each_scheme, values};
+    use crate::solver::consistency::revise::AC3;
+    use crate::solver::consistency::scheme::{ArcOriented, Scheme};
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::limits::SearchLimits;
+    use crate::vvals;
+
+    fn variables(domains: &[Vec<i32>]) -> HashMap<String, Rc<ExVar<i32>>> {
+        domains.iter().enumerate()
+            .map(|(i, d)| {
+                let x = format!("x{}", i + 1);
+                (x.clone(), Rc::new(ExVar::new(x, SetDom::new(d.clone()))))
+            })
+            .collect()
+    }
+
+    fn scope(vmap: &HashMap<String, Rc<ExVar<i32>>>) -> Vec<Rc<ExVar<i32>>> {
+        (1..=vmap.len()).map(|i| vmap[&format!("x{}", i)].clone()).collect()
+    }
+
+    fn alldiff(domains: &[Vec<i32>], filtering: Filtering) -> Csp<i32> {
+        let vmap = variables(domains);
+        let c = match filtering {
+            Filtering::Gac => AllDifferent::new(scope(&vmap)),
+            Filtering::Bounds => AllDifferent::bounds(scope(&vmap)),
+        };
+        Csp::new(vmap, vec![Rc::new(c)])
+    }
+
+    fn pairwise(vmap: &HashMap<String, Rc<ExVar<i32>>>, scope: &[Rc<ExVar<i32>>]) -> Vec<Rc<dyn Constraint<i32>>> {
+        let mut constraints: Vec<Rc<dyn Constraint<i32>>> = vec![];
+        for (i, x) in scope.iter().enumerate() {
+            for y in &scope[i + 1..] {
+                constraints.push(Rc::new(Intensional::from_pred(neq!(var!(vmap[x.label()]), var!(vmap[y.label()])))));
+            }
+        }
+        constraints
+    }
+
+    #[test]
+    fn alldiff_semantics() {
+        let csp = alldiff(&[vec![1, 2], vec![1, 2], vec![1, 2, 3]], Filtering::Gac);
+        let c = &csp.constraints()[0];
+        assert_eq!(c.check_assignment(&vvals!("x1" => 1, "x2" => 2, "x3" => 3)), Truth::True);
+        assert_eq!(c.check_assignment(&vvals!("x1" => 1, "x2" => 2, "x3" => 1)), Truth::False);
+        assert_eq!(c.check_assignment(&vvals!("x1" => 1, "x2" => 2)), Truth::Unknown);
+        assert_eq!(c.label(), "alldiff_x1x2x3");
+        assert_eq!(c.to_string(), "alldifferent(x1, x2, x3)");
+        assert!(c.idempotent() && c.has_filter());
+    }
+
+    #[test]
+    fn alldiff_gac() {
+        //{x1, x2} is a Hall set of {1, 2}: x3 = 3, then x4 = 4
+        let domains = [vec![1, 2], vec![1, 2], vec![1, 2, 3], vec![1, 2, 3, 4]];
+        filtered_by_each_scheme(|| alldiff(&domains, Filtering::Gac), "x4", 3, "alldiff_x1x2x3x4", |csp, _| {
+            assert_eq!(values(csp, "x3"), vec![3]);
+            assert_eq!(values(csp, "x4"), vec![4]);
+            assert_eq!(values(csp, "x1"), vec![1, 2]);
+        });
+
+        //pairwise differences: arc consistent as is
+        let vmap = variables(&domains);
+        let mut csp = Csp::new(vmap.clone(), pairwise(&vmap, &scope(&vmap)));
+        let events = reduced(csp.vars().keys());
+        assert!(ArcOriented.enforce(&mut csp, events, &mut AC3, &mut Statistics::default()));
+        assert_eq!(values(&csp, "x3"), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn alldiff_alternating_paths() {
+        //x1, x2 on {1, 3} leave 2 to x3, x4 free on {4, 5} with x5
+        let domains = [vec![1, 3], vec![1, 3], vec![1, 2, 3], vec![2, 4, 5], vec![4, 5, 6]];
+        let mut csp = alldiff(&domains, Filtering::Gac);
+        let events = reduced(csp.vars().keys());
+        assert!(ArcOriented.enforce(&mut csp, events, &mut AC3, &mut Statistics::default()));
+        assert_eq!(values(&csp, "x3"), vec![2]);
+        assert_eq!(values(&csp, "x4"), vec![4, 5]);
+        assert_eq!(values(&csp, "x5"), vec![4, 5, 6]);
+    }
+
+    #[test]
+    fn alldiff_bounds() {
+        //Hall interval [1, 2]
+        let mut csp = alldiff(&[vec![1, 2], vec![1, 2], vec![1, 2, 3, 4], vec![2, 3, 4]], Filtering::Bounds);
+        let events = reduced(csp.vars().keys());
+        assert!(ArcOriented.enforce(&mut csp, events, &mut AC3, &mut Statistics::default()));
+        assert_eq!(values(&csp, "x3"), vec![3, 4]);
+        assert_eq!(values(&csp, "x4"), vec![3, 4]);
+
+        //holes are not seen by the bounds: GAC removes 1 and 3 from x3
+        let domains = [vec![1, 3], vec![1, 3], vec![1, 2, 3]];
+        let mut bounds = alldiff(&domains, Filtering::Bounds);
+        let events = reduced(bounds.vars().keys());
+        assert!(ArcOriented.enforce(&mut bounds, events, &mut AC3, &mut Statistics::default()));
+        assert_eq!(values(&bounds, "x3"), vec![1, 2, 3]);
+        let mut gac = alldiff(&domains, Filtering::Gac);
+        let events = reduced(gac.vars().keys());
+        assert!(ArcOriented.enforce(&mut gac, events, &mut AC3, &mut Statistics::default()));
+        assert_eq!(values(&gac, "x3"), vec![2]);
+    }
+
+    #[test]
+    fn alldiff_pigeonhole() {
+        //4 variables, 3 values: pairwise differences are arc consistent, alldifferent fails
+        for filtering in [Filtering::Gac, Filtering::Bounds] {
+            let mut csp = alldiff(&vec![vec![1, 2, 3]; 4], filtering);
+            let events = reduced(csp.vars().keys());
+            assert!(!PropagatorOriented.enforce(&mut csp, events, &mut AC3, &mut Statistics::default()));
+            assert_eq!(csp.explanations().conflict().unwrap().constraint, Some("alldiff_x1x2x3x4".to_string()));
+        }
+    }
+
+    #[test]
+    fn alldiff_search() {
+        //3x3 latin squares: 12 solutions, fewer nodes than with pairwise differences
+        let count = |global: Option<Filtering>| {
+            let vmap = generate_variables("x", 9, &SetDom::new(vec![1, 2, 3]));
+            let cell = |r: usize, c: usize| vmap[&format!("x{}", 3 * r + c + 1)].clone();
+            let mut constraints: Vec<Rc<dyn Constraint<i32>>> = vec![];
+            for i in 0..3 {
+                for line in [(0..3).map(|j| cell(i, j)).collect::<Vec<_>>(), (0..3).map(|j| cell(j, i)).collect()] {
+                    match global {
+                        Some(Filtering::Gac) => constraints.push(Rc::new(AllDifferent::new(line))),
+                        Some(Filtering::Bounds) => constraints.push(Rc::new(AllDifferent::bounds(line))),
+                        None => constraints.extend(pairwise(&vmap, &line)),
+                    }
+                }
+            }
+            let mut csp = Csp::new(vmap.clone(), constraints);
+            let mut engine = Engine::new(Consistency::new(PropagatorOriented, AC3, Statistics::default()));
+            engine.set_limits(SearchLimits::none());
+            assert!(engine.search(&mut csp).is_sat());
+            (engine.limiter().solutions, engine.monitor().nodes)
+        };
+        let (solutions, nodes) = count(None);
+        assert_eq!(solutions, 12);
+        assert_eq!(count(Some(Filtering::Bounds)).0, 12);
+        let (solutions, gac_nodes) = count(Some(Filtering::Gac));
+        assert_eq!(solutions, 12);
+        assert!(gac_nodes <= nodes);
+    }
+}
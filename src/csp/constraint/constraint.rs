@@ -9,6 +9,7 @@
 /**************************************
             Trait Constraint
 ***************************************/
+use std::any::Any;
 use std::cmp::Ordering::Equal;
 use std::collections::HashMap;
 use std::fmt;
@@ -20,6 +21,43 @@ use crate::csp::truth::Truth;
 use crate::csp::variable::extvar::ExVar;
 use crate::csp::variable::vvalue::{make_assignment, vv, VValue};
 use crate::csp::csp::exists_extension;
+use crate::instrumentation::monitor::Monitor;
+
+//cost class of a propagator, cheapest first (scheduling priority, see propagator.rs)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Cost {
+    Unary,
+    Binary,
+    Linear,
+    Quadratic,
+    Expensive,
+}
+
+impl Cost {
+    pub const ALL: [Cost; 5] = [Cost::Unary, Cost::Binary, Cost::Linear, Cost::Quadratic, Cost::Expensive];
+
+    //generic support seeking: d^(r-1) checks per value beyond the binary case
+    pub fn of_arity(r: usize) -> Self {
+        match r {
+            0 | 1 => Cost::Unary,
+            2 => Cost::Binary,
+            _ => Cost::Expensive,
+        }
+    }
+}
+
+//outcome of a custom filtering (see Constraint::filter)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Changed {
+    Unchanged,
+    Reduced,
+}
+
+//domain of the scope wiped out by a custom filtering
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wipeout {
+    pub variable: String,
+}
 
 pub trait Constraint<T:OrdT> : Debug {
 
@@ -35,6 +73,19 @@ pub trait Constraint<T:OrdT> : Debug {
 
     //Trait : Methods to implement --- END
 
+    //AST of an intensional constraint (a Formula<E>, to downcast), None otherwise
+    //used by revise functions reasoning on expressions (see bounds.rs)
+    fn expression(&self) -> Option<&dyn Any> { None }
+
+    //Propagator: cost class of a propagation of the constraint
+    fn cost(&self) -> Cost { Cost::of_arity(self.scp().len()) }
+    //a propagation reaches the fixpoint of the constraint (it is not enqueued again on its own removals)
+    fn idempotent(&self) -> bool { false }
+    //custom filtering: the schemes call filter instead of revising the arcs of the constraint
+    fn has_filter(&self) -> bool { false }
+    //removes (at level) the values of the scope the constraint rules out, the schemes explain the removals
+    fn filter(&self, _level: usize, _monitor: &mut dyn Monitor) -> Result<Changed, Wipeout> { Ok(Changed::Unchanged) }
+
     //Constraint's label
     //Generated with c_ and the variables' label in its scope
     fn label(&self) -> String { format!("c_{}", self.scp().iter().map(|v| v.label())
@@ -0,0 +1,282 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Element constraint, value = array[index] with array a list of constants or variables
+    - positions from 0, an index value out of the array (or not a position) has no support
+    - GAC on index and value: index keeps the positions p with dom(array[p]) ∩ dom(value) not empty,
+      value keeps the values of dom(value) in dom(array[p]) for a kept position p
+    - once index is fixed to p, array[p] (a variable) and value share their values
+***************************************/
+
+/**************************************
+            Element
+***************************************/
+use std::fmt;
+use std::rc::Rc;
+use crate::csp::constraint::constraint::{Changed, Constraint, Cost, Wipeout};
+use crate::csp::domain::domain::{Domain, OrdT};
+use crate::csp::variable::extvar::ExVar;
+use crate::csp::variable::vvalue::VValue;
+use crate::instrumentation::monitor::Monitor;
+
+#[derive(Debug, Clone)]
+pub enum Entry<T: OrdT> {
+    Const(T),
+    Var(Rc<ExVar<T>>),
+}
+
+impl<T: OrdT> Entry<T> {
+    //possible values of the entry
+    fn values(&self) -> Vec<T> {
+        match self {
+            Entry::Const(a) => vec![a.clone()],
+            Entry::Var(x) => x.valid_values(),
+        }
+    }
+}
+
+impl<T: OrdT> fmt::Display for Entry<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Entry::Const(a) => write!(f, "{}", a),
+            Entry::Var(x) => write!(f, "{}", x.label()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Element<T: OrdT> {
+    //index, value, then the variables of the array
+    scope: Vec<Rc<ExVar<T>>>,
+    array: Vec<Entry<T>>,
+}
+
+impl<T: OrdT + TryInto<usize>> Element<T> {
+    pub fn new(index: Rc<ExVar<T>>, array: Vec<Entry<T>>, value: Rc<ExVar<T>>) -> Self {
+        let mut scope = vec![index, value];
+        for e in &array {
+            if let Entry::Var(x) = e
+                && scope.iter().all(|y| y.label() != x.label()) {
+                scope.push(x.clone());
+            }
+        }
+        Self { scope, array }
+    }
+
+    pub fn index(&self) -> &Rc<ExVar<T>> { &self.scope[0] }
+    pub fn value(&self) -> &Rc<ExVar<T>> { &self.scope[1] }
+    pub fn array(&self) -> &[Entry<T>] { &self.array }
+
+    fn snapshot(&self) -> Self {
+        let scope: Vec<Rc<ExVar<T>>> = self.scope.iter()
+            .map(|v| Rc::new(v.deep_clone()))
+            .collect();
+        //array variables shared with the copied scope
+        let array = self.array.iter()
+            .map(|e| match e {
+                Entry::Const(a) => Entry::Const(a.clone()),
+                Entry::Var(x) => Entry::Var(scope.iter().find(|y| y.label() == x.label()).unwrap().clone()),
+            })
+            .collect();
+        Self { scope, array }
+    }
+
+    //entry at the position a, None out of the array
+    fn entry(&self, a: &T) -> Option<&Entry<T>> {
+        a.clone().try_into().ok().and_then(|p: usize| self.array.get(p))
+    }
+
+    fn remove(x: &Rc<ExVar<T>>, values: Vec<T>, level: usize, monitor: &mut dyn Monitor) -> Result<bool, Wipeout> {
+        let reduced = !values.is_empty();
+        for a in values {
+            monitor.on_value_deleted();
+            x.dom_mut().remove_value(&a, level);
+        }
+        if x.dom().is_empty() {
+            return Err(Wipeout { variable: x.label().clone() });
+        }
+        Ok(reduced)
+    }
+
+    //one pass over index, value and the selected entry, true if a domain is reduced
+    fn revise(&self, level: usize, monitor: &mut dyn Monitor) -> Result<bool, Wipeout> {
+        let (index, value) = (self.index(), self.value());
+        let values = value.valid_values();
+
+        //positions with a common value
+        let mut supported: Vec<T> = vec![];
+        let mut unsupported: Vec<T> = vec![];
+        for p in index.valid_values() {
+            match self.entry(&p).map(|e| e.values()) {
+                Some(entry) if entry.iter().any(|a| values.contains(a)) => {
+                    supported.extend(entry.into_iter().filter(|a| values.contains(a)));
+                }
+                _ => unsupported.push(p),
+            }
+        }
+        let mut reduced = Self::remove(index, unsupported, level, monitor)?;
+        let unsupported = values.into_iter().filter(|a| !supported.contains(a)).collect();
+        reduced |= Self::remove(value, unsupported, level, monitor)?;
+
+        //fixed index: the selected variable takes the values of value
+        if let Some(p) = index.value()
+            && let Some(Entry::Var(x)) = self.entry(&p) {
+            let values = value.valid_values();
+            let unsupported = x.valid_values().into_iter().filter(|a| !values.contains(a)).collect();
+            reduced |= Self::remove(x, unsupported, level, monitor)?;
+        }
+        Ok(reduced)
+    }
+}
+
+impl<T: OrdT + TryInto<usize> + 'static> Constraint<T> for Element<T> {
+    fn deep_clone(&self) -> Rc<dyn Constraint<T>> {
+        Rc::new(self.snapshot())
+    }
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = [{}][{}]", self.value().label(), self.array.iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join(", "), self.index().label())
+    }
+
+    //false if index, value or the selected variable is not assigned
+    fn apply(&self, asn: &Vec<VValue<T>>) -> bool {
+        let value_of = |x: &Rc<ExVar<T>>| asn.iter().find(|vv| &vv.label == x.label()).map(|vv| vv.value.clone());
+        let (Some(p), Some(v)) = (value_of(self.index()), value_of(self.value())) else { return false };
+        match self.entry(&p) {
+            Some(Entry::Const(a)) => *a == v,
+            Some(Entry::Var(x)) => value_of(x) == Some(v),
+            None => false,
+        }
+    }
+
+    fn scp(&self) -> &[Rc<ExVar<T>>] { &self.scope }
+
+    //---- Overriding -----------
+    fn label(&self) -> String {
+        format!("element_{}", self.scope.iter().map(|v| v.label())
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(""))
+    }
+
+    fn cost(&self) -> Cost { Cost::Linear }
+    fn idempotent(&self) -> bool { true }
+    fn has_filter(&self) -> bool { true }
+
+    fn filter(&self, level: usize, monitor: &mut dyn Monitor) -> Result<Changed, Wipeout> {
+        let mut changed = Changed::Unchanged;
+        while self.revise(level, monitor)? {
+            changed = Changed::Reduced;
+        }
+        Ok(changed)
+    }
+}
+
+/**************************************
+            Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::element::{Element, Entry};
+    use crate::csp::csp::Csp;
+    use crate::csp::event::reduced;
+    use crate::csp::truth::Truth;
+    use crate::csp::variable::extvar::ExVar;
+    use crate::csp::variable::vvalue::{vv, VValue};
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::propagator::PropagatorOriented;
+    use crate::solver::consistency::propagator::fixtures::{filtered_by_each_scheme, values, variable};
+    use crate::solver::consistency::revise::AC3;
+    use crate::solver::consistency::scheme::{ArcOriented, Scheme};
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::limits::SearchLimits;
+    use crate::vvals;
+
+    fn element(index: Rc<ExVar<i32>>, array: Vec<Entry<i32>>, value: Rc<ExVar<i32>>) -> Csp<i32> {
+        let c = Element::new(index, array, value);
+        let vmap: HashMap<_, _> = c.scp().iter().map(|x| (x.label().clone(), x.clone())).collect();
+        Csp::new(vmap, vec![Rc::new(c)])
+    }
+
+    fn costs(table: &[i32]) -> Vec<Entry<i32>> {
+        table.iter().map(|a| Entry::Const(*a)).collect()
+    }
+
+    #[test]
+    fn element_semantics() {
+        let (i, v, y) = (variable("i", vec![0, 1]), variable("v", vec![1, 2]), variable("y", vec![1, 2]));
+        let c = Element::new(i, vec![Entry::Const(2), Entry::Var(y)], v);
+        assert_eq!(c.scp().len(), 3);
+        let c: &dyn Constraint<i32> = &c;
+        assert_eq!(c.to_string(), "v = [2, y][i]");
+        assert_eq!(c.label(), "element_ivy");
+        assert_eq!(c.check_assignment(&vvals!("i" => 0, "v" => 2, "y" => 1)), Truth::True);
+        assert_eq!(c.check_assignment(&vvals!("i" => 1, "v" => 2, "y" => 1)), Truth::False);
+        assert_eq!(c.check_assignment(&vvals!("i" => 1, "v" => 1)), Truth::Unknown);
+    }
+
+    #[test]
+    fn element_constants() {
+        //cost table: -1 and 4 are out of the array, 20 is not a possible cost
+        let csp = || element(variable("i", vec![-1, 0, 1, 2, 3, 4]), costs(&[10, 20, 10, 30]),
+                             variable("v", vec![10, 30, 40]));
+        filtered_by_each_scheme(csp, "i", 1, "element_iv", |csp, _| {
+            assert_eq!(values(csp, "i"), vec![0, 2, 3]);
+            assert_eq!(values(csp, "v"), vec![10, 30]);
+        });
+
+        //v = 10: positions 0 and 2
+        let mut csp = element(variable("i", vec![0, 1, 2, 3]), costs(&[10, 20, 10, 30]), variable("v", vec![10]));
+        let events = reduced(csp.vars().keys());
+        assert!(ArcOriented.enforce(&mut csp, events, &mut AC3, &mut Statistics::default()));
+        assert_eq!(values(&csp, "i"), vec![0, 2]);
+    }
+
+    #[test]
+    fn element_variables() {
+        let (x, y) = (variable("x", vec![1, 2]), variable("y", vec![3, 4]));
+        let mut csp = element(variable("i", vec![0, 1, 2]), vec![Entry::Var(x), Entry::Var(y), Entry::Const(7)],
+                              variable("v", vec![2, 3, 5]));
+        let events = reduced(csp.vars().keys());
+        assert!(ArcOriented.enforce(&mut csp, events, &mut AC3, &mut Statistics::default()));
+        assert_eq!(values(&csp, "i"), vec![0, 1]);
+        assert_eq!(values(&csp, "v"), vec![2, 3]);
+        //array variables are not filtered while the index is free
+        assert_eq!(values(&csp, "x"), vec![1, 2]);
+
+        //i = 0: v and x share their values
+        csp.assign(vv("i".to_string(), 0));
+        let events = csp.decision_events("i");
+        assert!(ArcOriented.enforce(&mut csp, events, &mut AC3, &mut Statistics::default()));
+        assert_eq!(values(&csp, "v"), vec![2]);
+        assert_eq!(values(&csp, "x"), vec![2]);
+        assert_eq!(values(&csp, "y"), vec![3, 4]);
+    }
+
+    #[test]
+    fn element_wipeout() {
+        let mut csp = element(variable("i", vec![0, 1]), costs(&[10, 20]), variable("v", vec![15]));
+        let events = reduced(csp.vars().keys());
+        assert!(!PropagatorOriented.enforce(&mut csp, events, &mut AC3, &mut Statistics::default()));
+        assert_eq!(csp.explanations().conflict().unwrap().constraint, Some("element_iv".to_string()));
+    }
+
+    #[test]
+    fn element_search() {
+        //v = [x, y][i] on {1, 2}: 2 positions, 2 values, the other entry free
+        let mut csp = element(variable("i", vec![0, 1]), vec![Entry::Var(variable("x", vec![1, 2])),
+            Entry::Var(variable("y", vec![1, 2]))], variable("v", vec![1, 2]));
+        let mut engine = Engine::new(Consistency::new(PropagatorOriented, AC3, Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut csp).is_sat());
+        assert_eq!(engine.limiter().solutions, 8);
+        assert_eq!(engine.limiter().failures, 0);
+    }
+}
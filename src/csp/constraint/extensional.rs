@@ -75,6 +75,11 @@ impl<T: OrdT + 'static> Constraint<T> for ExtConstraint<T> {
     }
 
     fn check_assignment(&self, asn: &Vec<VValue<T>>) -> Truth {
+        //projection of asn on the scope (in scope order), asn may cover more variables
+        let projected: Vec<VValue<T>> = self.scope.iter()
+            .filter_map(|x| asn.iter().find(|vv| &vv.label == x.label()).cloned())
+            .collect();
+        let asn = if projected.len() == self.scope.len() { &projected } else { asn };
         if self.allowed.iter().any(|t| t == asn) {
             Truth::True
         } else {
@@ -6,6 +6,7 @@
 /**************************************
    Formula (Intensional Constraints)
 ***************************************/
+use std::any::Any;
 use std::collections::HashSet;
 use std::fmt;
 use std::fmt::{Debug, Display};
@@ -82,6 +83,10 @@ impl<T:OrdT + 'static, E:Eval<Output = T> + 'static> Constraint<T> for Intension
     fn scp(&self) -> &[Rc<ExVar<T>>] {
         &self.scope
     }
+
+    fn expression(&self) -> Option<&dyn Any> {
+        Some(self.formula.as_ref())
+    }
 }
 
 impl<T: OrdT, E: Eval<Output = T>> Intensional<T, E> {
@@ -0,0 +1,326 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Linear sum constraint, Σ a_i.x_i (op) k with op in {=, <=, >=, !=} over ArithT values
+    - no cartesian product: the bounds of each term a_i.x_i are summed (min and max of the sum),
+      the values of x_i out of [k - max of the rest, k - min of the rest] are trimmed from its bounds
+      (ArithT has no division: the feasible values are bounded by one pass over the sorted domain,
+      the values out of these bounds are removed at once, the holes are kept)
+    - the sums are updated with the changes of the trimmed terms, to the fixpoint of the constraint;
+      nothing is kept between two calls of filter: the bounds of every term are computed again
+    - !=: x_i loses a_i.v = k - rest once the other variables are fixed
+***************************************/
+
+/**************************************
+            LinearSum
+***************************************/
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use crate::csp::ast::expr::ArithT;
+use crate::csp::constraint::constraint::{Changed, Constraint, Cost, Wipeout};
+use crate::csp::domain::domain::Domain;
+use crate::csp::variable::extvar::ExVar;
+use crate::csp::variable::vvalue::VValue;
+use crate::instrumentation::monitor::Monitor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Le,
+    Ge,
+    Neq,
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", match self {
+            Comparison::Eq => "=",
+            Comparison::Le => "<=",
+            Comparison::Ge => ">=",
+            Comparison::Neq => "!=",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct LinearSum<T: ArithT> {
+    scope: Vec<Rc<ExVar<T>>>,
+    coefficients: Vec<T>,
+    op: Comparison,
+    k: T,
+}
+
+impl<T: ArithT> LinearSum<T> {
+    //terms (a_i, x_i), the coefficients of a repeated variable are added
+    pub fn new(terms: Vec<(T, Rc<ExVar<T>>)>, op: Comparison, k: T) -> Self {
+        assert!(!terms.is_empty(), "LinearSum without terms");
+        let mut scope: Vec<Rc<ExVar<T>>> = vec![];
+        let mut coefficients: Vec<T> = vec![];
+        for (a, x) in terms {
+            match scope.iter().position(|y| y.label() == x.label()) {
+                Some(i) => coefficients[i] = coefficients[i].clone() + a,
+                None => {
+                    scope.push(x);
+                    coefficients.push(a);
+                }
+            }
+        }
+        Self { scope, coefficients, op, k }
+    }
+
+    pub fn coefficients(&self) -> &[T] { &self.coefficients }
+    pub fn op(&self) -> Comparison { self.op }
+    pub fn k(&self) -> &T { &self.k }
+
+    fn snapshot(&self) -> Self {
+        let scope = self.scope.iter()
+            .map(|v| Rc::new(v.deep_clone()))
+            .collect();
+        Self { scope, coefficients: self.coefficients.clone(), op: self.op, k: self.k.clone() }
+    }
+
+    //(min, max) of a_i.x_i on the current domain, None if wiped out
+    fn term(&self, i: usize) -> Option<(T, T)> {
+        let dom = self.scope[i].dom();
+        let (min, max) = (dom.min()?, dom.max()?);
+        let (a, b) = (self.coefficients[i].clone() * min, self.coefficients[i].clone() * max);
+        Some(if a <= b { (a, b) } else { (b, a) })
+    }
+
+    //can a_i.v reach k with the rest of the sum in [min, max]
+    fn feasible(&self, i: usize, v: &T, rest: &(T, T)) -> bool {
+        let av = self.coefficients[i].clone() * v.clone();
+        let (min, max) = (av.clone() + rest.0.clone(), av + rest.1.clone());
+        match self.op {
+            Comparison::Eq => min <= self.k && self.k <= max,
+            Comparison::Le => min <= self.k,
+            Comparison::Ge => max >= self.k,
+            Comparison::Neq => min != max || min != self.k,
+        }
+    }
+
+    //removes the values of x_i below its first feasible value and above its last one
+    //(any value for !=), true if x_i reduced
+    fn trim(&self, i: usize, rest: &(T, T), level: usize, monitor: &mut dyn Monitor) -> Result<bool, Wipeout> {
+        let x = &self.scope[i];
+        let mut reduced = false;
+        if self.op == Comparison::Neq {
+            if rest.0 == rest.1 {
+                for v in x.valid_values().into_iter().filter(|v| !self.feasible(i, v, rest)) {
+                    monitor.on_value_deleted();
+                    x.dom_mut().remove_value(&v, level);
+                    reduced = true;
+                }
+            }
+        } else {
+            let mut values = x.valid_values();
+            values.sort();
+            let first = values.iter().position(|v| self.feasible(i, v, rest));
+            let last = values.iter().rposition(|v| self.feasible(i, v, rest));
+            let removed: Vec<&T> = match (first, last) {
+                (Some(first), Some(last)) => values[..first].iter().chain(&values[last + 1..]).collect(),
+                _ => values.iter().collect(),
+            };
+            for v in removed {
+                monitor.on_value_deleted();
+                x.dom_mut().remove_value(v, level);
+                reduced = true;
+            }
+        }
+        if x.dom().is_empty() {
+            return Err(Wipeout { variable: x.label().clone() });
+        }
+        Ok(reduced)
+    }
+}
+
+impl<T: ArithT + 'static> Constraint<T> for LinearSum<T> {
+    fn deep_clone(&self) -> Rc<dyn Constraint<T>> {
+        Rc::new(self.snapshot())
+    }
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.coefficients.iter().zip(&self.scope)
+            .map(|(a, x)| format!("{}*{}", a, x.label()))
+            .collect::<Vec<_>>()
+            .join(" + "), self.op, self.k)
+    }
+
+    //false if a variable of the scope is not assigned
+    fn apply(&self, asn: &Vec<VValue<T>>) -> bool {
+        let values: HashMap<&String, &T> = asn.iter().map(|vv| (&vv.label, &vv.value)).collect();
+        let mut sum: Option<T> = None;
+        for (a, x) in self.coefficients.iter().zip(&self.scope) {
+            let Some(v) = values.get(x.label()) else { return false };
+            let term = a.clone() * (*v).clone();
+            sum = Some(match sum {
+                Some(s) => s + term,
+                None => term,
+            });
+        }
+        let sum = sum.unwrap();
+        match self.op {
+            Comparison::Eq => sum == self.k,
+            Comparison::Le => sum <= self.k,
+            Comparison::Ge => sum >= self.k,
+            Comparison::Neq => sum != self.k,
+        }
+    }
+
+    fn scp(&self) -> &[Rc<ExVar<T>>] { &self.scope }
+
+    //---- Overriding -----------
+    fn label(&self) -> String {
+        format!("sum_{}", self.scope.iter().map(|v| v.label())
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(""))
+    }
+
+    fn cost(&self) -> Cost { Cost::Linear }
+    fn idempotent(&self) -> bool { true }
+    fn has_filter(&self) -> bool { true }
+
+    fn filter(&self, level: usize, monitor: &mut dyn Monitor) -> Result<Changed, Wipeout> {
+        let mut terms = vec![];
+        for (i, x) in self.scope.iter().enumerate() {
+            terms.push(self.term(i).ok_or(Wipeout { variable: x.label().clone() })?);
+        }
+        let (mut min, mut max) = terms[1..].iter()
+            .fold(terms[0].clone(), |(min, max), (a, b)| (min + a.clone(), max + b.clone()));
+
+        let mut changed = Changed::Unchanged;
+        loop {
+            let mut reduced = false;
+            for (i, term) in terms.iter_mut().enumerate() {
+                let rest = (min.clone() - term.0.clone(), max.clone() - term.1.clone());
+                if self.trim(i, &rest, level, monitor)? {
+                    //sums updated with the new bounds of the term
+                    *term = self.term(i).unwrap();
+                    min = rest.0 + term.0.clone();
+                    max = rest.1 + term.1.clone();
+                    reduced = true;
+                }
+            }
+            if !reduced {
+                return Ok(changed);
+            }
+            changed = Changed::Reduced;
+        }
+    }
+}
+
+/**************************************
+            Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::linear::{Comparison, LinearSum};
+    use crate::csp::csp::Csp;
+    use crate::csp::event::reduced;
+    use crate::csp::truth::Truth;
+    use crate::csp::variable::extvar::ExVar;
+    use crate::csp::variable::vvalue::VValue;
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::propagator::PropagatorOriented;
+    use crate::solver::consistency::propagator::fixtures::{filtered_by_each_scheme, values, variable};
+    use crate::solver::consistency::revise::AC3;
+    use crate::solver::consistency::scheme::{ArcOriented, Scheme};
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::limits::SearchLimits;
+    use crate::vvals;
+
+    //one linear sum over its variables
+    fn linear(terms: Vec<(i32, Rc<ExVar<i32>>)>, op: Comparison, k: i32) -> Csp<i32> {
+        let vmap = terms.iter().map(|(_, x)| (x.label().clone(), x.clone())).collect();
+        Csp::new(vmap, vec![Rc::new(LinearSum::new(terms, op, k))])
+    }
+
+    #[test]
+    fn linear_semantics() {
+        let (x, y) = (variable("x", vec![0, 1, 2]), variable("y", vec![0, 1, 2]));
+        let c = LinearSum::new(vec![(2, x.clone()), (3, y.clone()), (1, x.clone())], Comparison::Le, 6);
+        assert_eq!(c.coefficients(), &[3, 3]);
+        let c: &dyn Constraint<i32> = &c;
+        assert_eq!(c.to_string(), "3*x + 3*y <= 6");
+        assert_eq!(c.label(), "sum_xy");
+        assert_eq!(c.check_assignment(&vvals!("x" => 1, "y" => 1)), Truth::True);
+        assert_eq!(c.check_assignment(&vvals!("x" => 2, "y" => 1)), Truth::False);
+        assert_eq!(c.check_assignment(&vvals!("x" => 2)), Truth::Unknown);
+    }
+
+    #[test]
+    fn linear_bounds() {
+        //x + 2y = 10, x in 0..9, y in 0..9: y in 1..5, x in 0..8
+        let csp = || linear(vec![(1, variable("x", (0..10).collect())), (2, variable("y", (0..10).collect()))],
+                            Comparison::Eq, 10);
+        filtered_by_each_scheme(csp, "y", 6, "sum_xy", |csp, _| {
+            assert_eq!(values(csp, "y"), (1..=5).collect::<Vec<_>>());
+            assert_eq!(values(csp, "x"), (0..=8).collect::<Vec<_>>());
+        });
+    }
+
+    #[test]
+    fn linear_negative_coefficients() {
+        //x - y >= 3 and x - y <= 3 on 0..5
+        let (x, y) = (variable("x", (0..=5).collect()), variable("y", (0..=5).collect()));
+        let mut csp = linear(vec![(1, x.clone()), (-1, y.clone())], Comparison::Ge, 3);
+        let events = reduced(csp.vars().keys());
+        assert!(ArcOriented.enforce(&mut csp, events, &mut AC3, &mut Statistics::default()));
+        assert_eq!(values(&csp, "x"), vec![3, 4, 5]);
+        assert_eq!(values(&csp, "y"), vec![0, 1, 2]);
+
+        let mut csp = linear(vec![(1, variable("x", vec![0, 1])), (-1, variable("y", vec![4, 5]))], Comparison::Le, -5);
+        let events = reduced(csp.vars().keys());
+        assert!(ArcOriented.enforce(&mut csp, events, &mut AC3, &mut Statistics::default()));
+        assert_eq!((values(&csp, "x"), values(&csp, "y")), (vec![0], vec![5]));
+    }
+
+    #[test]
+    fn linear_large_domains() {
+        //knapsack weight: 3a + 5b + 7c <= 20 on 0..1000
+        let (a, b, c) = (variable("a", (0..1000).collect()), variable("b", (0..1000).collect()),
+                         variable("c", (0..1000).collect()));
+        let mut csp = linear(vec![(3, a), (5, b), (7, c)], Comparison::Le, 20);
+        let events = reduced(csp.vars().keys());
+        let mut stats = Statistics::default();
+        assert!(PropagatorOriented.enforce(&mut csp, events, &mut AC3, &mut stats));
+        assert_eq!(values(&csp, "a"), (0..=6).collect::<Vec<_>>());
+        assert_eq!(values(&csp, "b"), (0..=4).collect::<Vec<_>>());
+        assert_eq!(values(&csp, "c"), (0..=2).collect::<Vec<_>>());
+        assert_eq!(stats.checks, 0);
+    }
+
+    #[test]
+    fn linear_neq_and_failure() {
+        //x + y != 3: y loses 2 once x = 1
+        let mut csp = linear(vec![(1, variable("x", vec![1])), (1, variable("y", vec![1, 2, 3]))], Comparison::Neq, 3);
+        let events = reduced(csp.vars().keys());
+        assert!(ArcOriented.enforce(&mut csp, events, &mut AC3, &mut Statistics::default()));
+        assert_eq!(values(&csp, "y"), vec![1, 3]);
+
+        //x + y >= 7 on 0..3
+        let mut csp = linear(vec![(1, variable("x", (0..=3).collect())), (1, variable("y", (0..=3).collect()))],
+                             Comparison::Ge, 7);
+        let events = reduced(csp.vars().keys());
+        assert!(!PropagatorOriented.enforce(&mut csp, events, &mut AC3, &mut Statistics::default()));
+        assert_eq!(csp.explanations().conflict().unwrap().constraint, Some("sum_xy".to_string()));
+    }
+
+    #[test]
+    fn linear_search() {
+        //x + y + z = 6 on 1..4: 10 solutions
+        let vars: Vec<_> = ["x", "y", "z"].iter().map(|x| (1, variable(x, (1..=4).collect()))).collect();
+        let mut csp = linear(vars, Comparison::Eq, 6);
+        let mut engine = Engine::new(Consistency::new(PropagatorOriented, AC3, Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut csp).is_sat());
+        assert_eq!(engine.limiter().solutions, 10);
+        //bounds of a sum of two variables: no dead end
+        assert_eq!(engine.limiter().failures, 0);
+    }
+}
@@ -1,3 +1,7 @@
+pub mod alldifferent;
 pub mod constraint;
+pub mod element;
 pub mod extensional;
-pub mod intensional;
\ No newline at end of file
+pub mod intensional;
+pub mod linear;
+pub mod nogood;
\ No newline at end of file
@@ -0,0 +1,122 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Nogood constraint, forbids a conjunction of literals ([x = v] / [x != v])
+    - learned from conflict analysis (see solver::search::learning)
+    - scope: the variables of the literals (lexicographic order)
+***************************************/
+
+/**************************************
+            Nogood
+***************************************/
+use std::fmt;
+use std::rc::Rc;
+use crate::csp::constraint::constraint::Constraint;
+use crate::csp::domain::domain::OrdT;
+use crate::csp::explanation::Literal;
+use crate::csp::variable::extvar::ExVar;
+use crate::csp::variable::vvalue::VValue;
+
+#[derive(Debug)]
+pub struct Nogood<T: OrdT> {
+    scope: Vec<Rc<ExVar<T>>>,
+    literals: Vec<Literal<T>>,
+}
+
+impl<T: OrdT> Nogood<T> {
+    //vars must contain the variables of the literals
+    pub fn new(vars: &[Rc<ExVar<T>>], literals: Vec<Literal<T>>) -> Self {
+        let mut scope: Vec<_> = vars.iter()
+            .filter(|v| literals.iter().any(|l| l.label() == v.label()))
+            .cloned()
+            .collect();
+        scope.sort_by(|a, b| a.label().cmp(b.label()));
+        scope.dedup_by(|a, b| a.label() == b.label());
+        debug_assert!(literals.iter().all(|l| scope.iter().any(|v| v.label() == l.label())),
+                      "Nogood literal out of vars");
+        Self { scope, literals }
+    }
+
+    pub fn literals(&self) -> &[Literal<T>] { &self.literals }
+
+    fn snapshot(&self) -> Self {
+        let scope = self.scope.iter()
+            .map(|v| Rc::new(v.deep_clone()))
+            .collect();
+        Self { scope, literals: self.literals.clone() }
+    }
+}
+
+impl<T: OrdT + 'static> Constraint<T> for Nogood<T> {
+    fn deep_clone(&self) -> Rc<dyn Constraint<T>> {
+        Rc::new(self.snapshot())
+    }
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "not({})", self.literals.iter()
+            .map(|l| l.to_string())
+            .collect::<Vec<_>>()
+            .join(" && "))
+    }
+
+    //violated iff every literal holds
+    fn apply(&self, asn: &Vec<VValue<T>>) -> bool {
+        !self.literals.iter().all(|l| l.eval(asn) == Some(true))
+    }
+
+    fn scp(&self) -> &[Rc<ExVar<T>>] { &self.scope }
+
+    //---- Overriding -----------
+    //nogoods may share scopes: label from literals
+    fn label(&self) -> String {
+        format!("ng_{}", self.literals.iter()
+            .map(|l| l.to_string())
+            .collect::<Vec<_>>()
+            .join(""))
+    }
+}
+
+/**************************************
+            Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::nogood::Nogood;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::explanation::Literal;
+    use crate::csp::truth::Truth;
+    use crate::csp::variable::extvar::ExVar;
+    use crate::csp::variable::vvalue::{vv, VValue};
+    use crate::{dom, var_dom, vvals};
+
+    fn setup_nogood() -> Nogood<i32> {
+        let x = var_dom!("x".into(), dom![1, 2, 3]);
+        let y = var_dom!("y".into(), dom![1, 2, 3]);
+        let z = var_dom!("z".into(), dom![1, 2, 3]);
+        //not (x = 1 and y != 2)
+        Nogood::new(&[z, y, x], vec![
+            Literal::Eq(vv("x".into(), 1)),
+            Literal::Neq(vv("y".into(), 2)),
+        ])
+    }
+
+    #[test]
+    fn nogood_scope() {
+        let ng: Rc<dyn Constraint<i32>> = Rc::new(setup_nogood());
+        assert_eq!(ng.scp().iter().map(|v| v.label().clone()).collect::<Vec<_>>(), vec!["x", "y"]);
+        assert_eq!(ng.label(), "ng_[x = 1][y != 2]");
+        assert_eq!(ng.to_string(), "not([x = 1] && [y != 2])");
+    }
+
+    #[test]
+    fn nogood_check() {
+        let ng = setup_nogood();
+        assert_eq!(ng.check_assignment(&vvals!("x" => 1, "y" => 1)), Truth::False);
+        assert_eq!(ng.check_assignment(&vvals!("x" => 1, "y" => 2)), Truth::True);
+        assert_eq!(ng.check_assignment(&vvals!("x" => 2, "y" => 1)), Truth::True);
+        assert_eq!(ng.rel().len(), 7);
+        assert_eq!(ng.is_support(&vv("x".into(), 1)), Truth::True);
+    }
+}
@@ -5,31 +5,38 @@
     - V, over Variables defined on D domains
 ***************************************/
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt;
 use std::rc::Rc;
 use petgraph::graph::UnGraph;
 use statrs::function::factorial::binomial;
 use crate::csp::constraint::constraint::Constraint;
-use crate::csp::domain::setdom::CartesianWalker;
+use crate::csp::domain::setdom::{CartesianWalker, SetDom};
 use crate::csp::domain::domain::{Domain, OrdT};
+use crate::csp::event::TriggerEvent;
+use crate::csp::explanation::{Explanation, Explanations, Literal};
 use crate::csp::truth::Truth;
 use crate::csp::variable::extvar::ExVar;
 use crate::csp::variable::vvalue::{vv, VValue};
 
+//domains and explanations of a Csp, restored by Csp::restore
+pub type Snapshot<T> = (HashMap<String, SetDom<T>>, Explanations<T>);
+
 pub struct Csp<T:OrdT> {
     vars : HashMap<String, Rc<ExVar<T>>>,
     constraints : Vec<Rc<dyn Constraint<T>>>,
     // -- for consistencies --
     past : Vec<String>, //instantiated variables
+    explanations : Explanations<T>, //why values have been removed
 }
 
 impl<T:OrdT> Csp<T> {
     pub fn new (v: HashMap<String, Rc<ExVar<T>>>, c: Vec<Rc<dyn Constraint<T>>>) -> Csp<T> {
-        Csp { vars: v, constraints: c, past : Vec::new() }
+        Csp { vars: v, constraints: c, past : Vec::new(), explanations: Explanations::new() }
     }
 
     pub fn constraints(&self) -> &Vec<Rc<dyn Constraint<T>>> {&self.constraints}
+    pub fn add_constraint(&mut self, c: Rc<dyn Constraint<T>>) {self.constraints.push(c)}
     pub fn vars(&self) -> &HashMap<String, Rc<ExVar<T>>> {&self.vars}
 
     pub fn cover(&self, asn: &Vec<VValue<T>>) -> Vec<&dyn Constraint<T>> {
@@ -117,12 +124,14 @@ impl<T:OrdT> Csp<T> {
         for v in self.vars.keys() {
             imap.insert(v.clone(),g.add_node(v.clone()));
         }
+        //one edge per pair of variables of a scope (a clique for non-binary constraints)
         for c in &self.constraints {
             let op = c.scp();
-            g.add_edge(
-                imap.get(op.get(0).unwrap().label()).unwrap().clone(),
-                imap.get(op.get(1).unwrap().label()).unwrap().clone(),
-                c.to_string());
+            for (i, x) in op.iter().enumerate() {
+                for y in &op[i + 1..] {
+                    g.add_edge(imap[x.label()], imap[y.label()], c.to_string());
+                }
+            }
         }
         g
     }
@@ -201,9 +210,106 @@ impl<T:OrdT> Csp<T> {
     //--- --- ------ --- ------ --- ------ --- ---
     pub fn past(&self) -> &Vec<String> {&self.past}
     pub fn level(&self) -> usize {self.past.len()}
+    //each assignment opens a new level: the decision and its propagation share it
     pub fn assign(&mut self, vvalue: VValue<T>) {
-        self.vars.get(&vvalue.label).unwrap().dom_mut().reduce_to(&vvalue.value, self.level());
-        self.past.push(vvalue.label);
+        self.past.push(vvalue.label.clone());
+        self.explanations.decide(vvalue.clone());
+        let lvl = self.level();
+        let x = self.vars.get(&vvalue.label).unwrap().clone();
+        for a in x.valid_values() {
+            if a != vvalue.value {
+                self.explanations.record(x.label(), Explanation::new(
+                    a, None, lvl, BTreeSet::from([lvl]), vec![Literal::Eq(vvalue.clone())]));
+            }
+        }
+        x.dom_mut().reduce_to(&vvalue.value, lvl);
+    }
+
+    //undo the last assignment and every removal made at its level
+    pub fn unassign(&mut self) -> Option<String> {
+        let lvl = self.level();
+        let label = self.past.pop()?;
+        for v in self.vars.values() {
+            v.dom_mut().restore_up_to(lvl);
+        }
+        self.explanations.restore_up_to(lvl);
+        Some(label)
+    }
+
+    //removals made without opening a level (no decision) are undone by restoring a snapshot
+    pub fn snapshot(&self) -> Snapshot<T> {
+        let doms = self.vars.iter().map(|(x, v)| (x.clone(), v.dom().snapshot())).collect();
+        (doms, self.explanations.clone())
+    }
+
+    pub fn restore(&mut self, snapshot: Snapshot<T>) {
+        let (mut doms, explanations) = snapshot;
+        for (x, v) in &self.vars {
+            if let Some(dom) = doms.remove(x) {
+                *v.dom_mut() = dom;
+            }
+        }
+        self.explanations = explanations;
+    }
+
+    //pending events of the domains, in label order
+    pub fn take_events(&self) -> Vec<TriggerEvent> {
+        let mut labels: Vec<&String> = self.vars.keys().collect();
+        labels.sort();
+        labels.into_iter().flat_map(|x| self.vars[x].take_events()).collect()
+    }
+
+    //pending events of the assigned variable x (a decision on a singleton domain is still an instantiation)
+    pub fn decision_events(&self, x: &str) -> Vec<TriggerEvent> {
+        let events = self.vars[x].take_events();
+        if events.is_empty() { vec![TriggerEvent::Instantiated(x.to_string())] } else { events }
+    }
+
+    //--- --- ---   Explanations  --- --- ---
+    pub fn explanations(&self) -> &Explanations<T> {&self.explanations}
+
+    //record the values removed from x while revising (c, x):
+    //they are explained by the removed values of the other variables of scp(c)
+    pub fn explain_removals(&mut self, c: &Rc<dyn Constraint<T>>, x: &ExVar<T>, removed: Vec<T>) {
+        let mut reason: Vec<Literal<T>> = vec![];
+        for y in c.scp().iter().filter(|y| y.label() != x.label()) {
+            let active = y.valid_values();
+            for b in y.dom().get_initial_values().iter().filter(|b| !active.contains(b)) {
+                reason.push(Literal::Neq(vv(y.label().clone(), b.clone())));
+            }
+        }
+        self.explain_removals_with(c, x, removed, reason);
+    }
+
+    //record the values removed from x by c, implied by the literals of reason
+    pub fn explain_removals_with(&mut self, c: &Rc<dyn Constraint<T>>, x: &ExVar<T>, removed: Vec<T>,
+                                 reason: Vec<Literal<T>>) {
+        let decisions = self.explanations.decisions_of(&reason);
+        let lvl = self.level();
+        for a in removed {
+            self.explanations.record(x.label(), Explanation::new(
+                a, Some(c.label()), lvl, decisions.clone(), reason.clone()));
+        }
+    }
+
+    //record the values removed from x by a bound (e.g. an objective bound) of the current branch:
+    //they are implied by all the decisions, a wipeout of x becomes the conflict
+    pub fn explain_bound(&mut self, name: &str, x: &ExVar<T>, removed: Vec<T>) {
+        let reason = self.explanations.decisions();
+        let decisions = (1..=self.level()).collect::<BTreeSet<_>>();
+        let lvl = self.level();
+        for a in removed {
+            self.explanations.record(x.label(), Explanation::new(
+                a, Some(name.to_string()), lvl, decisions.clone(), reason.clone()));
+        }
+        if x.dom().is_empty() {
+            self.explanations.set_conflict(x.label(), Some(name.to_string()));
+        }
+    }
+
+    //dom(x) has been wiped out while revising c
+    pub fn explain_wipeout(&mut self, c: &Rc<dyn Constraint<T>>, x: &ExVar<T>) {
+        self.explanations.set_conflict(x.label(), Some(c.label()));
     }
 
 }
@@ -11,6 +11,7 @@
             Type
 ***************************************/
 use crate::csp::domain::setdom::SetDomIter;
+use crate::csp::event::EventKind;
 
 pub trait OrdT:
 Clone + std::fmt::Debug + std::fmt::Display + Eq + Ord + std::hash::Hash
@@ -42,6 +43,7 @@ pub trait Domain<T:OrdT> {
     fn active_values(&self) -> Vec<T>;
     fn head(&self) -> Option<T>;
     fn tail(&self) -> Option<T>;
+    //0 if v is active, 1 + level of its removal otherwise
     fn absent(&self, v: &T) -> usize;
     fn next(&self, v: &T) -> Option<T>;
     fn remove_value(&mut self, v : &T, lvl : usize) -> ();
@@ -49,4 +51,6 @@ pub trait Domain<T:OrdT> {
     fn reduce_to(&mut self, v : &T, lvl : usize) -> ();
     fn restore_up_to(&mut self, lvl : usize) -> ();
     fn add_value(&mut self, v : &T) -> ();
+    //Events: kinds of the changes since the last call
+    fn take_events(&mut self) -> Vec<EventKind>;
 }
\ No newline at end of file
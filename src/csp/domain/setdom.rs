@@ -3,19 +3,21 @@
 - Desc: Contains:
     - Set Domain, an explicit and ordered set of values
     - Trailing method for backtracking
+    - Events of the changes (see csp/event.rs), pending until taken
 ***************************************/
 
 /**************************************
             SetDom
 ***************************************/
 use crate::csp::domain::domain::{Domain, OrdT};
+use crate::csp::event::EventKind;
 
 #[derive(Debug, Clone)]
 #[derive(PartialEq, PartialOrd)]
 pub struct SetDom<OrdT> {
     values: Vec<OrdT>,
     //trailing (indices on values)
-    absent:     Vec<usize>, //lvl of removed values
+    absent:     Vec<usize>, //1 + lvl of removed values (0: active, removals at level 0 included)
     next:       Vec<usize>, //links from first to last
     prev:       Vec<usize>, //links from last to first
     prev_absent: Vec<usize>,//links of removed values
@@ -23,7 +25,9 @@ pub struct SetDom<OrdT> {
     tail: usize,
     tail_absent: usize,
     //fast access to size
-    size:usize
+    size:usize,
+    //kinds of the changes since the last take_events
+    events: Vec<EventKind>
 }
 
 impl<T:OrdT> SetDom<T> {
@@ -45,7 +49,15 @@ impl<T:OrdT> SetDom<T> {
             head:1,
             tail:d,
             tail_absent:0,
-            size:d
+            size:d,
+            events: vec![]
+        }
+    }
+
+    //each kind is pending once
+    fn emit(&mut self, kind: EventKind) {
+        if !self.events.contains(&kind) {
+            self.events.push(kind);
         }
     }
 
@@ -69,7 +81,8 @@ impl<T:OrdT> Domain<T> for SetDom<T> {
             tail:           self.tail,
             tail_absent: self.tail_absent,
 
-            size: self.size
+            size: self.size,
+            events: self.events.clone()
         }
     }
 
@@ -117,9 +130,10 @@ impl<T:OrdT> Domain<T> for SetDom<T> {
     }
     fn remove_value(&mut self, v : &T, lvl : usize) -> () {
         if self.active_values().contains(v) {
+            let bound = self.min().as_ref() == Some(v) || self.max().as_ref() == Some(v);
             match self.values.iter().position(|i| i == v) {
                 Some(idx) => {
-                    self.absent[idx] = lvl;
+                    self.absent[idx] = lvl + 1;
                     self.prev_absent[idx] = self.tail_absent;
                     self.tail_absent = idx + 1;
 
@@ -131,6 +145,13 @@ impl<T:OrdT> Domain<T> for SetDom<T> {
                 }
                 _ => panic!("Error value {} not in domain",v)
             }
+            self.emit(EventKind::ValueRemoved);
+            if bound { self.emit(EventKind::BoundsChanged); }
+            match self.size {
+                0 => self.emit(EventKind::Wipeout),
+                1 => self.emit(EventKind::Instantiated),
+                _ => {}
+            }
         }
         #[cfg(debug_assertions)]
         self.check_size_invariant();
@@ -148,10 +169,15 @@ impl<T:OrdT> Domain<T> for SetDom<T> {
 
     fn restore_up_to(&mut self, lvl: usize) -> () {
         let mut b = self.tail_absent;
-        while b != 0 && self.absent[b-1] >= lvl {
+        while b != 0 && self.absent[b-1] > lvl {
             self.add_value(&self.values[b-1].clone());
             b = self.prev_absent[b-1];
         }
+        self.events.clear();
+    }
+
+    fn take_events(&mut self) -> Vec<EventKind> {
+        std::mem::take(&mut self.events)
     }
 
     fn add_value(&mut self, v: &T) -> () {
@@ -295,6 +321,19 @@ mod tests {
         assert_eq!(dom.size(), 2); // must not decrement twice
     }
 
+    #[test]
+    fn absent_levels() {
+        let mut dom = SetDom::new(vec![1, 2, 3]);
+        dom.remove_value(&1, 0);
+        dom.remove_value(&3, 2);
+        assert_eq!(dom.absent(&1), 1);
+        assert_eq!(dom.absent(&2), 0);
+        assert_eq!(dom.absent(&3), 3);
+        dom.restore_up_to(1);
+        assert_eq!(dom.absent(&3), 0);
+        assert_eq!(dom.active_values(), vec![2, 3]);
+    }
+
     #[test]
     fn domain_size_empty() {
         let mut dom = SetDom::new(vec![1]);
@@ -0,0 +1,133 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Solver events, contains:
+    - EventKind: what happened (value removed, bounds changed, instantiated, wipeout, backtrack, restart)
+    - TriggerEvent: an event and its variable (or level), handled by the consistency schemes
+  Domains emit the kinds of their changes (see SetDom::take_events): a removal is a ValueRemoved,
+  and a BoundsChanged if it was the min or the max, an Instantiated if one value is left, a Wipeout if none.
+  Revise functions subscribe to the kinds they care about (see Revise::reacts_to).
+***************************************/
+
+/**************************************
+            Events
+***************************************/
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EventKind {
+    ValueRemoved,
+    BoundsChanged,
+    Instantiated,
+    Wipeout,
+    Backtrack,
+    Restart,
+}
+
+impl EventKind {
+    //kinds emitted by a domain reduction
+    pub fn is_domain_change(&self) -> bool {
+        matches!(self, EventKind::ValueRemoved | EventKind::BoundsChanged | EventKind::Instantiated)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TriggerEvent {
+    ValueRemoved(String),
+    BoundsChanged(String),
+    Instantiated(String),
+    Wipeout(String),
+    //back to level - 1: the data structures updated at a level >= level are restored
+    Backtrack(usize),
+    Restart,
+}
+
+impl TriggerEvent {
+    pub fn new(kind: EventKind, label: &str) -> Self {
+        match kind {
+            EventKind::ValueRemoved => TriggerEvent::ValueRemoved(label.to_string()),
+            EventKind::BoundsChanged => TriggerEvent::BoundsChanged(label.to_string()),
+            EventKind::Instantiated => TriggerEvent::Instantiated(label.to_string()),
+            EventKind::Wipeout => TriggerEvent::Wipeout(label.to_string()),
+            EventKind::Backtrack => TriggerEvent::Backtrack(0),
+            EventKind::Restart => TriggerEvent::Restart,
+        }
+    }
+
+    pub fn kind(&self) -> EventKind {
+        match self {
+            TriggerEvent::ValueRemoved(_) => EventKind::ValueRemoved,
+            TriggerEvent::BoundsChanged(_) => EventKind::BoundsChanged,
+            TriggerEvent::Instantiated(_) => EventKind::Instantiated,
+            TriggerEvent::Wipeout(_) => EventKind::Wipeout,
+            TriggerEvent::Backtrack(_) => EventKind::Backtrack,
+            TriggerEvent::Restart => EventKind::Restart,
+        }
+    }
+
+    //label of the variable, None for the search events
+    pub fn variable(&self) -> Option<&String> {
+        match self {
+            TriggerEvent::ValueRemoved(x) | TriggerEvent::BoundsChanged(x)
+            | TriggerEvent::Instantiated(x) | TriggerEvent::Wipeout(x) => Some(x),
+            TriggerEvent::Backtrack(_) | TriggerEvent::Restart => None,
+        }
+    }
+}
+
+//every variable seen as reduced: triggers the initial propagation
+pub fn reduced<'a>(labels: impl IntoIterator<Item = &'a String>) -> Vec<TriggerEvent> {
+    labels.into_iter()
+        .flat_map(|x| [TriggerEvent::ValueRemoved(x.clone()), TriggerEvent::BoundsChanged(x.clone())])
+        .collect()
+}
+
+impl fmt::Display for TriggerEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TriggerEvent::Backtrack(lvl) => write!(f, "Backtrack({})", lvl),
+            TriggerEvent::Restart => write!(f, "Restart"),
+            e => write!(f, "{:?}({})", e.kind(), e.variable().unwrap()),
+        }
+    }
+}
+
+/**************************************
+            Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::domain::domain::Domain;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::event::{reduced, EventKind, TriggerEvent};
+
+    #[test]
+    fn domain_emits_events() {
+        let mut dom = SetDom::new(vec![1, 2, 3, 4]);
+        dom.remove_value(&2, 1);
+        assert_eq!(dom.take_events(), vec![EventKind::ValueRemoved]);
+        assert!(dom.take_events().is_empty());
+        dom.remove_value(&4, 1);
+        assert_eq!(dom.take_events(), vec![EventKind::ValueRemoved, EventKind::BoundsChanged]);
+        dom.reduce_to(&3, 2);
+        assert_eq!(dom.take_events(), vec![EventKind::ValueRemoved, EventKind::BoundsChanged, EventKind::Instantiated]);
+        dom.remove_value(&3, 2);
+        assert!(dom.take_events().contains(&EventKind::Wipeout));
+        //backtrack: pending events are dropped
+        dom.remove_value(&1, 1);
+        dom.restore_up_to(1);
+        assert!(dom.take_events().is_empty());
+    }
+
+    #[test]
+    fn trigger_event_accessors() {
+        let e = TriggerEvent::new(EventKind::Instantiated, "x");
+        assert_eq!(e, TriggerEvent::Instantiated("x".into()));
+        assert_eq!(e.variable(), Some(&"x".to_string()));
+        assert_eq!(TriggerEvent::Backtrack(2).variable(), None);
+        assert!(!TriggerEvent::Restart.kind().is_domain_change());
+        let labels = vec!["x".to_string(), "y".to_string()];
+        assert_eq!(reduced(&labels).len(), 4);
+        assert_eq!(e.to_string(), "Instantiated(x)");
+    }
+}
@@ -0,0 +1,289 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Explanations of value removals, contains:
+    - Literal: [x = v] or [x != v]
+    - Explanation: why a v-value (x, a) has been removed (constraint, decisions and reason)
+    - Conflict: the explanation of a domain wipeout
+    - Explanations: store kept alongside the SetDom trail (same levels)
+  Decisions are identified by their search level (see Csp::level()).
+  A removal [x != a] is implied by its reason (conjunction of literals),
+  i.e. the clause (not reason or [x != a]) holds.
+***************************************/
+
+/**************************************
+            Literal
+***************************************/
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use crate::csp::domain::domain::OrdT;
+use crate::csp::variable::vvalue::VValue;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Literal<T:OrdT> {
+    Eq(VValue<T>),
+    Neq(VValue<T>),
+}
+
+impl<T:OrdT> Literal<T> {
+    pub fn vvalue(&self) -> &VValue<T> {
+        match self {
+            Literal::Eq(vv) | Literal::Neq(vv) => vv,
+        }
+    }
+
+    pub fn label(&self) -> &String { &self.vvalue().label }
+
+    pub fn negate(&self) -> Self {
+        match self {
+            Literal::Eq(vv) => Literal::Neq(vv.clone()),
+            Literal::Neq(vv) => Literal::Eq(vv.clone()),
+        }
+    }
+
+    //None if the variable is not assigned by asn
+    pub fn eval(&self, asn: &[VValue<T>]) -> Option<bool> {
+        let value = &asn.iter().find(|vv| vv.label == *self.label())?.value;
+        match self {
+            Literal::Eq(vv) => Some(*value == vv.value),
+            Literal::Neq(vv) => Some(*value != vv.value),
+        }
+    }
+}
+
+impl<T:OrdT> fmt::Display for Literal<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Eq(vv) => write!(f, "[{} = {}]", vv.label, vv.value),
+            Literal::Neq(vv) => write!(f, "[{} != {}]", vv.label, vv.value),
+        }
+    }
+}
+
+/**************************************
+            Explanation
+***************************************/
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation<T:OrdT> {
+    pub value: T,
+    //label of the constraint responsible for the removal (None for a decision)
+    pub constraint: Option<String>,
+    //trail level of the removal
+    pub level: usize,
+    //levels of the decisions implying the removal
+    pub decisions: BTreeSet<usize>,
+    //literals implying the removal
+    pub reason: Vec<Literal<T>>,
+    //position on the trail (set by Explanations::record)
+    pub stamp: usize,
+}
+
+impl<T:OrdT> Explanation<T> {
+    pub fn new(value: T, constraint: Option<String>, level: usize,
+               decisions: BTreeSet<usize>, reason: Vec<Literal<T>>) -> Self {
+        Self { value, constraint, level, decisions, reason, stamp: 0 }
+    }
+}
+
+impl<T:OrdT> fmt::Display for Explanation<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} removed at level {} by {} because of {:?}",
+               self.value,
+               self.level,
+               self.constraint.clone().unwrap_or(String::from("decision")),
+               self.decisions)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub variable: String,
+    pub constraint: Option<String>,
+    pub decisions: BTreeSet<usize>,
+}
+
+/**************************************
+            Explanations (store)
+***************************************/
+
+#[derive(Debug, Clone)]
+pub struct Explanations<T:OrdT> {
+    //removed values of each variable (in removal order)
+    removals: HashMap<String, Vec<Explanation<T>>>,
+    //decision of each level (level i at index i-1) with its stamp
+    decided: Vec<(VValue<T>, usize)>,
+    //last domain wipeout
+    conflict: Option<Conflict>,
+    stamp: usize,
+}
+
+impl<T:OrdT> Default for Explanations<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T:OrdT> Explanations<T> {
+    pub fn new() -> Self {
+        Self { removals: HashMap::new(), decided: Vec::new(), conflict: None, stamp: 0 }
+    }
+
+    pub fn record(&mut self, label: &str, mut explanation: Explanation<T>) {
+        self.stamp += 1;
+        explanation.stamp = self.stamp;
+        self.removals.entry(label.to_string()).or_default().push(explanation);
+    }
+
+    //new level opened by the decision vvalue
+    pub fn decide(&mut self, vvalue: VValue<T>) {
+        self.stamp += 1;
+        self.decided.push((vvalue, self.stamp));
+    }
+
+    pub fn decisions(&self) -> Vec<Literal<T>> {
+        self.decided.iter().map(|(vv, _)| Literal::Eq(vv.clone())).collect()
+    }
+
+    //(level, stamp) of a literal currently true:
+    //- [x = a] if it is a decision
+    //- [x != a] if a has been removed from dom(x) (None if not explained, as an initial fact)
+    pub fn position(&self, literal: &Literal<T>) -> Option<(usize, usize)> {
+        match literal {
+            Literal::Eq(vv) => self.decided.iter()
+                .position(|(d, _)| d == vv)
+                .map(|i| (i + 1, self.decided[i].1)),
+            Literal::Neq(vv) => self.why(&vv.label, &vv.value)
+                .map(|e| (e.level, e.stamp)),
+        }
+    }
+
+    //literals implying a literal (None for decisions and facts)
+    pub fn reason(&self, literal: &Literal<T>) -> Option<&Vec<Literal<T>>> {
+        match literal {
+            Literal::Eq(_) => None,
+            Literal::Neq(vv) => self.why(&vv.label, &vv.value).map(|e| &e.reason),
+        }
+    }
+
+    //decisions responsible for a conjunction of literals
+    pub fn decisions_of(&self, literals: &[Literal<T>]) -> BTreeSet<usize> {
+        literals.iter()
+            .flat_map(|l| match l {
+                Literal::Eq(_) => self.position(l).map(|(lvl, _)| BTreeSet::from([lvl])).unwrap_or_default(),
+                Literal::Neq(vv) => self.why(&vv.label, &vv.value).map(|e| e.decisions.clone()).unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    //explanations of the values currently removed from the variable label
+    pub fn of(&self, label: &str) -> &[Explanation<T>] {
+        self.removals.get(label).map(|e| e.as_slice()).unwrap_or(&[])
+    }
+
+    //explanation of the removal of the v-value (label, value)
+    pub fn why(&self, label: &str, value: &T) -> Option<&Explanation<T>> {
+        self.of(label).iter().rev().find(|e| e.value == *value)
+    }
+
+    //decisions responsible for the current reduction of dom(label)
+    pub fn culprits(&self, label: &str) -> BTreeSet<usize> {
+        self.of(label).iter()
+            .flat_map(|e| e.decisions.iter().cloned())
+            .collect()
+    }
+
+    pub fn conflict(&self) -> Option<&Conflict> { self.conflict.as_ref() }
+
+    //dom(label) is empty: its conflict set is the union of its removals' decisions
+    pub fn set_conflict(&mut self, label: &str, constraint: Option<String>) {
+        self.conflict = Some(Conflict {
+            variable: label.to_string(),
+            constraint,
+            decisions: self.culprits(label),
+        });
+    }
+
+    //Trailing: forget removals made at a level >= lvl (mirrors SetDom::restore_up_to)
+    pub fn restore_up_to(&mut self, lvl: usize) {
+        for expl in self.removals.values_mut() {
+            expl.retain(|e| e.level < lvl);
+        }
+        self.removals.retain(|_, expl| !expl.is_empty());
+        self.decided.truncate(lvl.saturating_sub(1));
+        self.conflict = None;
+    }
+}
+
+/**************************************
+            Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use crate::csp::explanation::{Explanation, Explanations, Literal};
+    use crate::csp::variable::vvalue::vv;
+
+    fn removal(value: i32, level: usize, decisions: Vec<usize>) -> Explanation<i32> {
+        Explanation::new(value, Some(String::from("c_xy")), level,
+                         decisions.into_iter().collect(), vec![])
+    }
+
+    #[test]
+    fn explanations_culprits() {
+        let mut expl = Explanations::new();
+        expl.record("x", removal(1, 1, vec![1]));
+        expl.record("x", removal(2, 2, vec![1, 2]));
+
+        assert_eq!(expl.of("x").len(), 2);
+        assert_eq!(expl.of("y").len(), 0);
+        assert_eq!(expl.why("x", &2).unwrap().level, 2);
+        assert_eq!(expl.culprits("x"), BTreeSet::from([1, 2]));
+    }
+
+    #[test]
+    fn explanations_restore() {
+        let mut expl = Explanations::new();
+        expl.record("x", removal(1, 1, vec![1]));
+        expl.record("x", removal(2, 2, vec![2]));
+        expl.record("y", removal(3, 2, vec![2]));
+        expl.set_conflict("x", None);
+        assert_eq!(expl.conflict().unwrap().decisions, BTreeSet::from([1, 2]));
+
+        expl.restore_up_to(2);
+        assert!(expl.conflict().is_none());
+        assert!(expl.why("x", &2).is_none());
+        assert!(expl.why("x", &1).is_some());
+        assert_eq!(expl.of("y").len(), 0);
+    }
+
+    #[test]
+    fn literals() {
+        let asn = vec![vv("x".into(), 1), vv("y".into(), 2)];
+        let eq = Literal::Eq(vv("x".into(), 1));
+        assert_eq!(eq.eval(&asn), Some(true));
+        assert_eq!(eq.negate().eval(&asn), Some(false));
+        assert_eq!(Literal::Neq(vv("z".into(), 1)).eval(&asn), None);
+        assert_eq!(eq.to_string(), "[x = 1]");
+    }
+
+    #[test]
+    fn literal_positions() {
+        let mut expl = Explanations::new();
+        expl.decide(vv("y".into(), 2));
+        let eq = Literal::Eq(vv("y".into(), 2));
+        expl.record("x", Explanation::new(1, Some(String::from("c_xy")), 1,
+                                          BTreeSet::from([1]), vec![eq.clone()]));
+        let neq = Literal::Neq(vv("x".into(), 1));
+
+        assert_eq!(expl.position(&eq), Some((1, 1)));
+        assert_eq!(expl.position(&neq), Some((1, 2)));
+        assert_eq!(expl.reason(&neq), Some(&vec![eq.clone()]));
+        assert_eq!(expl.reason(&eq), None);
+        assert_eq!(expl.decisions(), vec![eq.clone()]);
+
+        expl.restore_up_to(1);
+        assert_eq!(expl.position(&eq), None);
+        assert_eq!(expl.position(&neq), None);
+    }
+}
@@ -4,6 +4,8 @@ pub mod constraint;
 pub mod csp;
 pub mod macros;
 pub mod truth;
+pub mod explanation;
+pub mod event;
 pub mod prelude;
 pub mod ast;
 
@@ -30,6 +30,7 @@ use std::hash::{Hash, Hasher};
 use std::rc::Rc;
 use crate::csp::domain::setdom::SetDom;
 use crate::csp::domain::domain::{Domain, OrdT};
+use crate::csp::event::TriggerEvent;
 use crate::{var_dom};
 
 #[derive(Debug)]
@@ -81,6 +82,10 @@ impl<T:OrdT> ExVar<T> {
     pub fn dom_mut(&self) -> std::cell::RefMut<'_, SetDom<T>> {
         self.dom.borrow_mut()
     }
+    //pending events of the domain
+    pub fn take_events(&self) -> Vec<TriggerEvent> {
+        self.dom_mut().take_events().into_iter().map(|k| TriggerEvent::new(k, &self.label)).collect()
+    }
 }
 
 impl<T:OrdT> fmt::Display for ExVar<T> {
@@ -6,6 +6,7 @@ use std::collections::BTreeMap;
 use std::time::{Duration, Instant};
 use crate::csp::csp::Csp;
 use crate::csp::prelude::domain::OrdT;
+use crate::solver::search::limits::Interrupt;
 
 pub trait Monitor {
     fn on_revision_check(&mut self);
@@ -17,7 +18,20 @@ pub trait Monitor {
     fn on_enforce_end(&mut self);
     fn on_enqueue(&mut self);
     fn on_dequeue(&mut self);
-    fn on_domain_snapshot<T: OrdT>(&mut self, csp: &Csp<T>);
+    //not available on a dyn Monitor (see Constraint::filter)
+    fn on_domain_snapshot<T: OrdT>(&mut self, csp: &Csp<T>) where Self: Sized;
+    //Search
+    fn on_node(&mut self);
+    fn on_backtrack(&mut self);
+    fn on_backjump(&mut self, from: usize, to: usize);
+    fn on_solution(&mut self);
+    fn on_nogood(&mut self, size: usize);
+    //Optimisation: accepted improvement of the incumbent
+    fn on_improvement(&mut self);
+    //Limits: interrupt of the running search (None once it is over), polled by the schemes at each step
+    //a monitor ignoring it (e.g. NoMonitor) is only stopped between nodes
+    fn set_interrupt(&mut self, _interrupt: Option<Interrupt>) {}
+    fn interrupted(&self) -> bool { false }
 }
 
 
@@ -52,6 +66,18 @@ impl Monitor for NoMonitor {
     fn on_dequeue(&mut self) {}
     #[inline(always)]
     fn on_domain_snapshot<T: OrdT>(&mut self, csp: &Csp<T>) {}
+    #[inline(always)]
+    fn on_node(&mut self) {}
+    #[inline(always)]
+    fn on_backtrack(&mut self) {}
+    #[inline(always)]
+    fn on_backjump(&mut self, _from: usize, _to: usize) {}
+    #[inline(always)]
+    fn on_solution(&mut self) {}
+    #[inline(always)]
+    fn on_nogood(&mut self, _size: usize) {}
+    #[inline(always)]
+    fn on_improvement(&mut self) {}
 }
 
 /**************************************
@@ -73,7 +99,19 @@ pub struct Statistics {
     //CSP
     pub domain_wipeouts: usize,
     pub value_deletions: usize,
-    pub domain_histogram: BTreeMap<usize, usize>
+    pub domain_histogram: BTreeMap<usize, usize>,
+    //Search
+    pub nodes: usize,
+    pub backtracks: usize,
+    pub backjumps: usize,
+    pub skipped_levels: usize,
+    pub solutions: usize,
+    pub nogoods: usize,
+    pub nogood_literals: usize,
+    //Optimisation
+    pub improvements: usize,
+    //Limits
+    interrupt: Option<Interrupt>,
 }
 
 impl Monitor for Statistics {
@@ -121,4 +159,38 @@ impl Monitor for Statistics {
             *self.domain_histogram.entry(size).or_insert(0) += 1;
         }
     }
+
+    fn on_node(&mut self) {
+        self.nodes += 1;
+    }
+
+    fn on_backtrack(&mut self) {
+        self.backtracks += 1;
+    }
+
+    fn on_backjump(&mut self, from: usize, to: usize) {
+        self.backjumps += 1;
+        self.skipped_levels += from - to - 1;
+    }
+
+    fn on_solution(&mut self) {
+        self.solutions += 1;
+    }
+
+    fn on_nogood(&mut self, size: usize) {
+        self.nogoods += 1;
+        self.nogood_literals += size;
+    }
+
+    fn on_improvement(&mut self) {
+        self.improvements += 1;
+    }
+
+    fn set_interrupt(&mut self, interrupt: Option<Interrupt>) {
+        self.interrupt = interrupt;
+    }
+
+    fn interrupted(&self) -> bool {
+        self.interrupt.as_ref().is_some_and(|i| i.is_raised())
+    }
 }
\ No newline at end of file
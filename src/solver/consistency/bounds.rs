@@ -0,0 +1,506 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Bounds(Z) consistency for arithmetic intensional constraints
+    - Interval: interval arithmetic over ArithT (add, sub, mul)
+    - BoundsZ revise: the bounds of x are first narrowed to the projection of the predicates
+      (x = z - y for x + y == z, ...), then the min (resp. max) of x is removed while the predicates
+      cannot hold, x being fixed to it and the other variables relaxed to [min, max] (no tuple enumeration).
+      The projection is exact when x occurs once in a predicate without product: one check per bound.
+  Handles conjunctions of predicates over Expr / AExpr (Formula::Atom, Formula::And),
+  the other constraints (Or, Not, extensional...) are revised by AC3rm.
+  So is the last free variable of a constraint: arcs of assigned variables are not revised,
+  the values of the last variable must be supports.
+  The arithmetic constraints only react to bounds changes and instantiations (see reacts_to_bounds).
+***************************************/
+
+/**************************************
+            Interval
+***************************************/
+use std::rc::Rc;
+use crate::csp::ast::expr::{AExpr, ArithT, Expr};
+use crate::csp::ast::eval::Eval;
+use crate::csp::ast::formula::Formula;
+use crate::csp::ast::pred::Pred;
+use crate::csp::constraint::constraint::Constraint;
+use crate::csp::domain::domain::{Domain, OrdT};
+use crate::csp::event::EventKind;
+use crate::instrumentation::monitor::Monitor;
+use crate::solver::consistency::arc::Arc;
+use crate::solver::consistency::revise::{AC3rm, Revise};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interval<T: ArithT> {
+    pub min: T,
+    pub max: T,
+}
+
+impl<T: ArithT> Interval<T> {
+    pub fn new(min: T, max: T) -> Self { Self { min, max } }
+    pub fn point(v: T) -> Self { Self { min: v.clone(), max: v } }
+
+    pub fn add(&self, other: &Self) -> Self {
+        Self::new(self.min.clone() + other.min.clone(), self.max.clone() + other.max.clone())
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        Self::new(self.min.clone() - other.max.clone(), self.max.clone() - other.min.clone())
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        let products = [
+            self.min.clone() * other.min.clone(), self.min.clone() * other.max.clone(),
+            self.max.clone() * other.min.clone(), self.max.clone() * other.max.clone(),
+        ];
+        Self::new(products.iter().min().unwrap().clone(), products.iter().max().unwrap().clone())
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.min <= other.max && other.min <= self.max
+    }
+
+    //None if empty
+    pub fn intersect(&self, other: &Self) -> Option<Self> {
+        let i = Self::new(self.min.clone().max(other.min.clone()), self.max.clone().min(other.max.clone()));
+        (i.min <= i.max).then_some(i)
+    }
+
+    pub fn contains(&self, v: &T) -> bool {
+        self.min <= *v && *v <= self.max
+    }
+
+    pub fn is_point(&self) -> bool { self.min == self.max }
+}
+
+//what a predicate requires from the values of x
+pub enum Projection<T: ArithT> {
+    //no narrowing (x absent, several occurrences, product)
+    Any,
+    Within(Interval<T>),
+    Never,
+}
+
+impl<T: ArithT> Projection<T> {
+    //[min, max] (Never if min > max)
+    fn new(min: T, max: T) -> Self {
+        match min <= max {
+            true => Projection::Within(Interval::new(min, max)),
+            false => Projection::Never,
+        }
+    }
+}
+
+//expressions evaluated over intervals
+pub trait Bounded<T: ArithT> {
+    //x fixed to a, other variables to [min, max], None if a domain is empty
+    fn interval(&self, x: &str, a: &T) -> Option<Interval<T>>;
+    //every variable to [min, max]
+    fn range(&self) -> Option<Interval<T>>;
+    fn occurs(&self, x: &str) -> bool;
+    //values of x for which the expression may lie in target
+    fn project(&self, x: &str, target: &Interval<T>) -> Projection<T>;
+}
+
+impl<T: ArithT> Bounded<T> for Expr<T> {
+    fn interval(&self, x: &str, a: &T) -> Option<Interval<T>> {
+        match self {
+            Expr::Var(v) if v.label() == x => Some(Interval::point(a.clone())),
+            _ => self.range(),
+        }
+    }
+
+    fn range(&self) -> Option<Interval<T>> {
+        match self {
+            Expr::Const(c) => Some(Interval::point(c.clone())),
+            Expr::Var(v) => Some(Interval::new(v.dom().min()?, v.dom().max()?)),
+        }
+    }
+
+    fn occurs(&self, x: &str) -> bool {
+        matches!(self, Expr::Var(v) if v.label() == x)
+    }
+
+    fn project(&self, x: &str, target: &Interval<T>) -> Projection<T> {
+        match self.occurs(x) {
+            true => Projection::Within(target.clone()),
+            false => Projection::Any,
+        }
+    }
+}
+
+impl<T: ArithT> Bounded<T> for AExpr<T> {
+    fn interval(&self, x: &str, a: &T) -> Option<Interval<T>> {
+        match self {
+            AExpr::Base(b) => b.interval(x, a),
+            AExpr::Add(l, r) => Some(l.interval(x, a)?.add(&r.interval(x, a)?)),
+            AExpr::Sub(l, r) => Some(l.interval(x, a)?.sub(&r.interval(x, a)?)),
+            AExpr::Mul(l, r) => Some(l.interval(x, a)?.mul(&r.interval(x, a)?)),
+        }
+    }
+
+    fn range(&self) -> Option<Interval<T>> {
+        match self {
+            AExpr::Base(b) => b.range(),
+            AExpr::Add(l, r) => Some(l.range()?.add(&r.range()?)),
+            AExpr::Sub(l, r) => Some(l.range()?.sub(&r.range()?)),
+            AExpr::Mul(l, r) => Some(l.range()?.mul(&r.range()?)),
+        }
+    }
+
+    fn occurs(&self, x: &str) -> bool {
+        match self {
+            AExpr::Base(b) => b.occurs(x),
+            AExpr::Add(l, r) | AExpr::Sub(l, r) | AExpr::Mul(l, r) => l.occurs(x) || r.occurs(x),
+        }
+    }
+
+    //l + r in target: l in target - r (x on one side only, the product is not inverted)
+    fn project(&self, x: &str, target: &Interval<T>) -> Projection<T> {
+        let (l, r) = match self {
+            AExpr::Base(b) => return b.project(x, target),
+            AExpr::Add(l, r) | AExpr::Sub(l, r) | AExpr::Mul(l, r) => (l, r),
+        };
+        if matches!(self, AExpr::Mul(_, _)) || (l.occurs(x) && r.occurs(x)) {
+            return Projection::Any;
+        }
+        let (inner, other) = match l.occurs(x) {
+            true => (l, r),
+            false if r.occurs(x) => (r, l),
+            false => return Projection::Any,
+        };
+        let other = match other.range() {
+            Some(o) => o,
+            None => return Projection::Never,
+        };
+        let target = match self {
+            AExpr::Add(_, _) => target.sub(&other),
+            AExpr::Sub(_, _) if l.occurs(x) => target.add(&other),
+            _ => other.sub(target),
+        };
+        inner.project(x, &target)
+    }
+}
+
+//the predicate may hold for some values of the intervals
+fn may_hold<T: ArithT, E: Eval<Output = T> + Bounded<T>>(p: &Pred<E>, x: &str, a: &T) -> bool {
+    let (l, r) = match p {
+        Pred::Eq(l, r) | Pred::Neq(l, r) | Pred::Lt(l, r)
+        | Pred::Le(l, r) | Pred::Gt(l, r) | Pred::Ge(l, r) => (l, r),
+    };
+    let (l, r) = match (l.interval(x, a), r.interval(x, a)) {
+        (Some(l), Some(r)) => (l, r),
+        _ => return false,
+    };
+    match p {
+        Pred::Eq(_, _) => l.intersects(&r),
+        Pred::Neq(_, _) => !(l.is_point() && r.is_point() && l.min == r.min),
+        Pred::Lt(_, _) => l.min < r.max,
+        Pred::Le(_, _) => l.min <= r.max,
+        Pred::Gt(_, _) => l.max > r.min,
+        Pred::Ge(_, _) => l.max >= r.min,
+    }
+}
+
+//values of x for which the predicate may hold (Lt / Gt projected as Le / Ge, Neq not projected)
+fn project<T: ArithT, E: Eval<Output = T> + Bounded<T>>(p: &Pred<E>, x: &str) -> Projection<T> {
+    let (l, r) = match p {
+        Pred::Eq(l, r) | Pred::Lt(l, r) | Pred::Le(l, r) | Pred::Gt(l, r) | Pred::Ge(l, r) => (l, r),
+        Pred::Neq(_, _) => return Projection::Any,
+    };
+    if l.occurs(x) == r.occurs(x) {
+        return Projection::Any;
+    }
+    let (lr, rr) = match (l.range(), r.range()) {
+        (Some(lr), Some(rr)) => (lr, rr),
+        _ => return Projection::Never,
+    };
+    //interval of the side of x
+    let target = match p {
+        Pred::Eq(_, _) => match lr.intersect(&rr) {
+            Some(i) => i,
+            None => return Projection::Never,
+        },
+        Pred::Lt(_, _) | Pred::Le(_, _) => match Projection::new(lr.min, rr.max) {
+            Projection::Within(i) => i,
+            _ => return Projection::Never,
+        },
+        _ => match Projection::new(rr.min, lr.max) {
+            Projection::Within(i) => i,
+            _ => return Projection::Never,
+        },
+    };
+    match l.occurs(x) {
+        true => l.project(x, &target),
+        false => r.project(x, &target),
+    }
+}
+
+//predicates of a conjunction, None if the formula is not one
+pub fn conjuncts<E: Eval>(f: &Formula<E>) -> Option<Vec<&Pred<E>>> {
+    match f {
+        Formula::Atom(p) => Some(vec![p]),
+        Formula::And(fs) => {
+            let mut preds = vec![];
+            for f in fs {
+                preds.extend(conjuncts(f)?);
+            }
+            Some(preds)
+        }
+        _ => None,
+    }
+}
+
+/**************************************
+            BoundsZ
+***************************************/
+
+pub struct BoundsZ<T: ArithT> {
+    //revise of the other constraints
+    fallback: AC3rm<T>,
+}
+
+impl<T: ArithT> Default for BoundsZ<T> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<T: ArithT> BoundsZ<T> {
+    pub fn new() -> Self {
+        Self { fallback: AC3rm::new() }
+    }
+}
+
+impl<M: Monitor, T: ArithT + 'static> Revise<M, T> for BoundsZ<T> {
+    fn revise(&mut self, arc: &Arc<T>, level: usize, monitor: &mut M) -> bool {
+        if others_fixed(arc) {
+            return self.fallback.revise(arc, level, monitor);
+        }
+        let expression = arc.constraint.expression();
+        if let Some(preds) = expression.and_then(|e| e.downcast_ref::<Formula<AExpr<T>>>()).and_then(conjuncts) {
+            return revise_bounds(&preds, arc, level, monitor);
+        }
+        if let Some(preds) = expression.and_then(|e| e.downcast_ref::<Formula<Expr<T>>>()).and_then(conjuncts) {
+            return revise_bounds(&preds, arc, level, monitor);
+        }
+        self.fallback.revise(arc, level, monitor)
+    }
+
+    fn restore_up_to(&mut self, lvl: usize) {
+        Revise::<M, T>::restore_up_to(&mut self.fallback, lvl);
+    }
+
+    fn reacts_to(&self, c: &Rc<dyn Constraint<T>>, kind: EventKind) -> bool {
+        reacts_to_bounds(c, kind)
+    }
+}
+
+//subscription of the bounds revisions: an inner removal cannot narrow the interval of an arithmetic
+//constraint, the other constraints (revised by the fallback) react to any domain change
+pub fn reacts_to_bounds<T: ArithT + 'static>(c: &Rc<dyn Constraint<T>>, kind: EventKind) -> bool {
+    let expression = c.expression();
+    let arithmetic = expression.and_then(|e| e.downcast_ref::<Formula<AExpr<T>>>()).and_then(conjuncts).is_some()
+        || expression.and_then(|e| e.downcast_ref::<Formula<Expr<T>>>()).and_then(conjuncts).is_some();
+    match kind {
+        EventKind::BoundsChanged | EventKind::Instantiated => true,
+        EventKind::ValueRemoved => !arithmetic,
+        _ => false,
+    }
+}
+
+//the other variables of the constraint have a single value
+pub fn others_fixed<T: OrdT>(arc: &Arc<T>) -> bool {
+    arc.constraint.scp().iter()
+        .all(|y| y.label() == arc.variable.label() || y.dom().size() == 1)
+}
+
+//trims the bounds of arc.variable, true if revision effective
+fn revise_bounds<M: Monitor, T: ArithT, E: Eval<Output = T> + Bounded<T>>(
+    preds: &[&Pred<E>], arc: &Arc<T>, level: usize, monitor: &mut M) -> bool {
+    monitor.on_revision_check();
+    let x = &arc.variable;
+    let size_before = x.dom().size();
+    let (min, max) = match (x.dom().min(), x.dom().max()) {
+        (Some(min), Some(max)) => (min, max),
+        _ => return false,
+    };
+    //projection: the values out of it are removed without any check
+    let mut within = Some(Interval::new(min, max));
+    for p in preds {
+        within = match project(p, x.label()) {
+            Projection::Any => within,
+            Projection::Within(i) => within.and_then(|w| w.intersect(&i)),
+            Projection::Never => None,
+        };
+    }
+    for a in x.valid_values() {
+        if !within.as_ref().is_some_and(|w| w.contains(&a)) {
+            monitor.on_value_deleted();
+            x.dom_mut().remove_value(&a, level);
+        }
+    }
+    //exact bounds (x fixed to each of them)
+    for upper in [false, true] {
+        loop {
+            let bound = if upper { x.dom().max() } else { x.dom().min() };
+            let a = match bound {
+                Some(a) => a,
+                None => break,
+            };
+            monitor.on_constraint_check();
+            if preds.iter().all(|p| may_hold(p, x.label(), &a)) {
+                break;
+            }
+            monitor.on_value_deleted();
+            x.dom_mut().remove_value(&a, level);
+        }
+    }
+    size_before != x.dom().size()
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::{Expr, AExpr};
+    use crate::csp::ast::pred::Pred;
+    use crate::csp::ast::formula::Formula;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use crate::{add, atom, base, cst, eq, le, mul, neq, or, sub, var, var_dom};
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::domain::Domain;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::event::reduced;
+    use crate::csp::variable::extvar::ExVar;
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::consistency::arc::Arc;
+    use crate::solver::consistency::bounds::{BoundsZ, Interval};
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::revise::Revise;
+    use crate::solver::consistency::scheme::{ArcOriented, Scheme};
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::limits::SearchLimits;
+
+    #[test]
+    fn interval_arithmetic() {
+        let a = Interval::new(-2, 3);
+        let b = Interval::new(1, 4);
+        assert_eq!(a.add(&b), Interval::new(-1, 7));
+        assert_eq!(a.sub(&b), Interval::new(-6, 2));
+        assert_eq!(a.mul(&b), Interval::new(-8, 12));
+        assert!(a.intersects(&b));
+        assert!(!Interval::new(5, 6).intersects(&b));
+        assert_eq!(a.intersect(&b), Some(Interval::new(1, 3)));
+        assert_eq!(Interval::new(5, 6).intersect(&b), None);
+    }
+
+    #[test]
+    fn bounds_large_domains() {
+        //x + y == z, x, y in [0, 1000], z in [0, 10]
+        let x = var_dom!("x".into(), SetDom::new((0..=1000).collect()));
+        let y = var_dom!("y".into(), SetDom::new((0..=1000).collect()));
+        let z = var_dom!("z".into(), SetDom::new((0..=10).collect()));
+        let c = Rc::new(Intensional::from_pred(eq!(add!(base!(var!(x)), base!(var!(y))), base!(var!(z)))));
+
+        let mut bounds = BoundsZ::new();
+        let mut monitor = Statistics::default();
+        assert!(bounds.revise(&Arc { constraint: c.clone(), variable: x.clone() }, 0, &mut monitor));
+        assert_eq!((x.dom().min(), x.dom().max()), (Some(0), Some(10)));
+        //projection x in [0, 10] - [0, 1000]: one check per bound
+        assert_eq!(monitor.checks, 2);
+        assert!(!bounds.revise(&Arc { constraint: c.clone(), variable: z.clone() }, 0, &mut monitor));
+    }
+
+    #[test]
+    fn bounds_fixpoint() {
+        //x * y == 12, x - y <= -2 on [1, 12]
+        let dom = SetDom::new((1..=12).collect());
+        let x = var_dom!("x".into(), dom.snapshot());
+        let y = var_dom!("y".into(), dom);
+        let mut vmap: HashMap<String, _> = HashMap::new();
+        vmap.insert("x".into(), x.clone());
+        vmap.insert("y".into(), y.clone());
+        let mut csp = Csp::new(vmap, vec![
+            Rc::new(Intensional::from_pred(eq!(mul!(base!(var!(x)), base!(var!(y))), base!(cst!(12))))),
+            Rc::new(Intensional::from_pred(le!(sub!(base!(var!(x)), base!(var!(y))), base!(cst!(-2))))),
+        ]);
+        let vars = reduced(csp.vars().keys());
+        assert!(ArcOriented.enforce(&mut csp, vars, &mut BoundsZ::new(), &mut Statistics::default()));
+        //x <= 10 then x * 3 <= 12, y >= 3: inner values are kept by bounds consistency
+        assert_eq!((x.dom().min(), x.dom().max()), (Some(1), Some(4)));
+        assert_eq!((y.dom().min(), y.dom().max()), (Some(3), Some(12)));
+        assert_eq!(y.dom().size(), 10);
+
+        //search: (1, 12), (2, 6) ((3, 4) violates x - y <= -2)
+        let mut engine = Engine::new(Consistency::new(ArcOriented, BoundsZ::new(), Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut csp).is_sat());
+        assert_eq!(engine.limiter().solutions, 2);
+    }
+
+    #[test]
+    fn bounds_mac() {
+        //x1 != x2 on {1, 2, 3}: nothing to narrow before the assignments, 6 solutions
+        let dom = SetDom::new(vec![1, 2, 3]);
+        let x1 = var_dom!("x1".into(), dom.snapshot());
+        let x2 = var_dom!("x2".into(), dom);
+        let mut vmap: HashMap<String, _> = HashMap::new();
+        vmap.insert("x1".into(), x1.clone());
+        vmap.insert("x2".into(), x2.clone());
+        let mut csp = Csp::new(vmap, vec![
+            Rc::new(Intensional::from_pred(neq!(base!(var!(x1)), base!(var!(x2))))),
+        ]);
+
+        let mut engine = Engine::new(Consistency::new(ArcOriented, BoundsZ::new(), Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut csp).is_sat());
+        assert_eq!(engine.limiter().solutions, 6);
+        assert_eq!((x1.dom().size(), x2.dom().size()), (3, 3));
+    }
+
+    #[test]
+    fn bounds_subscription() {
+        //x + y == 10 on [0, 10]: an inner removal of x does not trigger the revision of y
+        let dom = SetDom::new((0..=10).collect());
+        let x = var_dom!("x".into(), dom.snapshot());
+        let y = var_dom!("y".into(), dom);
+        let mut vmap: HashMap<String, _> = HashMap::new();
+        vmap.insert("x".into(), x.clone());
+        vmap.insert("y".into(), y.clone());
+        let mut csp = Csp::new(vmap, vec![
+            Rc::new(Intensional::from_pred(eq!(add!(base!(var!(x)), base!(var!(y))), base!(cst!(10))))),
+        ]);
+        x.dom_mut().remove_value(&5, 0);
+        let mut stats = Statistics::default();
+        assert!(ArcOriented.enforce(&mut csp, x.take_events(), &mut BoundsZ::new(), &mut stats));
+        assert_eq!(stats.revise_calls, 0);
+
+        x.dom_mut().remove_value(&10, 0);
+        assert!(ArcOriented.enforce(&mut csp, x.take_events(), &mut BoundsZ::new(), &mut stats));
+        assert_eq!(stats.revise_calls, 1);
+        assert_eq!(y.dom().min(), Some(1));
+    }
+
+    #[test]
+    fn bounds_last_variable() {
+        //x != y, x fixed: the inner value 2 of y is removed
+        let x = var_dom!("x".into(), SetDom::new(vec![2]));
+        let y = var_dom!("y".into(), SetDom::new(vec![1, 2, 3]));
+        let c = Rc::new(Intensional::from_pred(neq!(var!(x), var!(y))));
+        assert!(BoundsZ::new().revise(&Arc { constraint: c, variable: y.clone() }, 0, &mut Statistics::default()));
+        assert_eq!(y.valid_values(), vec![1, 3]);
+    }
+
+    #[test]
+    fn bounds_fallback() {
+        //x == 1 || y == 1 is not a conjunction: revised by AC3rm
+        let x = var_dom!("x".into(), SetDom::new(vec![0, 1]));
+        let y = var_dom!("y".into(), SetDom::new(vec![0]));
+        let c = Rc::new(Intensional::from_formula(Rc::new(or!(
+            atom!(eq!(var!(x), cst!(1))), atom!(eq!(var!(y), cst!(1)))))));
+        let mut bounds = BoundsZ::new();
+        assert!(bounds.revise(&Arc { constraint: c, variable: x.clone() }, 0, &mut Statistics::default()));
+        assert_eq!(x.valid_values(), vec![1]);
+    }
+}
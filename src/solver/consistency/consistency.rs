@@ -1,20 +1,21 @@
 /**************************************
 - Author: Clement Poncelet
 - Desc: Main Trait of consistency management
-    Scheme: Arc oriented Algorithm 7 (gac_enforce_arc) | Var oriented Algorithm 9 (gac_enforce_var)
-    Revise: AC1 Algorithm 8 (revise) | AC3 Algorithm 18 (seekSupport-3)  | AC2001 Algorithm 19 (seekSupport-2001)
-- Optimization:
-    add triggerEvent type for handling solver's events (wipeout, var assignments, val deletions, restart...)
+    Scheme: Arc oriented Algorithm 7 (gac_enforce_arc) | Var oriented Algorithm 9 (gac_enforce_var) | AC4 | AC6 (fine-grained)
+            | Max-RPC | light Max-RPC | NIC (stronger domain filtering)
+            | Propagator oriented (constraints scheduled by cost class)
+    Revise: AC1 Algorithm 8 (revise) | AC3 Algorithm 18 (seekSupport-3)  | AC2001 Algorithm 19 (seekSupport-2001) | AC3rm (residual supports)
+    Events: TriggerEvent (see csp/event.rs), domain events are enforced by the scheme,
+            backtrack and restart events restore the scheme and revise data structures
 ***************************************/
 
 use crate::csp::csp::Csp;
+use crate::csp::event::TriggerEvent;
 use crate::csp::prelude::domain::OrdT;
 pub(crate) use crate::solver::consistency::revise::Revise;
 use crate::solver::consistency::scheme::{Scheme};
 use crate::instrumentation::monitor::Monitor;
 
-//TriggerEvent
-
 pub struct Consistency<M, S, R, T:OrdT>
 where
     S: Scheme<M, T, R>,
@@ -42,7 +43,26 @@ where
         }
     }
 
-    pub fn enforce_consistency(&mut self, csp: &mut Csp<T>, events: Vec<String>) {
-        self.scheme.enforce(csp, events, &mut self.revise, &mut self.monitor);
+    //false iff a domain wipeout occurred (see csp.explanations().conflict())
+    pub fn enforce_consistency(&mut self, csp: &mut Csp<T>, events: Vec<TriggerEvent>) -> bool {
+        self.scheme.enforce(csp, events, &mut self.revise, &mut self.monitor)
+    }
+
+    //search events: backtrack to a level (see restore_up_to) or restart from the root
+    pub fn notify(&mut self, event: &TriggerEvent) {
+        match event {
+            TriggerEvent::Backtrack(lvl) => self.restore_up_to(*lvl),
+            TriggerEvent::Restart => self.restore_up_to(1),
+            _ => {}
+        }
     }
+
+    //back to level lvl - 1: the scheme and revise data structures updated at a level >= lvl are restored
+    pub fn restore_up_to(&mut self, lvl: usize) {
+        self.scheme.restore_up_to(lvl);
+        self.revise.restore_up_to(lvl);
+    }
+
+    pub fn monitor(&self) -> &M {&self.monitor}
+    pub fn monitor_mut(&mut self) -> &mut M {&mut self.monitor}
 }
\ No newline at end of file
@@ -2,7 +2,7 @@
 - Author: Clement Poncelet
 - Desc: Cvalue <c, x, a>, constraint, variable, value
 - Optimization:
-    - Improve Eq and hash (now on label())
+    - Improve Eq and hash (now on key(), no formatting)
 ***************************************/
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
@@ -12,6 +12,35 @@ use crate::csp::domain::domain::{Domain, OrdT};
 use crate::csp::prelude::extvar::ExVar;
 use crate::csp::prelude::vvalue::vv_from_hashmap;
 
+//constraint compared by address, unlike labels (constraints sharing a scope collide):
+//the key holds the constraint, its address cannot be reused by another one (e.g. of a later csp)
+pub struct CRef<T: OrdT>(Rc<dyn Constraint<T>>);
+
+impl<T: OrdT> CRef<T> {
+    fn address(&self) -> usize { Rc::as_ptr(&self.0) as *const () as usize }
+}
+
+impl<T: OrdT> Clone for CRef<T> {
+    fn clone(&self) -> Self { CRef(self.0.clone()) }
+}
+
+impl<T: OrdT> PartialEq for CRef<T> {
+    fn eq(&self, other: &Self) -> bool { self.address() == other.address() }
+}
+
+impl<T: OrdT> Eq for CRef<T> {}
+
+impl<T: OrdT> Hash for CRef<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.address().hash(state); }
+}
+
+impl<T: OrdT> std::fmt::Debug for CRef<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "{}", self.0.label()) }
+}
+
+//cvalue <c, x, a>
+pub type CKey<T> = (CRef<T>, String, T);
+
 pub struct CValue<T:OrdT> {
     pub constraint: Rc<dyn Constraint<T>>,
     pub variable: Rc<ExVar<T>>,
@@ -21,13 +50,13 @@ pub struct CValue<T:OrdT> {
 // Implement Eq + PartialEq
 impl<T:OrdT> PartialEq for CValue<T> {
     fn eq(&self, other: &Self) -> bool {
-        self.label() == other.label()
+        self.key() == other.key()
     }
 }
 
 impl<T:OrdT> Hash for CValue<T> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.label().hash(state);
+        self.key().hash(state);
     }
 }
 
@@ -38,6 +67,9 @@ impl<T:OrdT> CValue<T> {
         format!("{}", vec![self.constraint.label(), self.variable.label().clone(), self.value.to_string()]
             .join("_"))
     }
+    pub fn key(&self) -> CKey<T> {
+        (CRef(self.constraint.clone()), self.variable.label().clone(), self.value.clone())
+    }
     pub fn deep_clone(&self) -> Self {
         let c:Rc<dyn Constraint<T>> = self.constraint.deep_clone();
         let v = c.scp().iter().find(|v| v.label() == self.variable.label())
@@ -49,11 +81,8 @@ impl<T:OrdT> CValue<T> {
         }
     }
 
+    //first valid tuple of c with x = a (possibly not allowed: (x, a) may have no support)
     pub fn get_first_valid_tuple(&self) -> HashMap<String, T> {
-        let c_check = self.deep_clone();
-        c_check.variable.dom_mut().reduce_to(&c_check.value, 42); //to check but should not modify dom(cval.variable)
-        assert!(!c_check.constraint.rel().is_empty());
-
         let mut tuple: HashMap<String, T> = HashMap::new();
         tuple.insert(self.variable.label().clone(), self.value.clone());
 
@@ -87,15 +116,10 @@ impl<T:OrdT> CValue<T> {
         None
     }
 
+    //tuple: a stale support of (x, a), its first invalid position is limit (the tuple itself may still
+    //be allowed by c, e.g. a table constraint: only its validity matters)
     pub fn get_next_valid_tuple_limit(&self, tuple : &HashMap<String, T>, limit : i32) -> Option<HashMap<String, T>> {
         assert_eq!(tuple.get(self.variable.label()), Some(&self.value.clone()));
-
-        //tuple not in val(c)_x=a
-        let c_check = self.deep_clone();
-        c_check.variable.dom_mut().reduce_to(&c_check.value, 42);
-        assert!(!c_check.constraint.rel().contains(&vv_from_hashmap(tuple)));
-        assert!(c_check.constraint.rel().len() > 0);
-
         assert_eq!(limit, self.constraint.get_first_invalid_pos(Some(tuple)));
 
         let mut ret = tuple.clone();
@@ -0,0 +1,282 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Directional consistencies along a variable ordering x_1 < ... < x_n
+    - induced width of the ordering (Csp::primal_graph): the parents of x_i are connected
+      from x_n down to x_1, the width is the greatest number of parents
+    - DAC: from x_n down to x_1, each constraint c whose latest variable is x_i revises
+      the arcs (c, y) of its earlier variables (a tree ordered from its root becomes backtrack-free)
+    - adaptive consistency (bucket elimination): the bucket of x_i holds the constraints whose latest variable is x_i,
+      it records a constraint on its parents (the tuples extending to x_i), put in the bucket of their latest variable
+    - backtrack_free: assigns x_1 .. x_n in order, each value checked against the constraints of its bucket
+  The adaptive network shares the variables of the csp: its constraints and the recorded ExtConstraints.
+***************************************/
+use std::collections::{BTreeSet, HashMap};
+use std::rc::Rc;
+use crate::csp::constraint::constraint::{cartesian_product, Constraint};
+use crate::csp::constraint::extensional::ExtConstraint;
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::{Domain, OrdT};
+use crate::csp::variable::extvar::ExVar;
+use crate::csp::variable::vvalue::{make_assignment, vv, VValue};
+use crate::instrumentation::monitor::Monitor;
+use crate::solver::consistency::arc::Arc;
+use crate::solver::consistency::consistency::Revise;
+
+//width of the ordering in the induced graph (ordered primal graph, parents connected)
+pub fn induced_width<T: OrdT>(csp: &Csp<T>, order: &[String]) -> usize {
+    let pos = positions(order);
+    let graph = csp.primal_graph();
+    let mut adjacency: HashMap<String, BTreeSet<String>> = HashMap::new();
+    for e in graph.edge_indices() {
+        let (u, v) = graph.edge_endpoints(e).unwrap();
+        adjacency.entry(graph[u].clone()).or_default().insert(graph[v].clone());
+        adjacency.entry(graph[v].clone()).or_default().insert(graph[u].clone());
+    }
+
+    let mut width = 0;
+    for x in order.iter().rev() {
+        let parents: Vec<String> = adjacency.get(x).cloned().unwrap_or_default().into_iter()
+            .filter(|y| pos[y] < pos[x])
+            .collect();
+        width = width.max(parents.len());
+        for p in &parents {
+            for q in parents.iter().filter(|q| *q != p) {
+                adjacency.entry(p.clone()).or_default().insert(q.clone());
+            }
+        }
+    }
+    width
+}
+
+//directional arc consistency along order, false on domain wipeout
+pub fn dac<M: Monitor, T: OrdT, R: Revise<M, T>>(csp: &mut Csp<T>, order: &[String], revise: &mut R,
+                                                 monitor: &mut M) -> bool {
+    monitor.on_enforce_start();
+    let buckets = buckets(csp.constraints(), order);
+    for (i, bucket) in buckets.iter().enumerate().rev() {
+        for c in bucket {
+            for y in c.scp().iter().filter(|y| y.label() != &order[i]) {
+                let before = y.valid_values();
+                if !revise.revise(&Arc { constraint: c.clone(), variable: y.clone() }, csp.level(), monitor) {
+                    monitor.on_revise_fruitless();
+                    continue;
+                }
+                let after = y.valid_values();
+                csp.explain_removals(c, y, before.into_iter().filter(|a| !after.contains(a)).collect());
+                if y.dom().is_empty() {
+                    csp.explain_wipeout(c, y);
+                    monitor.on_domain_wipeout();
+                    monitor.on_enforce_end();
+                    return false;
+                }
+            }
+        }
+    }
+    monitor.on_enforce_end();
+    monitor.on_domain_snapshot(csp);
+    true
+}
+
+pub struct AdaptiveConsistency {
+    //induced width met by the last enforce (greatest number of parents of a bucket)
+    pub width: usize,
+    //constraints recorded by the last enforce
+    pub recorded: usize,
+}
+
+impl Default for AdaptiveConsistency {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AdaptiveConsistency {
+    pub fn new() -> Self {
+        Self { width: 0, recorded: 0 }
+    }
+
+    //backtrack-free network along order, None if a bucket has no consistent tuple
+    pub fn enforce<M: Monitor, T: OrdT + 'static>(&mut self, csp: &Csp<T>, order: &[String], monitor: &mut M) -> Option<Csp<T>> {
+        self.width = 0;
+        self.recorded = 0;
+        monitor.on_enforce_start();
+        let pos = positions(order);
+        let mut buckets = buckets(csp.constraints(), order);
+        let mut constraints = csp.constraints().clone();
+
+        for i in (0..order.len()).rev() {
+            if buckets[i].is_empty() {
+                continue;
+            }
+            let x = csp.vars()[&order[i]].clone();
+            let mut parents: Vec<Rc<ExVar<T>>> = buckets[i].iter()
+                .flat_map(|c| c.scp().iter().cloned())
+                .filter(|y| y.label() != x.label())
+                .collect();
+            parents.sort_by_key(|y| pos[y.label()]);
+            parents.dedup_by(|y, z| y.label() == z.label());
+            self.width = self.width.max(parents.len());
+
+            //tuples of the parents extending to x
+            let mut allowed = vec![];
+            let mut size = 0;
+            for values in cartesian_product(&parents) {
+                size += 1;
+                let mut asn = make_assignment(&parents, values);
+                let extends = x.valid_values().into_iter().any(|a| {
+                    asn.push(vv(x.label().clone(), a));
+                    let consistent = buckets[i].iter().all(|c| {
+                        monitor.on_constraint_check();
+                        c.check_assignment(&asn).to_bool().unwrap()
+                    });
+                    asn.pop();
+                    consistent
+                });
+                if extends {
+                    allowed.push(asn);
+                }
+            }
+            if allowed.is_empty() {
+                monitor.on_domain_wipeout();
+                monitor.on_enforce_end();
+                return None;
+            }
+            if allowed.len() < size {
+                let latest = pos[parents.last().unwrap().label()];
+                let recorded: Rc<dyn Constraint<T>> = Rc::new(ExtConstraint::new(parents, allowed));
+                buckets[latest].push(recorded.clone());
+                constraints.push(recorded);
+                self.recorded += 1;
+            }
+        }
+        monitor.on_enforce_end();
+        Some(Csp::new(csp.vars().clone(), constraints))
+    }
+}
+
+//greedy assignment along order (no backtrack), None on a dead end
+pub fn backtrack_free<M: Monitor, T: OrdT>(csp: &Csp<T>, order: &[String], monitor: &mut M) -> Option<Vec<VValue<T>>> {
+    let buckets = buckets(csp.constraints(), order);
+    let mut asn = vec![];
+    for (x, bucket) in order.iter().zip(&buckets) {
+        let a = csp.vars()[x].valid_values().into_iter().find(|a| {
+            asn.push(vv(x.clone(), a.clone()));
+            let consistent = bucket.iter().all(|c| {
+                monitor.on_constraint_check();
+                c.check_assignment(&asn).to_bool().unwrap()
+            });
+            asn.pop();
+            consistent
+        })?;
+        asn.push(vv(x.clone(), a));
+    }
+    Some(asn)
+}
+
+fn positions(order: &[String]) -> HashMap<String, usize> {
+    order.iter().enumerate().map(|(i, x)| (x.clone(), i)).collect()
+}
+
+//bucket of x_i: the constraints whose latest variable is x_i
+fn buckets<T: OrdT>(constraints: &[Rc<dyn Constraint<T>>], order: &[String]) -> Vec<Vec<Rc<dyn Constraint<T>>>> {
+    let pos = positions(order);
+    let mut buckets = vec![vec![]; order.len()];
+    for c in constraints {
+        if let Some(latest) = c.scp().iter().map(|y| pos[y.label()]).max() {
+            buckets[latest].push(c.clone());
+        }
+    }
+    buckets
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::Expr;
+    use crate::csp::ast::pred::Pred;
+    use std::rc::Rc;
+    use crate::{dom, eq, lt, neq, var};
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::truth::Truth;
+    use crate::csp::variable::extvar::generate_variables;
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::consistency::directional::{backtrack_free, dac, induced_width, AdaptiveConsistency};
+    use crate::solver::consistency::revise::AC3;
+
+    fn order(n: usize) -> Vec<String> {
+        (1..=n).map(|i| format!("x{}", i)).collect()
+    }
+
+    #[test]
+    fn induced_width_of_orderings() {
+        let vmap = generate_variables("x", 4, &dom![1, 2]);
+        let chain = Csp::new(vmap.clone(), vec![
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x1"]), var!(vmap["x2"])))),
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x2"]), var!(vmap["x3"])))),
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x3"]), var!(vmap["x4"])))),
+        ]);
+        assert_eq!(induced_width(&chain, &order(4)), 1);
+        //x3 after both its neighbours
+        let other: Vec<String> = ["x2", "x4", "x3", "x1"].iter().map(|x| x.to_string()).collect();
+        assert_eq!(induced_width(&chain, &other), 2);
+
+        let mut cycle = chain;
+        cycle.add_constraint(Rc::new(Intensional::from_pred(neq!(var!(vmap["x4"]), var!(vmap["x1"])))));
+        assert_eq!(induced_width(&cycle, &order(4)), 2);
+    }
+
+    #[test]
+    fn dac_tree_is_backtrack_free() {
+        //x2 < x1, x1 < x3, x4 != x3 with x4 = 4: a tree rooted in x1
+        let mut vmap = generate_variables("x", 3, &dom![1, 2, 3, 4]);
+        vmap.extend(generate_variables("x", 4, &dom![4]).into_iter().filter(|(x, _)| x == "x4"));
+        let mut csp = Csp::new(vmap.clone(), vec![
+            Rc::new(Intensional::from_pred(lt!(var!(vmap["x2"]), var!(vmap["x1"])))),
+            Rc::new(Intensional::from_pred(lt!(var!(vmap["x1"]), var!(vmap["x3"])))),
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x4"]), var!(vmap["x3"])))),
+        ]);
+        assert_eq!(induced_width(&csp, &order(4)), 1);
+        assert!(dac(&mut csp, &order(4), &mut AC3, &mut Statistics::default()));
+        assert_eq!(csp.vars()["x1"].valid_values(), vec![2]);
+        //only the earlier variables are filtered
+        assert_eq!(csp.vars()["x3"].valid_values(), vec![1, 2, 3]);
+
+        let solution = backtrack_free(&csp, &order(4), &mut Statistics::default()).unwrap();
+        assert_eq!(csp.is_solution(&solution), Truth::True);
+    }
+
+    #[test]
+    fn adaptive_consistency_records_parents() {
+        //x3 != x1 and x3 == x2: the greedy assignment x1 = 1, x2 = 1 is a dead end
+        let vmap = generate_variables("x", 3, &dom![1, 2]);
+        let csp = Csp::new(vmap.clone(), vec![
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x3"]), var!(vmap["x1"])))),
+            Rc::new(Intensional::from_pred(eq!(var!(vmap["x3"]), var!(vmap["x2"])))),
+        ]);
+        assert!(backtrack_free(&csp, &order(3), &mut Statistics::default()).is_none());
+
+        let mut adaptive = AdaptiveConsistency::new();
+        let network = adaptive.enforce(&csp, &order(3), &mut Statistics::default()).unwrap();
+        assert_eq!(adaptive.width, 2);
+        assert_eq!(adaptive.recorded, 1);
+        assert_eq!(network.e(), 3);
+        let solution = backtrack_free(&network, &order(3), &mut Statistics::default()).unwrap();
+        assert_eq!(csp.is_solution(&solution), Truth::True);
+    }
+
+    #[test]
+    fn adaptive_consistency_inconsistent() {
+        let vmap = generate_variables("x", 3, &dom![1, 2]);
+        let csp = Csp::new(vmap.clone(), vec![
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x1"]), var!(vmap["x2"])))),
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x2"]), var!(vmap["x3"])))),
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x1"]), var!(vmap["x3"])))),
+        ]);
+        assert!(AdaptiveConsistency::new().enforce(&csp, &order(3), &mut Statistics::default()).is_none());
+    }
+}
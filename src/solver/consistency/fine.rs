@@ -0,0 +1,644 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Fine-grained (value oriented) propagation schemes
+        - AC4: support counters of each cvalue <c, x, a> and support lists (tuples of c containing (y, b))
+        - AC6: one current support per cvalue, and the cvalues it supports for each (y, b)
+  The queue holds the deleted v-values (y, b) (domain delta), the revise function is not used.
+  Data structures are built by the first call on the current domains and kept across calls:
+  the queue is seeded with the values of the event variables removed since their last processing,
+  updates are trailed by level (see Scheme::restore_up_to). They are built again when the csp
+  gains constraints or on a backtrack to a level under the one they were built at.
+  Removals and wipeouts are explained and counted as for the coarse-grained schemes.
+***************************************/
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+use crate::csp::constraint::constraint::{cartesian_product, Constraint};
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::{Domain, OrdT};
+use crate::csp::event::TriggerEvent;
+use crate::csp::variable::extvar::ExVar;
+use crate::csp::variable::vvalue::{make_assignment, vv_from_hashmap};
+use crate::instrumentation::monitor::Monitor;
+use crate::solver::consistency::consistency::Revise;
+use crate::solver::consistency::cvalue::CValue;
+use crate::solver::consistency::scheme::Scheme;
+
+//cvalue <c, x, a> with c given by its index in csp.constraints() (labels may collide)
+type Key<T> = (usize, String, T);
+//v-values (x, a) attached to a cvalue
+type Supported<T> = HashMap<Key<T>, Vec<(Rc<ExVar<T>>, T)>>;
+type Tuple<T> = HashMap<String, T>;
+
+/**************************************
+            Delta
+***************************************/
+
+//removed v-values already propagated, common to AC4 and AC6
+struct Delta<T: OrdT> {
+    //level of the build (None: to build) and number of constraints it covers
+    root: Option<usize>,
+    constraints: usize,
+    //the last call stopped early (wipeout, interrupt): the next one seeds every variable
+    stale: bool,
+    involving: HashMap<String, Vec<usize>>,
+    propagated: HashSet<(String, T)>,
+    //(level, v-value) of each propagated removal
+    trail: Vec<(usize, String, T)>,
+}
+
+impl<T: OrdT> Delta<T> {
+    fn new() -> Self {
+        Self { root: None, constraints: 0, stale: false, involving: HashMap::new(), propagated: HashSet::new(), trail: vec![] }
+    }
+
+    fn outdated(&self, csp: &Csp<T>) -> bool {
+        self.root.is_none() || self.constraints != csp.e()
+    }
+
+    //removals of the current domains are taken as propagated
+    fn build(&mut self, csp: &Csp<T>) {
+        self.root = Some(csp.level());
+        self.constraints = csp.e();
+        self.stale = false;
+        self.involving = involving(csp.constraints());
+        self.trail.clear();
+        self.propagated = csp.vars().values()
+            .flat_map(|x| removed(x).into_iter().map(|a| (x.label().clone(), a)))
+            .collect();
+    }
+
+    //removed v-values of the event variables (of every variable if stale) not propagated yet
+    fn seed(&self, csp: &Csp<T>, events: &[TriggerEvent]) -> VecDeque<(String, T)> {
+        let labels: HashSet<&String> = match self.stale {
+            true => csp.vars().keys().collect(),
+            false => events.iter().filter_map(|e| e.variable()).collect(),
+        };
+        let mut queue = VecDeque::new();
+        for x in labels.into_iter().filter_map(|l| csp.vars().get(l)) {
+            for a in removed(x) {
+                if !self.propagated.contains(&(x.label().clone(), a.clone())) {
+                    queue.push_back((x.label().clone(), a));
+                }
+            }
+        }
+        queue
+    }
+
+    fn propagate(&mut self, level: usize, y: &str, b: &T) {
+        self.propagated.insert((y.to_string(), b.clone()));
+        self.trail.push((level, y.to_string(), b.clone()));
+    }
+
+    //false if the structures are to build again (backtrack under the build level)
+    fn restore_up_to(&mut self, lvl: usize) -> bool {
+        if self.root.is_some_and(|root| lvl <= root) {
+            self.root = None;
+            return false;
+        }
+        while self.trail.last().is_some_and(|(l, _, _)| *l >= lvl) {
+            let (_, y, b) = self.trail.pop().expect("Should not be None!");
+            self.propagated.remove(&(y, b));
+        }
+        true
+    }
+}
+
+/**************************************
+            AC4
+***************************************/
+
+pub struct AC4<T: OrdT> {
+    delta: Delta<T>,
+    //allowed tuples of each constraint on the domains of the build, and whether they are alive
+    tuples: Vec<Vec<(Vec<T>, bool)>>,
+    scopes: Vec<Vec<String>>,
+    counter: HashMap<Key<T>, usize>,
+    supports: HashMap<Key<T>, Vec<usize>>,
+    //(level, constraint, tuple) of each killed tuple
+    killed: Vec<(usize, usize, usize)>,
+}
+
+impl<T: OrdT> Default for AC4<T> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<T: OrdT> AC4<T> {
+    pub fn new() -> Self {
+        Self { delta: Delta::new(), tuples: vec![], scopes: vec![], counter: HashMap::new(), supports: HashMap::new(), killed: vec![] }
+    }
+
+    //tuples and counters on the current domains, false on domain wipeout
+    fn build<M: Monitor>(&mut self, csp: &mut Csp<T>, constraints: &[Rc<dyn Constraint<T>>],
+                         queue: &mut VecDeque<(String, T)>, monitor: &mut M) -> bool {
+        self.delta.build(csp);
+        self.tuples.clear();
+        self.scopes = constraints.iter().map(|c| c.scp().iter().map(|x| x.label().clone()).collect()).collect();
+        self.counter.clear();
+        self.supports.clear();
+        self.killed.clear();
+        for (i, c) in constraints.iter().enumerate() {
+            let mut allowed = vec![];
+            for values in cartesian_product(c.scp()) {
+                monitor.on_constraint_check();
+                if c.check_assignment(&make_assignment(c.scp(), values.clone())).to_bool().unwrap() {
+                    for (x, a) in c.scp().iter().zip(&values) {
+                        *self.counter.entry((i, x.label().clone(), a.clone())).or_insert(0) += 1;
+                        self.supports.entry((i, x.label().clone(), a.clone())).or_default().push(allowed.len());
+                    }
+                    allowed.push((values, true));
+                }
+            }
+            self.tuples.push(allowed);
+        }
+        for (i, c) in constraints.iter().enumerate() {
+            for x in c.scp() {
+                for a in x.valid_values() {
+                    if !self.counter.contains_key(&(i, x.label().clone(), a.clone()))
+                        && !delete(csp, c, x, a, queue, monitor) {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    //the tuples of c containing a deleted v-value are killed, false on domain wipeout
+    fn propagate<M: Monitor>(&mut self, csp: &mut Csp<T>, constraints: &[Rc<dyn Constraint<T>>],
+                             mut queue: VecDeque<(String, T)>, monitor: &mut M) -> bool {
+        let level = csp.level();
+        while let Some((y, b)) = queue.pop_front() {
+            monitor.on_dequeue();
+            if monitor.interrupted() {
+                monitor.on_enforce_end();
+                return false; //interrupted (see Limiter::reached)
+            }
+            self.delta.propagate(level, &y, &b);
+            for i in self.delta.involving.get(&y).cloned().unwrap_or_default() {
+                let c = &constraints[i];
+                let key = (i, y.clone(), b.clone());
+                //taken out while its tuples are killed (put back before any return)
+                let list = self.supports.remove(&key).unwrap_or_default();
+                let mut consistent = true;
+                'tuples: for &t in &list {
+                    if !self.tuples[i][t].1 {
+                        continue;
+                    }
+                    self.tuples[i][t].1 = false;
+                    self.killed.push((level, i, t));
+                    for (x, a) in c.scp().iter().zip(&self.tuples[i][t].0) {
+                        let n = self.counter.get_mut(&(i, x.label().clone(), a.clone())).expect("Counter of an allowed value");
+                        *n -= 1;
+                        if *n == 0 && *x.label() != y && x.dom().absent(a) == 0
+                            && !delete(csp, c, x, a.clone(), &mut queue, monitor) {
+                            consistent = false;
+                            break 'tuples;
+                        }
+                    }
+                }
+                self.supports.insert(key, list);
+                if !consistent {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+impl<M: Monitor, T: OrdT, R: Revise<M, T>> Scheme<M, T, R> for AC4<T> {
+    fn enforce(&mut self, csp: &mut Csp<T>, events: Vec<TriggerEvent>, _revise: &mut R, monitor: &mut M) -> bool {
+        monitor.on_enforce_start();
+        let constraints = csp.constraints().clone();
+        let mut queue = VecDeque::new();
+        let consistent = match self.delta.outdated(csp) {
+            true => self.build(csp, &constraints, &mut queue, monitor),
+            false => {
+                queue = self.delta.seed(csp, &events);
+                queue.iter().for_each(|_| monitor.on_enqueue());
+                true
+            }
+        } && self.propagate(csp, &constraints, queue, monitor);
+        self.delta.stale = !consistent;
+        if consistent {
+            monitor.on_enforce_end();
+            monitor.on_domain_snapshot(csp);
+        }
+        consistent
+    }
+
+    //killed tuples are alive again
+    fn restore_up_to(&mut self, lvl: usize) {
+        if !self.delta.restore_up_to(lvl) {
+            return;
+        }
+        while self.killed.last().is_some_and(|(l, _, _)| *l >= lvl) {
+            let (_, i, t) = self.killed.pop().expect("Should not be None!");
+            self.tuples[i][t].1 = true;
+            for (x, a) in self.scopes[i].iter().zip(&self.tuples[i][t].0) {
+                *self.counter.get_mut(&(i, x.clone(), a.clone())).expect("Counter of an allowed value") += 1;
+            }
+        }
+    }
+}
+
+/**************************************
+            AC6
+***************************************/
+
+pub struct AC6<T: OrdT> {
+    delta: Delta<T>,
+    //current support of each cvalue, and cvalues (x, a) attached to (y, b) in c
+    current: HashMap<Key<T>, Tuple<T>>,
+    supported: Supported<T>,
+    //(level, cvalue, previous current support) of each new support
+    moved: Vec<(usize, Key<T>, Option<Tuple<T>>)>,
+    //(level, list) of each attachment
+    attached: Vec<(usize, Key<T>)>,
+}
+
+impl<T: OrdT> Default for AC6<T> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<T: OrdT> AC6<T> {
+    pub fn new() -> Self {
+        Self { delta: Delta::new(), current: HashMap::new(), supported: HashMap::new(), moved: vec![], attached: vec![] }
+    }
+
+    //first support of each cvalue on the current domains, false on domain wipeout
+    fn build<M: Monitor>(&mut self, csp: &mut Csp<T>, constraints: &[Rc<dyn Constraint<T>>],
+                         queue: &mut VecDeque<(String, T)>, monitor: &mut M) -> bool {
+        self.delta.build(csp);
+        self.current.clear();
+        self.supported.clear();
+        self.moved.clear();
+        self.attached.clear();
+        let level = csp.level();
+        for (i, c) in constraints.iter().enumerate() {
+            for x in c.scp() {
+                for a in x.valid_values() {
+                    if x.dom().absent(&a) != 0 {
+                        continue; //deleted meanwhile
+                    }
+                    let cval = CValue { constraint: c.clone(), variable: x.clone(), value: a.clone() };
+                    match seek_support6(&cval, None, monitor) {
+                        Some(tau) => self.attach(level, i, &cval, tau),
+                        None => if !delete(csp, c, x, a, queue, monitor) {
+                            return false;
+                        }
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    //the cvalues supported by a deleted v-value seek their next support, false on domain wipeout
+    fn propagate<M: Monitor>(&mut self, csp: &mut Csp<T>, constraints: &[Rc<dyn Constraint<T>>],
+                             mut queue: VecDeque<(String, T)>, monitor: &mut M) -> bool {
+        let level = csp.level();
+        while let Some((y, b)) = queue.pop_front() {
+            monitor.on_dequeue();
+            if monitor.interrupted() {
+                monitor.on_enforce_end();
+                return false; //interrupted (see Limiter::reached)
+            }
+            self.delta.propagate(level, &y, &b);
+            for i in self.delta.involving.get(&y).cloned().unwrap_or_default() {
+                let c = &constraints[i];
+                let key = (i, y.clone(), b.clone());
+                //(y, b) is deleted: no attachment to it meanwhile (put back before any return)
+                let list = self.supported.remove(&key).unwrap_or_default();
+                let mut consistent = true;
+                for (x, a) in &list {
+                    if x.dom().absent(a) != 0 {
+                        continue;
+                    }
+                    let tau = &self.current[&(i, x.label().clone(), a.clone())];
+                    if tau[&y] != b {
+                        continue; //support changed since (x, a) was attached to (y, b)
+                    }
+                    let cval = CValue { constraint: c.clone(), variable: x.clone(), value: a.clone() };
+                    monitor.on_revision_check();
+                    match seek_support6(&cval, Some(tau), monitor) {
+                        Some(tau) => self.attach(level, i, &cval, tau),
+                        None => if !delete(csp, c, x, a.clone(), &mut queue, monitor) {
+                            consistent = false;
+                            break;
+                        }
+                    }
+                }
+                self.supported.insert(key, list);
+                if !consistent {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    //tau becomes the current support of cval (constraint i)
+    fn attach(&mut self, level: usize, i: usize, cval: &CValue<T>, tau: Tuple<T>) {
+        for y in cval.constraint.scp().iter().filter(|y| y.label() != cval.variable.label()) {
+            let key = (i, y.label().clone(), tau[y.label()].clone());
+            self.supported.entry(key.clone()).or_default().push((cval.variable.clone(), cval.value.clone()));
+            self.attached.push((level, key));
+        }
+        let key = (i, cval.variable.label().clone(), cval.value.clone());
+        let previous = self.current.insert(key.clone(), tau);
+        self.moved.push((level, key, previous));
+    }
+}
+
+impl<M: Monitor, T: OrdT, R: Revise<M, T>> Scheme<M, T, R> for AC6<T> {
+    fn enforce(&mut self, csp: &mut Csp<T>, events: Vec<TriggerEvent>, _revise: &mut R, monitor: &mut M) -> bool {
+        monitor.on_enforce_start();
+        let constraints = csp.constraints().clone();
+        let mut queue = VecDeque::new();
+        let consistent = match self.delta.outdated(csp) {
+            true => self.build(csp, &constraints, &mut queue, monitor),
+            false => {
+                queue = self.delta.seed(csp, &events);
+                queue.iter().for_each(|_| monitor.on_enqueue());
+                true
+            }
+        } && self.propagate(csp, &constraints, queue, monitor);
+        self.delta.stale = !consistent;
+        if consistent {
+            monitor.on_enforce_end();
+            monitor.on_domain_snapshot(csp);
+        }
+        consistent
+    }
+
+    //current supports (the first ones of their cvalue on the restored domains) and attachments are restored
+    fn restore_up_to(&mut self, lvl: usize) {
+        if !self.delta.restore_up_to(lvl) {
+            return;
+        }
+        while self.attached.last().is_some_and(|(l, _)| *l >= lvl) {
+            let (_, key) = self.attached.pop().expect("Should not be None!");
+            self.supported.get_mut(&key).and_then(|list| list.pop());
+        }
+        while self.moved.last().is_some_and(|(l, _, _)| *l >= lvl) {
+            let (_, key, previous) = self.moved.pop().expect("Should not be None!");
+            match previous {
+                Some(tau) => self.current.insert(key, tau),
+                None => self.current.remove(&key),
+            };
+        }
+    }
+}
+
+//first support of cval (after the invalid tuple from, if any, possibly still allowed by c)
+fn seek_support6<M: Monitor, T: OrdT>(cval: &CValue<T>, from: Option<&Tuple<T>>,
+                                      monitor: &mut M) -> Option<Tuple<T>> {
+    let mut tau = match from {
+        None => Some(cval.get_first_valid_tuple()),
+        Some(t) => cval.get_next_valid_tuple_limit(t, cval.constraint.get_first_invalid_pos(Some(t))),
+    };
+    while let Some(t) = tau {
+        monitor.on_constraint_check();
+        if cval.constraint.check_assignment(&vv_from_hashmap(&t)).to_bool().unwrap() {
+            return Some(t);
+        }
+        tau = cval.get_next_valid_tuple(&t);
+    }
+    None
+}
+
+/**************************************
+            Helpers
+***************************************/
+
+//indices of the constraints involving each variable
+fn involving<T: OrdT>(constraints: &[Rc<dyn Constraint<T>>]) -> HashMap<String, Vec<usize>> {
+    let mut involving: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, c) in constraints.iter().enumerate() {
+        for x in c.scp() {
+            involving.entry(x.label().clone()).or_default().push(i);
+        }
+    }
+    involving
+}
+
+//values removed from dom(x)
+fn removed<T: OrdT>(x: &ExVar<T>) -> Vec<T> {
+    let dom = x.dom();
+    dom.get_initial_values().iter().filter(|a| dom.absent(a) != 0).cloned().collect()
+}
+
+//removes (x, a) with no support in c, false on domain wipeout
+fn delete<M: Monitor, T: OrdT>(csp: &mut Csp<T>, c: &Rc<dyn Constraint<T>>, x: &ExVar<T>, a: T,
+                               queue: &mut VecDeque<(String, T)>, monitor: &mut M) -> bool {
+    monitor.on_value_deleted();
+    x.dom_mut().remove_value(&a, csp.level());
+    csp.explain_removals(c, x, vec![a.clone()]);
+    if x.dom().is_empty() {
+        csp.explain_wipeout(c, x);
+        monitor.on_domain_wipeout();
+        monitor.on_enforce_end();
+        monitor.on_domain_snapshot(csp);
+        return false;
+    }
+    monitor.on_enqueue();
+    queue.push_back((x.label().clone(), a));
+    true
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::{Expr, AExpr};
+    use crate::csp::ast::pred::Pred;
+    use crate::csp::ast::formula::Formula;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use crate::{add, and, atom, base, cst, dom, eq, lt, neq, or, var, var_dom};
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::extensional::ExtConstraint;
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::domain::Domain;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::event::reduced;
+    use crate::csp::variable::extvar::{generate_variables, ExVar};
+    use crate::csp::variable::vvalue::vv;
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::fine::{AC4, AC6};
+    use crate::solver::consistency::revise::{AC1, AC3};
+    use crate::solver::consistency::scheme::{ArcOriented, Scheme};
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::limits::SearchLimits;
+
+    //domino example (see scheme.rs)
+    fn domino() -> Csp<i32> {
+        let dom = SetDom::new(vec![0, 1, 2, 3]);
+        let w = var_dom!("w".into(), dom.snapshot());
+        let x = var_dom!("x".into(), dom.snapshot());
+        let y = var_dom!("y".into(), dom.snapshot());
+        let z = var_dom!("z".into(), dom);
+        let mut vmap: HashMap<String, _> = HashMap::new();
+        vmap.insert("w".into(), w.clone());
+        vmap.insert("x".into(), x.clone());
+        vmap.insert("y".into(), y.clone());
+        vmap.insert("z".into(), z.clone());
+
+        let c1 = Intensional::from_pred(eq!(var!(w), var!(x)));
+        let c2 = Intensional::from_pred(eq!(var!(x), var!(y)));
+        let c3 = Intensional::from_pred(eq!(var!(y), var!(z)));
+        // (w == z + 1) OR (w == z AND w == 3)
+        let c4 = Intensional::new(vec![w.clone(), z.clone()], Rc::new(or!(
+            atom!(eq!(base!(var!(w)), add!(base!(var!(z)), base!(cst!(1))))),
+            and!(
+                atom!(eq!(base!(var!(w)), base!(var!(z)))),
+                atom!(eq!(base!(var!(w)), base!(cst!(3))))
+            ))));
+        Csp::new(vmap, vec![Rc::new(c1), Rc::new(c2), Rc::new(c3), Rc::new(c4)])
+    }
+
+    #[test]
+    fn domino_example_ac4() {
+        let mut csp = domino();
+        let vars = reduced(csp.vars().keys());
+        let mut stats = Statistics::default();
+        assert!(AC4::new().enforce(&mut csp, vars, &mut AC1, &mut stats));
+        assert!(csp.vars().values().all(|v| v.dom().active_values() == vec![3]));
+        //3 values deleted from each variable
+        assert_eq!(stats.value_deletions, 12);
+    }
+
+    #[test]
+    fn domino_example_ac6() {
+        let mut csp = domino();
+        let vars = reduced(csp.vars().keys());
+        let mut stats = Statistics::default();
+        assert!(AC6::new().enforce(&mut csp, vars, &mut AC1, &mut stats));
+        assert!(csp.vars().values().all(|v| v.dom().active_values() == vec![3]));
+        assert_eq!(stats.value_deletions, 12);
+
+        //fewer constraint checks than the coarse-grained AC3 (same counter)
+        let mut ac3 = Statistics::default();
+        let mut csp = domino();
+        let vars = reduced(csp.vars().keys());
+        assert!(ArcOriented.enforce(&mut csp, vars, &mut AC3, &mut ac3));
+        assert!(stats.checks < ac3.checks);
+    }
+
+    #[test]
+    fn fine_grained_wipeout() {
+        //x < y < z on {1, 2}
+        let vmap = generate_variables("x", 3, &dom![1, 2]);
+        let mut csp = Csp::new(vmap.clone(), vec![
+            Rc::new(Intensional::from_pred(lt!(var!(vmap["x1"]), var!(vmap["x2"])))),
+            Rc::new(Intensional::from_pred(lt!(var!(vmap["x2"]), var!(vmap["x3"])))),
+        ]);
+        let vars = reduced(csp.vars().keys());
+        assert!(!AC4::new().enforce(&mut csp, vars.clone(), &mut AC1, &mut Statistics::default()));
+        assert!(csp.explanations().conflict().is_some());
+        for v in csp.vars().values() {
+            v.dom_mut().restore_up_to(0);
+        }
+        assert!(!AC6::new().enforce(&mut csp, vars, &mut AC1, &mut Statistics::default()));
+    }
+
+    #[test]
+    fn search_fine_grained() {
+        //x1 != x2, x2 != x3, x1 < x3 on {1, 2, 3}: (1,2,3), (1,3,2), (2,1,3)
+        let model = || {
+            let vmap = generate_variables("x", 3, &dom![1, 2, 3]);
+            Csp::new(vmap.clone(), vec![
+                Rc::new(Intensional::from_pred(neq!(var!(vmap["x1"]), var!(vmap["x2"])))),
+                Rc::new(Intensional::from_pred(neq!(var!(vmap["x2"]), var!(vmap["x3"])))),
+                Rc::new(Intensional::from_pred(lt!(var!(vmap["x1"]), var!(vmap["x3"])))),
+            ])
+        };
+        let mut engine = Engine::new(Consistency::new(ArcOriented, AC1, Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut model()).is_sat());
+        let expected = engine.limiter().solutions;
+        assert_eq!(expected, 3);
+
+        let mut engine = Engine::new(Consistency::new(AC4::new(), AC1, Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut model()).is_sat());
+        assert_eq!(engine.limiter().solutions, expected);
+
+        let mut engine = Engine::new(Consistency::new(AC6::new(), AC1, Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut model()).is_sat());
+        assert_eq!(engine.limiter().solutions, expected);
+    }
+
+    //x1 < x2 < x3 on {1, 2, 3, 4} as tables (stale supports are still allowed tuples)
+    fn tables() -> Csp<i32> {
+        let vmap = generate_variables("x", 3, &dom![1, 2, 3, 4]);
+        let lt = |x: &str, y: &str| -> Rc<dyn Constraint<i32>> {
+            let tuples = (1..=4).flat_map(|a| ((a + 1)..=4).map(move |b| (a, b)))
+                .map(|(a, b)| vec![vv(x.into(), a), vv(y.into(), b)])
+                .collect();
+            Rc::new(ExtConstraint::new(vec![vmap[x].clone(), vmap[y].clone()], tuples))
+        };
+        let constraints = vec![lt("x1", "x2"), lt("x2", "x3")];
+        Csp::new(vmap.clone(), constraints)
+    }
+
+    #[test]
+    fn fine_grained_tables() {
+        let mut csp = tables();
+        let vars = reduced(csp.vars().keys());
+        assert!(AC6::new().enforce(&mut csp, vars, &mut AC1, &mut Statistics::default()));
+        assert_eq!(csp.vars()["x1"].valid_values(), vec![1, 2]);
+        assert_eq!(csp.vars()["x2"].valid_values(), vec![2, 3]);
+        assert_eq!(csp.vars()["x3"].valid_values(), vec![3, 4]);
+
+        //4 solutions: (1,2,3), (1,2,4), (1,3,4), (2,3,4)
+        let mut engine = Engine::new(Consistency::new(AC4::new(), AC1, Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut tables()).is_sat());
+        assert_eq!(engine.limiter().solutions, 4);
+
+        let mut engine = Engine::new(Consistency::new(AC6::new(), AC1, Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut tables()).is_sat());
+        assert_eq!(engine.limiter().solutions, 4);
+    }
+
+    #[test]
+    fn fine_grained_incremental() {
+        //AC4: every constraint check is done by the first call
+        let mut csp = tables();
+        let mut engine = Engine::new(Consistency::new(AC4::new(), AC1, Statistics::default()));
+        assert!(engine.propagate(&mut csp));
+        let checks = engine.monitor().checks;
+        assert!(engine.decide(&mut csp, vv("x2".into(), 3)));
+        assert_eq!(csp.vars()["x1"].valid_values(), vec![1, 2]);
+        assert_eq!(csp.vars()["x3"].valid_values(), vec![4]);
+        assert_eq!(engine.monitor().checks, checks);
+
+        //undone: the killed tuples are alive again, x2 = 2 is propagated from the restored counters
+        engine.undo(&mut csp);
+        assert!(engine.decide(&mut csp, vv("x2".into(), 2)));
+        assert_eq!(csp.vars()["x1"].valid_values(), vec![1]);
+        assert_eq!(csp.vars()["x3"].valid_values(), vec![3, 4]);
+        assert_eq!(engine.monitor().checks, checks);
+
+        //AC6: supports are sought from the current ones
+        let mut csp = tables();
+        let mut engine = Engine::new(Consistency::new(AC6::new(), AC1, Statistics::default()));
+        assert!(engine.propagate(&mut csp));
+        let checks = engine.monitor().checks;
+        assert!(engine.decide(&mut csp, vv("x2".into(), 3)));
+        assert_eq!(csp.vars()["x3"].valid_values(), vec![4]);
+        assert!(engine.monitor().checks - checks < checks);
+        engine.undo(&mut csp);
+        assert!(engine.decide(&mut csp, vv("x2".into(), 2)));
+        assert_eq!(csp.vars()["x1"].valid_values(), vec![1]);
+        assert_eq!(csp.vars()["x3"].valid_values(), vec![3, 4]);
+    }
+}
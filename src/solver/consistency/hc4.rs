@@ -0,0 +1,314 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: HC4-revise over the AST of intensional constraints (Formula<AExpr<T>>, Formula<Expr<T>>)
+    - forward pass: intervals of the AExpr nodes, from the domain bounds of the Expr::Var leaves
+    - backward pass: the predicate narrows the intervals of its sides, which are projected down
+      to the leaves (x = n - y for an addition, ...)
+    - the projections reaching the leaves of the revised variable filter its domain
+  The multiplication is not inverted (ArithT has no division): a variable operand keeps the values v
+  such that v * [other operand] meets the projection, compound operands are not narrowed.
+  Lt / Gt are projected as Le / Ge, Neq only removes the value of a fixed side.
+  Conjunctions of predicates only, the other constraints (and the last free variable of a constraint,
+  see bounds.rs) are revised by AC3rm.
+***************************************/
+
+/**************************************
+            Forward / Backward
+***************************************/
+use std::rc::Rc;
+use crate::csp::ast::expr::{AExpr, ArithT, Expr};
+use crate::csp::ast::formula::Formula;
+use crate::csp::ast::pred::Pred;
+use crate::csp::constraint::constraint::Constraint;
+use crate::csp::domain::domain::Domain;
+use crate::csp::event::EventKind;
+use crate::instrumentation::monitor::Monitor;
+use crate::solver::consistency::arc::Arc;
+use crate::solver::consistency::bounds::{conjuncts, others_fixed, reacts_to_bounds, Interval};
+use crate::solver::consistency::revise::{AC3rm, Revise};
+
+//interval of each node of an expression, children first
+struct Node<T: ArithT> {
+    interval: Interval<T>,
+    children: Vec<Node<T>>,
+}
+
+//forward pass, None if a domain is empty
+fn forward<T: ArithT>(e: &AExpr<T>) -> Option<Node<T>> {
+    let node = |interval, children| Some(Node { interval, children });
+    match e {
+        AExpr::Base(Expr::Const(c)) => node(Interval::point(c.clone()), vec![]),
+        AExpr::Base(Expr::Var(v)) => node(Interval::new(v.dom().min()?, v.dom().max()?), vec![]),
+        AExpr::Add(l, r) | AExpr::Sub(l, r) | AExpr::Mul(l, r) => {
+            let (l, r) = (forward(l)?, forward(r)?);
+            let interval = match e {
+                AExpr::Add(_, _) => l.interval.add(&r.interval),
+                AExpr::Sub(_, _) => l.interval.sub(&r.interval),
+                _ => l.interval.mul(&r.interval),
+            };
+            node(interval, vec![l, r])
+        }
+    }
+}
+
+//what the projections require from the values of the revised variable
+enum Filter<T: ArithT> {
+    Within(Interval<T>),
+    //v * other meets target
+    Product(Interval<T>, Interval<T>),
+    Not(T),
+    //the constraint cannot hold
+    Never,
+}
+
+impl<T: ArithT> Filter<T> {
+    fn accepts(&self, v: &T) -> bool {
+        match self {
+            Filter::Within(i) => i.contains(v),
+            Filter::Product(other, target) => Interval::point(v.clone()).mul(other).intersects(target),
+            Filter::Not(a) => v != a,
+            Filter::Never => false,
+        }
+    }
+}
+
+fn is_var<T: ArithT>(e: &AExpr<T>, x: &str) -> bool {
+    matches!(e, AExpr::Base(Expr::Var(v)) if v.label() == x)
+}
+
+//backward pass: node of e must lie in target
+fn backward<T: ArithT>(e: &AExpr<T>, node: &Node<T>, target: &Interval<T>, x: &str, filters: &mut Vec<Filter<T>>) {
+    let target = match node.interval.intersect(target) {
+        Some(t) => t,
+        None => return filters.push(Filter::Never),
+    };
+    match e {
+        AExpr::Base(Expr::Const(_)) => {}
+        AExpr::Base(Expr::Var(v)) => if v.label() == x {
+            filters.push(Filter::Within(target));
+        }
+        AExpr::Add(l, r) => {
+            let (nl, nr) = (&node.children[0], &node.children[1]);
+            backward(l, nl, &target.sub(&nr.interval), x, filters);
+            backward(r, nr, &target.sub(&nl.interval), x, filters);
+        }
+        AExpr::Sub(l, r) => {
+            let (nl, nr) = (&node.children[0], &node.children[1]);
+            backward(l, nl, &target.add(&nr.interval), x, filters);
+            backward(r, nr, &nl.interval.sub(&target), x, filters);
+        }
+        AExpr::Mul(l, r) => {
+            let (nl, nr) = (&node.children[0], &node.children[1]);
+            if is_var(l, x) {
+                filters.push(Filter::Product(nr.interval.clone(), target.clone()));
+            }
+            if is_var(r, x) {
+                filters.push(Filter::Product(nl.interval.clone(), target));
+            }
+        }
+    }
+}
+
+//narrows both sides of the predicate then projects them
+fn project<T: ArithT>(p: &Pred<AExpr<T>>, x: &str, filters: &mut Vec<Filter<T>>) {
+    let (l, r) = match p {
+        Pred::Eq(l, r) | Pred::Neq(l, r) | Pred::Lt(l, r)
+        | Pred::Le(l, r) | Pred::Gt(l, r) | Pred::Ge(l, r) => (l, r),
+    };
+    let (nl, nr) = match (forward(l), forward(r)) {
+        (Some(nl), Some(nr)) => (nl, nr),
+        _ => return filters.push(Filter::Never),
+    };
+    let (il, ir) = (&nl.interval, &nr.interval);
+    //l <= r
+    let le = |il: &Interval<T>, ir: &Interval<T>| (
+        Interval::new(il.min.clone(), il.max.clone().min(ir.max.clone())),
+        Interval::new(ir.min.clone().max(il.min.clone()), ir.max.clone()));
+    let (tl, tr) = match p {
+        Pred::Eq(_, _) => match il.intersect(ir) {
+            Some(i) => (i.clone(), i),
+            None => return filters.push(Filter::Never),
+        },
+        Pred::Le(_, _) | Pred::Lt(_, _) => le(il, ir),
+        Pred::Ge(_, _) | Pred::Gt(_, _) => {
+            let (tr, tl) = le(ir, il);
+            (tl, tr)
+        }
+        Pred::Neq(_, _) => {
+            if ir.is_point() && is_var(l, x) {
+                filters.push(Filter::Not(ir.min.clone()));
+            }
+            if il.is_point() && is_var(r, x) {
+                filters.push(Filter::Not(il.min.clone()));
+            }
+            return;
+        }
+    };
+    let strict = matches!(p, Pred::Lt(_, _) | Pred::Gt(_, _));
+    let empty = match p {
+        Pred::Le(_, _) | Pred::Lt(_, _) => il.min > ir.max || (strict && il.min == ir.max),
+        Pred::Ge(_, _) | Pred::Gt(_, _) => il.max < ir.min || (strict && il.max == ir.min),
+        _ => false,
+    };
+    if empty {
+        return filters.push(Filter::Never);
+    }
+    backward(l, &nl, &tl, x, filters);
+    backward(r, &nr, &tr, x, filters);
+}
+
+//Expr predicates as AExpr ones
+fn lift<T: ArithT>(p: &Pred<Expr<T>>) -> Pred<AExpr<T>> {
+    let b = |e: &Expr<T>| AExpr::Base(e.clone());
+    match p {
+        Pred::Eq(l, r) => Pred::Eq(b(l), b(r)),
+        Pred::Neq(l, r) => Pred::Neq(b(l), b(r)),
+        Pred::Lt(l, r) => Pred::Lt(b(l), b(r)),
+        Pred::Le(l, r) => Pred::Le(b(l), b(r)),
+        Pred::Gt(l, r) => Pred::Gt(b(l), b(r)),
+        Pred::Ge(l, r) => Pred::Ge(b(l), b(r)),
+    }
+}
+
+/**************************************
+            HC4
+***************************************/
+
+pub struct HC4<T: ArithT> {
+    //revise of the other constraints
+    fallback: AC3rm<T>,
+}
+
+impl<T: ArithT> Default for HC4<T> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<T: ArithT> HC4<T> {
+    pub fn new() -> Self {
+        Self { fallback: AC3rm::new() }
+    }
+}
+
+impl<M: Monitor, T: ArithT + 'static> Revise<M, T> for HC4<T> {
+    fn revise(&mut self, arc: &Arc<T>, level: usize, monitor: &mut M) -> bool {
+        if others_fixed(arc) {
+            return self.fallback.revise(arc, level, monitor);
+        }
+        let expression = arc.constraint.expression();
+        let preds: Vec<Pred<AExpr<T>>> =
+            if let Some(preds) = expression.and_then(|e| e.downcast_ref::<Formula<AExpr<T>>>()).and_then(conjuncts) {
+                preds.into_iter().cloned().collect()
+            } else if let Some(preds) = expression.and_then(|e| e.downcast_ref::<Formula<Expr<T>>>()).and_then(conjuncts) {
+                preds.into_iter().map(lift).collect()
+            } else {
+                return self.fallback.revise(arc, level, monitor);
+            };
+
+        monitor.on_revision_check();
+        monitor.on_constraint_check();
+        let x = &arc.variable;
+        let mut filters = vec![];
+        for p in &preds {
+            project(p, x.label(), &mut filters);
+        }
+        let size_before = x.dom().size();
+        for a in x.valid_values() {
+            if !filters.iter().all(|f| f.accepts(&a)) {
+                monitor.on_value_deleted();
+                x.dom_mut().remove_value(&a, level);
+            }
+        }
+        size_before != x.dom().size()
+    }
+
+    fn restore_up_to(&mut self, lvl: usize) {
+        Revise::<M, T>::restore_up_to(&mut self.fallback, lvl);
+    }
+
+    fn reacts_to(&self, c: &Rc<dyn Constraint<T>>, kind: EventKind) -> bool {
+        reacts_to_bounds(c, kind)
+    }
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::{Expr, AExpr};
+    use crate::csp::ast::pred::Pred;
+    use crate::csp::ast::formula::Formula;
+    use std::rc::Rc;
+    use crate::{add, and, atom, base, cst, eq, lt, mul, neq, sub, var, var_dom};
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::variable::extvar::{generate_variables, ExVar};
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::consistency::arc::Arc;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::hc4::HC4;
+    use crate::solver::consistency::revise::{Revise, AC1};
+    use crate::solver::consistency::scheme::ArcOriented;
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::limits::SearchLimits;
+
+    #[test]
+    fn hc4_projection() {
+        //x - (y + z) == 0, y, z in [1, 2]
+        let x = var_dom!("x".into(), SetDom::new((0..=10).collect()));
+        let y = var_dom!("y".into(), SetDom::new(vec![1, 2]));
+        let z = var_dom!("z".into(), SetDom::new(vec![1, 2]));
+        let c = Rc::new(Intensional::from_pred(eq!(
+            sub!(base!(var!(x)), add!(base!(var!(y)), base!(var!(z)))), base!(cst!(0)))));
+        let mut hc4 = HC4::new();
+        let mut monitor = Statistics::default();
+        assert!(hc4.revise(&Arc { constraint: c, variable: x.clone() }, 0, &mut monitor));
+        assert_eq!(x.valid_values(), vec![2, 3, 4]);
+        //one evaluation of the tree
+        assert_eq!(monitor.checks, 1);
+    }
+
+    #[test]
+    fn hc4_product_and_conjunction() {
+        //3 * x == y && x != 3, y in [6, 9]
+        let x = var_dom!("x".into(), SetDom::new((0..=10).collect()));
+        let y = var_dom!("y".into(), SetDom::new((6..=9).collect()));
+        let three = var_dom!("three".into(), SetDom::new(vec![3]));
+        let c = Rc::new(Intensional::from_formula(Rc::new(and!(
+            atom!(eq!(mul!(base!(cst!(3)), base!(var!(x))), base!(var!(y)))),
+            atom!(neq!(base!(var!(x)), base!(var!(three))))))));
+        let mut hc4 = HC4::new();
+        assert!(hc4.revise(&Arc { constraint: c.clone(), variable: x.clone() }, 0, &mut Statistics::default()));
+        assert_eq!(x.valid_values(), vec![2]);
+        assert!(hc4.revise(&Arc { constraint: c, variable: y.clone() }, 0, &mut Statistics::default()));
+        assert_eq!(y.valid_values(), vec![6]);
+    }
+
+    #[test]
+    fn hc4_search() {
+        //x + y == z, x < y on [0, 5]: same solutions as AC1
+        let model = || {
+            let vmap = generate_variables("x", 3, &SetDom::new((0..=5).collect()));
+            let constraints: Vec<Rc<dyn Constraint<i32>>> = vec![
+                Rc::new(Intensional::from_pred(eq!(
+                    add!(base!(var!(vmap["x1"])), base!(var!(vmap["x2"]))), base!(var!(vmap["x3"]))))),
+                Rc::new(Intensional::from_pred(lt!(var!(vmap["x1"]), var!(vmap["x2"])))),
+            ];
+            Csp::new(vmap, constraints)
+        };
+        let mut engine = Engine::new(Consistency::new(ArcOriented, AC1, Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut model()).is_sat());
+        let expected = engine.limiter().solutions;
+        //(0,1..5), (1,2..4), (2,3)
+        assert_eq!(expected, 9);
+
+        let mut engine = Engine::new(Consistency::new(ArcOriented, HC4::new(), Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut model()).is_sat());
+        assert_eq!(engine.limiter().solutions, expected);
+    }
+}
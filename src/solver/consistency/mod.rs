@@ -2,5 +2,13 @@ pub mod arc;
 mod fc;
 pub mod consistency;
 pub mod scheme;
+pub mod fine;
+pub mod bounds;
+pub mod hc4;
+pub mod sac;
+pub mod path;
+pub mod rpc;
+pub mod propagator;
+pub mod directional;
 pub mod revise;
 pub mod cvalue;
\ No newline at end of file
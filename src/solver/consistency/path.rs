@@ -0,0 +1,359 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Path consistency (PC) of binary networks
+    the network is completed: every pair of variables {i, j} gets a relation R_ij
+    (the allowed tuples of its constraints in Csp::primal_graph, D_i x D_j otherwise)
+    - PC-2: queue of paths (i, k, j), R_ij <- R_ij & (R_ik o R_kj)
+    - PC-8: queue of v-values (i, a, j) having lost a pair in R_ij,
+            only the pairs (a, b) of the relations R_ik are revised through j
+  The tightened network is returned as a new Csp: fresh variables (values without support removed)
+  and one ExtConstraint per constrained pair or induced pair (R_ij strictly included in D_i x D_j).
+***************************************/
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+use crate::csp::constraint::constraint::Constraint;
+use crate::csp::constraint::extensional::ExtConstraint;
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::OrdT;
+use crate::csp::domain::setdom::SetDom;
+use crate::csp::variable::extvar::ExVar;
+use crate::csp::variable::vvalue::make_assignment;
+use crate::instrumentation::monitor::Monitor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathVariant {
+    PC2,
+    PC8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathError {
+    //a constraint is not binary
+    NotBinary,
+    //a relation became empty
+    Inconsistent,
+}
+
+pub struct PathConsistency {
+    pub variant: PathVariant,
+    //pairs removed from the relations by the last enforce
+    pub removed_pairs: usize,
+}
+
+//relations R_ij (i < j) of the completed network, pairs (a, b) with a in D_i, b in D_j
+struct Network<T: OrdT> {
+    labels: Vec<String>,
+    doms: Vec<Vec<T>>,
+    rels: HashMap<(usize, usize), BTreeSet<(T, T)>>,
+}
+
+impl<T: OrdT> Network<T> {
+    fn allowed(&self, i: usize, j: usize, a: &T, b: &T) -> bool {
+        if i < j {
+            self.rels[&(i, j)].contains(&(a.clone(), b.clone()))
+        } else {
+            self.rels[&(j, i)].contains(&(b.clone(), a.clone()))
+        }
+    }
+
+    fn forbid(&mut self, i: usize, j: usize, a: &T, b: &T) {
+        if i < j {
+            self.rels.get_mut(&(i, j)).unwrap().remove(&(a.clone(), b.clone()));
+        } else {
+            self.rels.get_mut(&(j, i)).unwrap().remove(&(b.clone(), a.clone()));
+        }
+    }
+
+    fn is_empty(&self, i: usize, j: usize) -> bool {
+        self.rels[&(i.min(j), i.max(j))].is_empty()
+    }
+
+    //pairs (a, b) of R_ij oriented from i
+    fn pairs(&self, i: usize, j: usize) -> Vec<(T, T)> {
+        if i < j {
+            self.rels[&(i, j)].iter().cloned().collect()
+        } else {
+            self.rels[&(j, i)].iter().map(|(b, a)| (a.clone(), b.clone())).collect()
+        }
+    }
+
+    //is (a, b) of R_ij supported by some c of D_k (R_ik and R_kj)
+    fn has_path<M: Monitor>(&self, i: usize, j: usize, k: usize, a: &T, b: &T, monitor: &mut M) -> bool {
+        self.doms[k].iter().any(|c| {
+            monitor.on_constraint_check();
+            self.allowed(i, k, a, c) && self.allowed(k, j, c, b)
+        })
+    }
+}
+
+impl PathConsistency {
+    pub fn new(variant: PathVariant) -> Self {
+        Self { variant, removed_pairs: 0 }
+    }
+
+    //path consistent network equivalent to csp (current domains)
+    pub fn enforce<M: Monitor, T: OrdT + 'static>(&mut self, csp: &Csp<T>, monitor: &mut M) -> Result<Csp<T>, PathError> {
+        self.removed_pairs = 0;
+        if csp.constraints().iter().any(|c| c.scp().len() != 2) {
+            return Err(PathError::NotBinary);
+        }
+        monitor.on_enforce_start();
+        let (mut network, explicit) = complete(csp, monitor);
+        let n = network.labels.len();
+        if network.rels.values().any(|r| r.is_empty()) {
+            monitor.on_enforce_end();
+            return Err(PathError::Inconsistent);
+        }
+
+        let consistent = match self.variant {
+            PathVariant::PC2 => self.pc2(&mut network, monitor),
+            PathVariant::PC8 => self.pc8(&mut network, monitor),
+        };
+        monitor.on_enforce_end();
+        if !consistent {
+            return Err(PathError::Inconsistent);
+        }
+
+        //values without support in some relation
+        let mut doms = network.doms.clone();
+        for (i, dom) in doms.iter_mut().enumerate() {
+            dom.retain(|a| (0..n).filter(|j| *j != i).all(|j| network.pairs(i, j).iter().any(|(b, _)| b == a)));
+            if dom.is_empty() {
+                return Err(PathError::Inconsistent);
+            }
+        }
+        Ok(tightened(&network, &doms, &explicit))
+    }
+
+    fn pc2<M: Monitor, T: OrdT>(&mut self, network: &mut Network<T>, monitor: &mut M) -> bool {
+        let n = network.labels.len();
+        let mut queue: VecDeque<(usize, usize, usize)> = VecDeque::new();
+        let mut queued: HashSet<(usize, usize, usize)> = HashSet::new();
+        for i in 0..n {
+            for j in i + 1..n {
+                for k in (0..n).filter(|k| *k != i && *k != j) {
+                    monitor.on_enqueue();
+                    queue.push_back((i, j, k));
+                    queued.insert((i, j, k));
+                }
+            }
+        }
+
+        while let Some((i, j, k)) = queue.pop_front() {
+            monitor.on_dequeue();
+            queued.remove(&(i, j, k));
+            let mut changed = false;
+            for (a, b) in network.pairs(i, j) {
+                if !network.has_path(i, j, k, &a, &b, monitor) {
+                    network.forbid(i, j, &a, &b);
+                    self.removed_pairs += 1;
+                    changed = true;
+                }
+            }
+            if !changed {
+                monitor.on_revise_fruitless();
+                continue;
+            }
+            if network.is_empty(i, j) {
+                monitor.on_domain_wipeout();
+                return false;
+            }
+            //paths going through R_ij
+            for l in (0..n).filter(|l| *l != i && *l != j) {
+                for path in [(i.min(l), i.max(l), j), (j.min(l), j.max(l), i)] {
+                    if queued.insert(path) {
+                        monitor.on_enqueue();
+                        queue.push_back(path);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn pc8<M: Monitor, T: OrdT>(&mut self, network: &mut Network<T>, monitor: &mut M) -> bool {
+        let n = network.labels.len();
+        let mut queue: VecDeque<(usize, T, usize)> = VecDeque::new();
+        let mut queued: HashSet<(usize, T, usize)> = HashSet::new();
+        for i in 0..n {
+            for a in network.doms[i].clone() {
+                for j in (0..n).filter(|j| *j != i) {
+                    monitor.on_enqueue();
+                    queue.push_back((i, a.clone(), j));
+                    queued.insert((i, a.clone(), j));
+                }
+            }
+        }
+
+        while let Some((i, a, j)) = queue.pop_front() {
+            monitor.on_dequeue();
+            queued.remove(&(i, a.clone(), j));
+            for k in (0..n).filter(|k| *k != i && *k != j) {
+                for b in network.doms[k].clone() {
+                    if !network.allowed(i, k, &a, &b) || network.has_path(i, k, j, &a, &b, monitor) {
+                        continue;
+                    }
+                    network.forbid(i, k, &a, &b);
+                    self.removed_pairs += 1;
+                    if network.is_empty(i, k) {
+                        monitor.on_domain_wipeout();
+                        return false;
+                    }
+                    for e in [(i, a.clone(), k), (k, b.clone(), i)] {
+                        if queued.insert(e.clone()) {
+                            monitor.on_enqueue();
+                            queue.push_back(e);
+                        }
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+//completed network on the current domains, and the explicit pairs (edges of the primal graph)
+fn complete<M: Monitor, T: OrdT>(csp: &Csp<T>, monitor: &mut M) -> (Network<T>, HashSet<(usize, usize)>) {
+    let mut labels: Vec<String> = csp.vars().keys().cloned().collect();
+    labels.sort();
+    let index: HashMap<&String, usize> = labels.iter().enumerate().map(|(i, x)| (x, i)).collect();
+    let doms: Vec<Vec<T>> = labels.iter().map(|x| csp.vars()[x].valid_values()).collect();
+
+    let graph = csp.primal_graph();
+    let explicit: HashSet<(usize, usize)> = graph.edge_indices()
+        .filter_map(|e| graph.edge_endpoints(e))
+        .map(|(u, v)| (index[&graph[u]], index[&graph[v]]))
+        .map(|(i, j)| (i.min(j), i.max(j)))
+        .collect();
+
+    let mut rels = HashMap::new();
+    for i in 0..labels.len() {
+        for j in i + 1..labels.len() {
+            let constraints: Vec<&Rc<dyn Constraint<T>>> = csp.constraints().iter()
+                .filter(|c| c.scp().iter().any(|x| x.label() == &labels[i])
+                    && c.scp().iter().any(|x| x.label() == &labels[j]))
+                .collect();
+            let mut rel = BTreeSet::new();
+            for a in &doms[i] {
+                for b in &doms[j] {
+                    let allowed = constraints.iter().all(|c| {
+                        monitor.on_constraint_check();
+                        let values = c.scp().iter()
+                            .map(|x| if x.label() == &labels[i] { a.clone() } else { b.clone() })
+                            .collect();
+                        c.check_assignment(&make_assignment(c.scp(), values)).to_bool().unwrap()
+                    });
+                    if allowed {
+                        rel.insert((a.clone(), b.clone()));
+                    }
+                }
+            }
+            rels.insert((i, j), rel);
+        }
+    }
+    (Network { labels, doms, rels }, explicit)
+}
+
+fn tightened<T: OrdT + 'static>(network: &Network<T>, doms: &[Vec<T>], explicit: &HashSet<(usize, usize)>) -> Csp<T> {
+    let vars: Vec<Rc<ExVar<T>>> = network.labels.iter().zip(doms)
+        .map(|(x, dom)| Rc::new(ExVar::new(x.clone(), SetDom::new(dom.clone()))))
+        .collect();
+    let mut constraints: Vec<Rc<dyn Constraint<T>>> = vec![];
+    for (&(i, j), rel) in network.rels.iter().collect::<std::collections::BTreeMap<_, _>>() {
+        let rel: Vec<(T, T)> = rel.iter()
+            .filter(|(a, b)| doms[i].contains(a) && doms[j].contains(b))
+            .cloned()
+            .collect();
+        if explicit.contains(&(i, j)) || rel.len() < doms[i].len() * doms[j].len() {
+            let scope = vec![vars[i].clone(), vars[j].clone()];
+            let tuples = rel.into_iter().map(|(a, b)| make_assignment(&scope, vec![a, b])).collect();
+            constraints.push(Rc::new(ExtConstraint::new(scope, tuples)));
+        }
+    }
+    let vmap = vars.into_iter().map(|x| (x.label().clone(), x)).collect();
+    Csp::new(vmap, constraints)
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::Expr;
+    use crate::csp::ast::pred::Pred;
+    use std::rc::Rc;
+    use crate::{dom, lt, neq, var};
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::variable::extvar::generate_variables;
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::path::{PathConsistency, PathError, PathVariant};
+    use crate::solver::consistency::revise::AC1;
+    use crate::solver::consistency::scheme::ArcOriented;
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::limits::SearchLimits;
+
+    //x1 < x2 < x3 on {1, 2, 3, 4}, no constraint between x1 and x3
+    fn chain() -> Csp<i32> {
+        let vmap = generate_variables("x", 3, &dom![1, 2, 3, 4]);
+        Csp::new(vmap.clone(), vec![
+            Rc::new(Intensional::from_pred(lt!(var!(vmap["x1"]), var!(vmap["x2"])))),
+            Rc::new(Intensional::from_pred(lt!(var!(vmap["x2"]), var!(vmap["x3"])))),
+        ])
+    }
+
+    fn relations(csp: &Csp<i32>) -> Vec<(String, String)> {
+        let mut rels: Vec<(String, String)> = csp.constraints().iter()
+            .map(|c| (c.label(), format!("{:?}", c.rel())))
+            .collect();
+        rels.sort();
+        rels
+    }
+
+    #[test]
+    fn pc_induced_constraint() {
+        for variant in [PathVariant::PC2, PathVariant::PC8] {
+            let mut pc = PathConsistency::new(variant);
+            let tight = pc.enforce(&chain(), &mut Statistics::default()).unwrap();
+            assert_eq!(tight.e(), 3);
+            assert_eq!(tight.vars()["x1"].valid_values(), vec![1, 2]);
+            assert_eq!(tight.vars()["x2"].valid_values(), vec![2, 3]);
+            assert_eq!(tight.vars()["x3"].valid_values(), vec![3, 4]);
+            //induced x1 + 2 <= x3
+            let induced = tight.constraints().iter().find(|c| c.label() == "c_x1x3").unwrap();
+            assert_eq!(induced.rel().len(), 3);
+            assert!(pc.removed_pairs > 0);
+        }
+        let pc2 = PathConsistency::new(PathVariant::PC2).enforce(&chain(), &mut Statistics::default()).unwrap();
+        let pc8 = PathConsistency::new(PathVariant::PC8).enforce(&chain(), &mut Statistics::default()).unwrap();
+        assert_eq!(relations(&pc2), relations(&pc8));
+    }
+
+    #[test]
+    fn pc_same_solutions() {
+        let mut tight = PathConsistency::new(PathVariant::PC8).enforce(&chain(), &mut Statistics::default()).unwrap();
+        let mut engine = Engine::new(Consistency::new(ArcOriented, AC1, Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut tight).is_sat());
+        assert_eq!(engine.limiter().solutions, 4);
+    }
+
+    #[test]
+    fn pc_inconsistent_triangle() {
+        //arc consistent, but no 2-colouring of a triangle
+        for variant in [PathVariant::PC2, PathVariant::PC8] {
+            let vmap = generate_variables("x", 3, &dom![1, 2]);
+            let csp = Csp::new(vmap.clone(), vec![
+                Rc::new(Intensional::from_pred(neq!(var!(vmap["x1"]), var!(vmap["x2"])))),
+                Rc::new(Intensional::from_pred(neq!(var!(vmap["x2"]), var!(vmap["x3"])))),
+                Rc::new(Intensional::from_pred(neq!(var!(vmap["x1"]), var!(vmap["x3"])))),
+            ]);
+            let mut stats = Statistics::default();
+            assert!(matches!(PathConsistency::new(variant).enforce(&csp, &mut stats), Err(PathError::Inconsistent)));
+            assert_eq!(stats.domain_wipeouts, 1);
+        }
+    }
+}
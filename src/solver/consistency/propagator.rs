@@ -0,0 +1,424 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Constraint oriented (propagator) scheme
+    - Propagator: a constraint propagates by revising the arcs of its future variables,
+      returns the events of the reduced domains (Err(TriggerEvent::Wipeout) on a domain wipeout)
+    - PropagatorOriented: constraints scheduled through one FIFO bucket per cost class
+      (Constraint::cost, cheapest first), a constraint is queued once
+    - an idempotent constraint (Constraint::idempotent) is not enqueued again on its own events
+    - filter: custom filtering of a constraint (Constraint::filter), used by the schemes instead of
+      revising its arcs, the removals are explained by the domains of the scope before filtering
+***************************************/
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use crate::csp::constraint::constraint::{Changed, Constraint, Cost};
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::{Domain, OrdT};
+use crate::csp::event::TriggerEvent;
+use crate::csp::explanation::Literal;
+use crate::csp::variable::vvalue::vv;
+use crate::instrumentation::monitor::Monitor;
+use crate::solver::consistency::arc::Arc;
+use crate::solver::consistency::revise::Revise;
+use crate::solver::consistency::scheme::{by_variable, triggers, Scheme};
+
+pub trait Propagator<T: OrdT> {
+    fn propagate<M: Monitor, R: Revise<M, T>>(&self, csp: &mut Csp<T>, revise: &mut R, monitor: &mut M)
+        -> Result<Vec<TriggerEvent>, TriggerEvent>;
+}
+
+impl<T: OrdT> Propagator<T> for Rc<dyn Constraint<T>> {
+    //custom filtering if any, else one revision of each arc (c, x), x not in past
+    fn propagate<M: Monitor, R: Revise<M, T>>(&self, csp: &mut Csp<T>, revise: &mut R, monitor: &mut M)
+        -> Result<Vec<TriggerEvent>, TriggerEvent> {
+        if self.has_filter() {
+            return filter(csp, self, monitor);
+        }
+        let mut events = vec![];
+        let future: Vec<_> = self.scp().iter().filter(|x| !csp.past().contains(x.label())).cloned().collect();
+        for x in &future {
+            let arc = Arc { constraint: self.clone(), variable: x.clone() };
+            let before = x.valid_values();
+            x.take_events();
+            if !revise.revise(&arc, csp.level(), monitor) {
+                monitor.on_revise_fruitless();
+                continue;
+            }
+            let after = x.valid_values();
+            csp.explain_removals(self, x, before.into_iter().filter(|a| !after.contains(a)).collect());
+            if x.dom().is_empty() {
+                csp.explain_wipeout(self, x);
+                return Err(TriggerEvent::Wipeout(x.label().clone()));
+            }
+            events.extend(x.take_events());
+        }
+        Ok(events)
+    }
+}
+
+//custom filtering of c: removals explained, events of the reduced domains (Err(TriggerEvent::Wipeout) on a wipeout)
+pub fn filter<M: Monitor, T: OrdT>(csp: &mut Csp<T>, c: &Rc<dyn Constraint<T>>, monitor: &mut M)
+    -> Result<Vec<TriggerEvent>, TriggerEvent> {
+    monitor.on_revision_check();
+    let scope = c.scp().to_vec();
+    let before: Vec<Vec<T>> = scope.iter()
+        .map(|x| {
+            x.take_events();
+            x.valid_values()
+        })
+        .collect();
+    let outcome = c.filter(csp.level(), monitor);
+
+    let mut events = vec![];
+    for (i, x) in scope.iter().enumerate() {
+        let after = x.valid_values();
+        if after.len() == before[i].len() {
+            continue;
+        }
+        let removed = before[i].iter().filter(|a| !after.contains(a)).cloned().collect();
+        let reason = scope.iter().zip(&before)
+            .filter(|(y, _)| y.label() != x.label())
+            .flat_map(|(y, values)| y.dom().get_initial_values().iter()
+                .filter(|b| !values.contains(b))
+                .map(|b| Literal::Neq(vv(y.label().clone(), b.clone())))
+                .collect::<Vec<_>>())
+            .collect();
+        csp.explain_removals_with(c, x, removed, reason);
+        events.extend(x.take_events());
+    }
+    match outcome {
+        Err(wipeout) => {
+            let x = csp.vars()[&wipeout.variable].clone();
+            csp.explain_wipeout(c, &x);
+            Err(TriggerEvent::Wipeout(wipeout.variable))
+        }
+        Ok(Changed::Unchanged) => {
+            monitor.on_revise_fruitless();
+            Ok(events)
+        }
+        Ok(Changed::Reduced) => Ok(events),
+    }
+}
+
+pub struct PropagatorOriented;
+
+impl<M: Monitor, T: OrdT, R: Revise<M, T>> Scheme<M, T, R> for PropagatorOriented {
+    fn enforce(&mut self, csp: &mut Csp<T>, events: Vec<TriggerEvent>, revise: &mut R, monitor: &mut M) -> bool {
+        monitor.on_enforce_start();
+        let events = by_variable(&events);
+        let constraints = csp.constraints().clone();
+        let mut involving: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, c) in constraints.iter().enumerate() {
+            for x in c.scp() {
+                involving.entry(x.label().clone()).or_default().push(i);
+            }
+        }
+
+        let mut queue = Buckets::new(constraints.len());
+        for (i, c) in constraints.iter().enumerate() {
+            if c.scp().iter().any(|x| !csp.past().contains(x.label()))
+                && c.scp().iter().any(|y| events.get(y.label()).is_some_and(|k| triggers(revise, c, k))) {
+                queue.push(i, c.cost(), monitor);
+            }
+        }
+
+        while let Some(i) = queue.pop(monitor) {
+            if monitor.interrupted() {
+                monitor.on_enforce_end();
+                return false; //interrupted (see Limiter::reached)
+            }
+            let c = &constraints[i];
+            match c.propagate(csp, revise, monitor) {
+                Err(_) => {
+                    monitor.on_domain_wipeout();
+                    monitor.on_enforce_end();
+                    monitor.on_domain_snapshot(csp);
+                    return false;
+                }
+                Ok(reduced) => {
+                    for (y, kinds) in by_variable(&reduced) {
+                        for j in &involving[&y] {
+                            let c2 = &constraints[*j];
+                            if (*j != i || !c.idempotent()) && triggers(revise, c2, &kinds) {
+                                queue.push(*j, c2.cost(), monitor);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        monitor.on_enforce_end();
+        monitor.on_domain_snapshot(csp);
+        true
+    }
+}
+
+//one FIFO of constraint indices per cost class, a constraint is queued once
+struct Buckets {
+    buckets: Vec<VecDeque<usize>>,
+    queued: Vec<bool>,
+}
+
+impl Buckets {
+    fn new(e: usize) -> Self {
+        Self { buckets: vec![VecDeque::new(); Cost::ALL.len()], queued: vec![false; e] }
+    }
+
+    fn push<M: Monitor>(&mut self, i: usize, cost: Cost, monitor: &mut M) {
+        if !self.queued[i] {
+            self.queued[i] = true;
+            monitor.on_enqueue();
+            self.buckets[cost as usize].push_back(i);
+        }
+    }
+
+    //from the cheapest non empty bucket
+    fn pop<M: Monitor>(&mut self, monitor: &mut M) -> Option<usize> {
+        let i = self.buckets.iter_mut().find_map(|b| b.pop_front())?;
+        self.queued[i] = false;
+        monitor.on_dequeue();
+        Some(i)
+    }
+}
+
+/**************************************
+        Test fixtures
+***************************************/
+
+//shared by the tests of the constraints filtered through the custom hook
+#[cfg(test)]
+pub mod fixtures {
+    use std::rc::Rc;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::event::reduced;
+    use crate::csp::variable::extvar::ExVar;
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::consistency::propagator::PropagatorOriented;
+    use crate::solver::consistency::revise::AC3;
+    use crate::solver::consistency::scheme::{ArcOriented, Scheme, VariableOriented};
+
+    pub fn variable(x: &str, values: Vec<i32>) -> Rc<ExVar<i32>> {
+        Rc::new(ExVar::new(x.into(), SetDom::new(values)))
+    }
+
+    pub fn values(csp: &Csp<i32>, x: &str) -> Vec<i32> { csp.vars()[x].valid_values() }
+
+    //same fixpoint with the three schemes: no tuple checked, (x, a) removed by the constraint label
+    pub fn filtered_by_each_scheme(csp: impl Fn() -> Csp<i32>, x: &str, a: i32, label: &str,
+                                   fixpoint: impl Fn(&Csp<i32>, &Statistics)) {
+        for scheme in 0..3 {
+            let mut csp = csp();
+            let events = reduced(csp.vars().keys());
+            let mut stats = Statistics::default();
+            assert!(match scheme {
+                0 => ArcOriented.enforce(&mut csp, events, &mut AC3, &mut stats),
+                1 => VariableOriented.enforce(&mut csp, events, &mut AC3, &mut stats),
+                _ => PropagatorOriented.enforce(&mut csp, events, &mut AC3, &mut stats),
+            });
+            fixpoint(&csp, &stats);
+            assert_eq!(stats.checks, 0);
+            assert_eq!(csp.explanations().why(x, &a).unwrap().constraint, Some(label.to_string()));
+        }
+    }
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::{AExpr, Expr};
+    use crate::csp::ast::pred::Pred;
+    use std::rc::Rc;
+    use crate::{add, base, cst, dom, lt, neq, var};
+    use crate::csp::constraint::constraint::{Changed, Constraint, Cost, Wipeout};
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::domain::Domain;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::event::reduced;
+    use crate::csp::variable::extvar::{generate_variables, ExVar};
+    use crate::csp::variable::vvalue::VValue;
+    use crate::instrumentation::monitor::{Monitor, Statistics};
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::propagator::PropagatorOriented;
+    use crate::solver::consistency::propagator::fixtures::{filtered_by_each_scheme, values};
+    use crate::solver::consistency::revise::{AC1, AC3, AC3rm};
+    use crate::solver::consistency::scheme::{ArcOriented, Scheme};
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::limits::SearchLimits;
+
+    //x1 < x2 < ... < xn on {1, .., n}
+    fn chain(n: usize) -> Csp<i32> {
+        let vmap = generate_variables("x", n, &SetDom::new((1..=n as i32).collect()));
+        let constraints: Vec<Rc<dyn Constraint<i32>>> = (1..n)
+            .map(|i| Rc::new(Intensional::from_pred(lt!(
+                var!(vmap[&format!("x{}", i)]), var!(vmap[&format!("x{}", i + 1)])))) as Rc<dyn Constraint<i32>>)
+            .collect();
+        Csp::new(vmap, constraints)
+    }
+
+    #[test]
+    fn propagator_fixpoint() {
+        let mut csp = chain(5);
+        let events = reduced(csp.vars().keys());
+        let mut stats = Statistics::default();
+        assert!(PropagatorOriented.enforce(&mut csp, events, &mut AC3, &mut stats));
+        for i in 1..=5 {
+            assert_eq!(csp.vars()[&format!("x{}", i)].valid_values(), vec![i]);
+        }
+        //a constraint is queued once
+        assert!(stats.max_queue_size <= csp.e());
+        assert_eq!(stats.nb_enqueue, 0);
+
+        //same fixpoint as the arc oriented scheme, no duplicated arcs
+        let mut arcs = Statistics::default();
+        let mut csp = chain(5);
+        let events = reduced(csp.vars().keys());
+        assert!(ArcOriented.enforce(&mut csp, events, &mut AC3, &mut arcs));
+        assert!(arcs.max_queue_size <= 2 * csp.e());
+        assert_eq!(arcs.value_deletions, stats.value_deletions);
+    }
+
+    //declares its wrapped constraint idempotent
+    #[derive(Debug)]
+    struct Idempotent(Rc<dyn Constraint<i32>>);
+
+    impl Constraint<i32> for Idempotent {
+        fn deep_clone(&self) -> Rc<dyn Constraint<i32>> { Rc::new(Idempotent(self.0.deep_clone())) }
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { Constraint::fmt(self.0.as_ref(), f) }
+        fn apply(&self, asn: &Vec<VValue<i32>>) -> bool { self.0.apply(asn) }
+        fn scp(&self) -> &[Rc<ExVar<i32>>] { self.0.scp() }
+        fn idempotent(&self) -> bool { true }
+    }
+
+    #[test]
+    fn propagator_idempotent() {
+        //x1 < x2 on {1, 2, 3}: one propagation reaches the fixpoint
+        let mut csp = chain(2);
+        let mut stats = Statistics::default();
+        let events = reduced(csp.vars().keys());
+        assert!(PropagatorOriented.enforce(&mut csp, events, &mut AC3, &mut stats));
+        //enqueued again on its own removals, the second propagation is fruitless
+        assert_eq!((stats.revise_calls, stats.revise_fruitless), (4, 2));
+
+        let csp = chain(2);
+        let c: Rc<dyn Constraint<i32>> = Rc::new(Idempotent(csp.constraints()[0].clone()));
+        let mut idempotent = Csp::new(csp.vars().clone(), vec![c]);
+        let mut stats = Statistics::default();
+        let events = reduced(idempotent.vars().keys());
+        assert!(PropagatorOriented.enforce(&mut idempotent, events, &mut AC3, &mut stats));
+        assert_eq!((stats.revise_calls, stats.revise_fruitless), (2, 0));
+    }
+
+    //x < y filtered on the bounds
+    #[derive(Debug)]
+    struct Less(Vec<Rc<ExVar<i32>>>);
+
+    impl Constraint<i32> for Less {
+        fn deep_clone(&self) -> Rc<dyn Constraint<i32>> {
+            Rc::new(Less(self.0.iter().map(|x| Rc::new(x.deep_clone())).collect()))
+        }
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{} < {}", self.0[0].label(), self.0[1].label())
+        }
+        fn apply(&self, asn: &Vec<VValue<i32>>) -> bool {
+            let value = |x: &Rc<ExVar<i32>>| asn.iter().find(|vv| vv.label == *x.label()).map(|vv| vv.value);
+            value(&self.0[0]) < value(&self.0[1])
+        }
+        fn scp(&self) -> &[Rc<ExVar<i32>>] { &self.0 }
+        fn idempotent(&self) -> bool { true }
+        fn has_filter(&self) -> bool { true }
+        fn filter(&self, level: usize, monitor: &mut dyn Monitor) -> Result<Changed, Wipeout> {
+            let (x, y) = (&self.0[0], &self.0[1]);
+            let mut changed = Changed::Unchanged;
+            let max = y.dom().max();
+            for a in x.valid_values().into_iter().filter(|a| max.is_none_or(|m| *a >= m)) {
+                monitor.on_value_deleted();
+                x.dom_mut().remove_value(&a, level);
+                changed = Changed::Reduced;
+            }
+            let min = x.dom().min().ok_or(Wipeout { variable: x.label().clone() })?;
+            for b in y.valid_values().into_iter().filter(|b| *b <= min) {
+                monitor.on_value_deleted();
+                y.dom_mut().remove_value(&b, level);
+                changed = Changed::Reduced;
+            }
+            if y.dom().is_empty() {
+                return Err(Wipeout { variable: y.label().clone() });
+            }
+            Ok(changed)
+        }
+    }
+
+    fn less_chain(n: usize, values: Vec<i32>) -> Csp<i32> {
+        let vmap = generate_variables("x", n, &SetDom::new(values));
+        let constraints: Vec<Rc<dyn Constraint<i32>>> = (1..n)
+            .map(|i| Rc::new(Less(vec![vmap[&format!("x{}", i)].clone(), vmap[&format!("x{}", i + 1)].clone()]))
+                as Rc<dyn Constraint<i32>>)
+            .collect();
+        Csp::new(vmap, constraints)
+    }
+
+    #[test]
+    fn filter_hook() {
+        //no support seeking, removals explained by the constraints
+        filtered_by_each_scheme(|| less_chain(5, (1..=5).collect()), "x1", 5, "c_x1x2", |csp, stats| {
+            for i in 1..=5 {
+                assert_eq!(values(csp, &format!("x{}", i)), vec![i]);
+            }
+            assert!(stats.revise_calls > 0);
+        });
+
+        let mut csp = less_chain(3, vec![1, 2]);
+        let events = reduced(csp.vars().keys());
+        assert!(!ArcOriented.enforce(&mut csp, events, &mut AC1, &mut Statistics::default()));
+        assert!(csp.explanations().conflict().is_some());
+
+        //search: 4 increasing triples of {1, 2, 3, 4}
+        let mut csp = less_chain(3, vec![1, 2, 3, 4]);
+        let mut engine = Engine::new(Consistency::new(ArcOriented, AC3, Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut csp).is_sat());
+        assert_eq!(engine.limiter().solutions, 4);
+    }
+
+    #[test]
+    fn propagator_cheapest_first() {
+        //x1 != x2 (binary) is listed first, x1 != 1 (unary) wipes x1 out first
+        let vmap = generate_variables("x", 2, &dom![1]);
+        let mut csp = Csp::new(vmap.clone(), vec![
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x1"]), var!(vmap["x2"])))),
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x1"]), cst!(1)))),
+        ]);
+        assert_eq!(csp.constraints()[1].cost(), Cost::Unary);
+        assert_eq!(csp.constraints()[0].cost(), Cost::Binary);
+        let events = reduced(csp.vars().keys());
+        assert!(!PropagatorOriented.enforce(&mut csp, events, &mut AC3, &mut Statistics::default()));
+        assert_eq!(csp.explanations().conflict().unwrap().constraint, Some("c_x1".to_string()));
+    }
+
+    #[test]
+    fn propagator_search() {
+        //4-queens: 2 solutions
+        let vmap = generate_variables("q", 4, &dom![1, 2, 3, 4]);
+        let mut constraints: Vec<Rc<dyn Constraint<i32>>> = vec![];
+        for i in 1..=4 {
+            for j in (i + 1)..=4 {
+                let (qi, qj) = (&vmap[&format!("q{}", i)], &vmap[&format!("q{}", j)]);
+                constraints.push(Rc::new(Intensional::from_pred(neq!(var!(qi), var!(qj)))));
+                let d = j - i;
+                constraints.push(Rc::new(Intensional::from_pred(neq!(base!(var!(qi)), add!(base!(var!(qj)), base!(cst!(d)))))));
+                constraints.push(Rc::new(Intensional::from_pred(neq!(base!(var!(qj)), add!(base!(var!(qi)), base!(cst!(d)))))));
+            }
+        }
+        let mut csp = Csp::new(vmap, constraints);
+        let mut engine = Engine::new(Consistency::new(PropagatorOriented, AC3rm::new(), Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut csp).is_sat());
+        assert_eq!(engine.limiter().solutions, 2);
+        assert_eq!(csp.level(), 0);
+    }
+}
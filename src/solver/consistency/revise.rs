@@ -3,22 +3,27 @@
 - Desc: Propagation variant (revise function)
         - AC1 Algorithm 8 (calling seekSupport)
         - AC3 Algorithm 18 (seekSupport-3)
-        - AC2001 Algorithm 19 (seekSupport-2001)
-- Optimization:
-    - output Option<TriggerEvent>
+        - AC2001 Algorithm 19 (seekSupport-2001), last supports trailed by level
+        - AC3rm (seekSupport-3 with residual supports, nothing to restore on backtrack)
+  Revise functions subscribe to the event kinds that can make them effective (see Revise::reacts_to).
 ***************************************/
 use std::collections::HashMap;
 use std::rc::Rc;
 use crate::csp::constraint::constraint::Constraint;
 use crate::csp::domain::domain::{Domain, OrdT};
+use crate::csp::event::EventKind;
 use crate::csp::prelude::extvar::ExVar;
 use crate::csp::prelude::vvalue::{vv, vv_from_hashmap};
 use crate::instrumentation::monitor::Monitor;
 use crate::solver::consistency::arc::Arc;
-use crate::solver::consistency::cvalue::CValue;
+use crate::solver::consistency::cvalue::{CKey, CValue};
 
 pub trait Revise<M: Monitor, T: OrdT> {
     fn revise(&mut self, arc : &Arc<T>, level: usize, monitor: &mut M) -> bool;
+    //Trailing: forget the data structures updated at a level >= lvl (mirrors SetDom::restore_up_to)
+    fn restore_up_to(&mut self, _lvl: usize) {}
+    //subscription: the arcs of c are revised on the events of this kind (default: any domain change)
+    fn reacts_to(&self, _c: &Rc<dyn Constraint<T>>, kind: EventKind) -> bool { kind.is_domain_change() }
 }
 
 pub struct AC1;
@@ -69,20 +74,83 @@ impl<M: Monitor, T:OrdT> Revise<M, T> for AC3 {
 }
 
 fn seek_support3<M: Monitor, T:OrdT>(cval:&CValue<T>, monitor: &mut M) -> bool {
+    find_support3(cval, monitor).is_some()
+}
+
+//first valid tuple of rel(c)_x=a, if any
+fn find_support3<M: Monitor, T:OrdT>(cval:&CValue<T>, monitor: &mut M) -> Option<HashMap<String, T>> {
     let mut tuple = Some(cval.get_first_valid_tuple());
     while !tuple.is_none() {
         monitor.on_constraint_check();
         let tau = tuple.unwrap();
         if cval.constraint.check_assignment(&vv_from_hashmap(&tau)).to_bool().unwrap() {
-            return true;
+            return Some(tau);
         }
         tuple = cval.get_next_valid_tuple(&tau);
     }
-    false
+    None
+}
+
+pub struct AC3rm<T: OrdT> {
+    //residual support of each cvalue, possibly no longer valid
+    residues: HashMap<CKey<T>, HashMap<String, T>>,
+}
+
+impl<M: Monitor, T:OrdT> Revise<M, T> for AC3rm<T> {
+    fn revise(&mut self, arc : &Arc<T>, level: usize, monitor : &mut M) -> bool {
+        monitor.on_revision_check();
+        //AC3rm
+        let size_before = arc.variable.dom().size();
+        for a in arc.variable.valid_values() {
+            if !self.seek_support3rm(CValue {
+                constraint: arc.constraint.clone(),
+                variable: arc.variable.clone(),
+                value: a.clone()
+                },
+                monitor) {
+                monitor.on_value_deleted();
+                arc.variable.dom_mut().remove_value(&a, level);
+            }
+        }
+        size_before != arc.variable.dom().size()
+    }
+}
+
+impl<T:OrdT> Default for AC3rm<T> {
+    fn default() -> Self { Self::new() }
 }
 
+impl<T:OrdT> AC3rm<T> {
+    pub fn new() -> Self {
+        Self { residues: HashMap::new() }
+    }
+
+    pub fn residues(&self) -> &HashMap<CKey<T>, HashMap<String, T>> {&self.residues}
+
+    //residues are only hints: a residue still valid is a support (whatever the level),
+    //an invalid one is replaced by a support found from scratch
+    fn seek_support3rm<M: Monitor>(&mut self, cval: CValue<T>, monitor: &mut M) -> bool {
+        if self.residues.get(&cval.key())
+            .is_some_and(|residue| cval.constraint.get_first_invalid_pos(Some(residue)) == -1) {
+            return true;
+        }
+        match find_support3(&cval, monitor) {
+            Some(tau) => {
+                self.residues.insert(cval.key(), tau);
+                true
+            }
+            None => false
+        }
+    }
+}
+
+//tuple (value of each variable of the scope, by label)
+type Tuple<T> = HashMap<String, T>;
+
 pub struct AC2001<M: Monitor, T: OrdT> {
-    last: HashMap<String, HashMap<String, T>>,
+    last: HashMap<CKey<T>, HashMap<String, T>>,
+    //(level, cvalue, previous last support) for each update of last
+    trail: Vec<(usize, CKey<T>, Option<Tuple<T>>)>,
     _phantom: std::marker::PhantomData<M>
 }
 
@@ -97,6 +165,7 @@ impl<M: Monitor, T:OrdT> Revise<M, T> for AC2001<M, T> {
                 variable: arc.variable.clone(),
                 value: a.clone()
                 },
+                level,
                 monitor) {
                 println!("remove {} from {}", &a, arc.variable);
                 monitor.on_value_deleted();
@@ -105,22 +174,37 @@ impl<M: Monitor, T:OrdT> Revise<M, T> for AC2001<M, T> {
         }
         size_before != arc.variable.dom().size()
     }
+
+    fn restore_up_to(&mut self, lvl: usize) {
+        while self.trail.last().is_some_and(|(l, _, _)| *l >= lvl) {
+            let (_, key, previous) = self.trail.pop().expect("Should not be None!");
+            match previous {
+                Some(t) => self.last.insert(key, t),
+                None => self.last.remove(&key),
+            };
+        }
+    }
+}
+
+impl<M: Monitor, T:OrdT> Default for AC2001<M, T> {
+    fn default() -> Self { Self::new() }
 }
 
 impl<M: Monitor, T:OrdT> AC2001<M, T> {
-    pub(crate) fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             last: HashMap::new(),
+            trail: Vec::new(),
             _phantom: std::marker::PhantomData
         }
     }
 
-    pub fn last_supports(&self) -> &HashMap<String, HashMap<String, T>> {&self.last}
+    pub fn last_supports(&self) -> &HashMap<CKey<T>, HashMap<String, T>> {&self.last}
 
     //optimal for binary constraints
-    fn seek_support2001(&mut self, cval: CValue<T>, monitor: &mut M) -> bool {
+    fn seek_support2001(&mut self, cval: CValue<T>, level: usize, monitor: &mut M) -> bool {
         let mut tau = None;
-        let last_support = self.last.get(&cval.label());
+        let last_support = self.last.get(&cval.key());
 
         match last_support {
             None => tau = Some(cval.get_first_valid_tuple()),
@@ -137,11 +221,9 @@ impl<M: Monitor, T:OrdT> AC2001<M, T> {
             let t = tau.expect("Should not be None!");
             monitor.on_constraint_check();
             if cval.constraint.check_assignment(&vv_from_hashmap(&t)).to_bool().unwrap() {
-                if let Some (e) = self.last.get_mut(&cval.label()) {
-                    *e = t;
-                } else {
-                    self.last.insert(cval.label(), t);
-                }
+                let key = cval.key();
+                let previous = self.last.insert(key.clone(), t);
+                self.trail.push((level, key, previous));
                 return true;
             }
             tau = cval.get_next_valid_tuple(&t);
@@ -164,14 +246,21 @@ mod tests {
     use std::collections::HashMap;
     use std::rc::Rc;
     use crate::csp::domain::setdom::SetDom;
-    use crate::{and, atom, cst, eq, or, var, var_dom, vvals};
+    use crate::{and, atom, cst, eq, neq, or, var, var_dom, vvals};
+    use crate::csp::constraint::extensional::ExtConstraint;
     use crate::csp::constraint::intensional::Intensional;
     use crate::csp::prelude::domain::Domain;
     use crate::csp::prelude::vvalue::{vv_from_hashmap, VValue};
     use crate::instrumentation::monitor::Statistics;
     use crate::solver::consistency::arc::{Arc};
     use crate::solver::consistency::cvalue::CValue;
-    use crate::solver::consistency::revise::{Revise, AC2001, AC3};
+    use crate::solver::consistency::revise::{Revise, AC2001, AC3, AC3rm};
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::csp::Csp;
+    use crate::csp::truth::Truth;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::scheme::ArcOriented;
+    use crate::solver::search::engine::Engine;
 
     #[test] //figure 4.3
     fn test_revise() {
@@ -212,17 +301,17 @@ mod tests {
         let mut consistency_ac2001 = AC2001::new();
         assert!(!consistency_ac2001.revise(&revision, 1, &mut monitor_ac3)); //just to not use NoMonitor...
         assert_eq!(vv_from_hashmap(consistency_ac2001.last_supports().get(
-            &CValue { constraint: c.clone(), variable: x.clone(), value: "a" }.label())
+            &CValue { constraint: c.clone(), variable: x.clone(), value: "a" }.key())
             .expect("Should not be None")),
                    vvals!("x" => "a", "y" => "a"));
 
         assert_eq!(vv_from_hashmap(consistency_ac2001.last_supports().get(
-            &CValue { constraint: c.clone(), variable: x.clone(), value: "b" }.label())
+            &CValue { constraint: c.clone(), variable: x.clone(), value: "b" }.key())
             .expect("Should not be None")),
                    vvals!("x" => "b", "y" => "c"));
 
         assert_eq!(vv_from_hashmap(consistency_ac2001.last_supports().get(
-            &CValue { constraint: c.clone(), variable: x.clone(), value: "c" }.label())
+            &CValue { constraint: c.clone(), variable: x.clone(), value: "c" }.key())
             .expect("Should not be None")),
                    vvals!("x" => "c", "y" => "d"));
 
@@ -240,18 +329,174 @@ mod tests {
         assert_eq!(monitor_ac2001_2.checks, 1);
         //add validity checks? (push the monitor into constraints
         assert_eq!(vv_from_hashmap(consistency_ac2001.last_supports().get(
-            &CValue { constraint: c.clone(), variable: x.clone(), value: "a" }.label())
+            &CValue { constraint: c.clone(), variable: x.clone(), value: "a" }.key())
             .expect("Should not be None")),
                    vvals!("x" => "a", "y" => "a"));
 
         assert_eq!(vv_from_hashmap(consistency_ac2001.last_supports().get(
-            &CValue { constraint: c.clone(), variable: x.clone(), value: "b" }.label())
+            &CValue { constraint: c.clone(), variable: x.clone(), value: "b" }.key())
             .expect("Should not be None")),
                    vvals!("x" => "b", "y" => "d"));
 
         assert_eq!(vv_from_hashmap(consistency_ac2001.last_supports().get(
-            &CValue { constraint: c.clone(), variable: x.clone(), value: "c" }.label())
+            &CValue { constraint: c.clone(), variable: x.clone(), value: "c" }.key())
             .expect("Should not be None")),
                    vvals!("x" => "c", "y" => "d"));
     }
+
+    #[test] //figure 4.3 with residues
+    fn test_revise_ac3rm() {
+        let x = var_dom!("x".into(), SetDom::new(vec!["a", "b", "c"]));
+        let y = var_dom!("y".into(), SetDom::new(vec!["a", "b", "c", "d"]));
+        let fxa = and!(
+            atom!(eq!(var!(x), cst!("a"))),
+            or!(atom!(eq!(var!(y), cst!("a"))), atom!(eq!(var!(y), cst!("b")))));
+        let fxb = and!(
+            atom!(eq!(var!(x), cst!("b"))),
+            or!(atom!(eq!(var!(y), cst!("c"))), atom!(eq!(var!(y), cst!("d")))));
+        let fxc = and!(
+            atom!(eq!(var!(x), cst!("c"))),
+            atom!(eq!(var!(y), cst!("d"))));
+        let c = Rc::new(Intensional::from_formula(Rc::new(or!(fxa, fxb, fxc))));
+        let revision = Arc { constraint: c.clone(), variable: x.clone() };
+
+        //first revision: same checks as AC3
+        let mut ac3rm = AC3rm::new();
+        let mut monitor = Statistics::default();
+        assert!(!ac3rm.revise(&revision, 1, &mut monitor));
+        assert_eq!(monitor.checks, 8);
+        assert_eq!(ac3rm.residues().len(), 3);
+
+        //(y, c) deleted: only (x, b) loses its residue, seeked again from scratch
+        y.dom_mut().remove_value(&"c", 2);
+        let mut monitor = Statistics::default();
+        assert!(!ac3rm.revise(&revision, 2, &mut monitor));
+        assert_eq!(monitor.checks, 3);
+        assert_eq!(vv_from_hashmap(ac3rm.residues().get(
+            &CValue { constraint: c.clone(), variable: x.clone(), value: "b" }.key())
+            .expect("Should not be None")),
+                   vvals!("x" => "b", "y" => "d"));
+
+        //backtrack: residues are still valid, no check
+        y.dom_mut().restore_up_to(2);
+        let mut monitor = Statistics::default();
+        assert!(!ac3rm.revise(&revision, 1, &mut monitor));
+        assert_eq!(monitor.checks, 0);
+
+        //(y, d) deleted: (x, c) has no support anymore
+        y.dom_mut().remove_value(&"d", 2);
+        let mut monitor = Statistics::default();
+        assert!(ac3rm.revise(&revision, 2, &mut monitor));
+        assert_eq!(x.valid_values(), vec!["a", "b"]);
+    }
+
+    #[test] //stale last supports of a table constraint are still allowed tuples
+    fn test_revise_ac2001_table() {
+        let x = var_dom!("x".into(), SetDom::new(vec![1, 2]));
+        let y = var_dom!("y".into(), SetDom::new(vec![1, 2, 3]));
+        let c = Rc::new(ExtConstraint::new(vec![x.clone(), y.clone()], vec![
+            vvals!("x" => 1, "y" => 1), vvals!("x" => 1, "y" => 3), vvals!("x" => 2, "y" => 2)]));
+        let revision = Arc { constraint: c.clone(), variable: x.clone() };
+        let mut ac2001 = AC2001::new();
+        let mut monitor = Statistics::default();
+        assert!(!ac2001.revise(&revision, 1, &mut monitor));
+
+        //(y, 1) deleted: the last support (1, 1) of (x, 1) is stale, (1, 3) is found after it
+        y.dom_mut().remove_value(&1, 2);
+        assert!(!ac2001.revise(&revision, 2, &mut monitor));
+        let key_1 = CValue { constraint: c.clone(), variable: x.clone(), value: 1 }.key();
+        assert_eq!(vv_from_hashmap(&ac2001.last_supports()[&key_1]), vvals!("x" => 1, "y" => 3));
+
+        //(y, 2) deleted: (x, 2) has no support anymore
+        y.dom_mut().remove_value(&2, 3);
+        assert!(ac2001.revise(&revision, 3, &mut monitor));
+        assert_eq!(x.valid_values(), vec![1]);
+    }
+
+    #[test] //constraints sharing a scope keep their own residues
+    fn test_residues_per_constraint() {
+        let x = var_dom!("x".into(), SetDom::new(vec!["a", "b"]));
+        let y = var_dom!("y".into(), SetDom::new(vec!["a", "b"]));
+        let c_eq = Rc::new(Intensional::from_pred(eq!(var!(x), var!(y))));
+        let c_neq = Rc::new(Intensional::from_pred(neq!(var!(x), var!(y))));
+        let mut ac3rm = AC3rm::new();
+        let mut monitor = Statistics::default();
+        assert!(!ac3rm.revise(&Arc { constraint: c_eq.clone(), variable: x.clone() }, 1, &mut monitor));
+        assert!(!ac3rm.revise(&Arc { constraint: c_neq.clone(), variable: x.clone() }, 1, &mut monitor));
+        assert_eq!(ac3rm.residues().len(), 4);
+        assert_eq!(vv_from_hashmap(&ac3rm.residues()[&CValue { constraint: c_eq, variable: x.clone(), value: "a" }.key()]),
+                   vvals!("x" => "a", "y" => "a"));
+        assert_eq!(vv_from_hashmap(&ac3rm.residues()[&CValue { constraint: c_neq, variable: x.clone(), value: "a" }.key()]),
+                   vvals!("x" => "a", "y" => "b"));
+    }
+
+    #[test] //figure 4.3, last supports under backtracking
+    fn test_revise_ac2001_restore() {
+        let x = var_dom!("x".into(), SetDom::new(vec!["a", "b", "c"]));
+        let y = var_dom!("y".into(), SetDom::new(vec!["a", "b", "c", "d"]));
+        let fxa = and!(
+            atom!(eq!(var!(x), cst!("a"))),
+            or!(atom!(eq!(var!(y), cst!("a"))), atom!(eq!(var!(y), cst!("b")))));
+        let fxb = and!(
+            atom!(eq!(var!(x), cst!("b"))),
+            or!(atom!(eq!(var!(y), cst!("c"))), atom!(eq!(var!(y), cst!("d")))));
+        let fxc = and!(
+            atom!(eq!(var!(x), cst!("c"))),
+            atom!(eq!(var!(y), cst!("d"))));
+        let c = Rc::new(Intensional::from_formula(Rc::new(or!(fxa, fxb, fxc))));
+        let revision = Arc { constraint: c.clone(), variable: x.clone() };
+        let key_b = CValue { constraint: c.clone(), variable: x.clone(), value: "b" }.key();
+        let mut monitor = Statistics::default();
+
+        let mut ac2001 = AC2001::new();
+        assert!(!ac2001.revise(&revision, 1, &mut monitor));
+        //level 2: (y, c) deleted, last support of (x, b) moves to (b, d)
+        y.dom_mut().remove_value(&"c", 2);
+        assert!(!ac2001.revise(&revision, 2, &mut monitor));
+        assert_eq!(vv_from_hashmap(&ac2001.last_supports()[&key_b]), vvals!("x" => "b", "y" => "d"));
+
+        //backtrack to level 1: (y, c) and the last support (b, c) are restored
+        y.dom_mut().restore_up_to(2);
+        Revise::<Statistics, &str>::restore_up_to(&mut ac2001, 2);
+        assert_eq!(vv_from_hashmap(&ac2001.last_supports()[&key_b]), vvals!("x" => "b", "y" => "c"));
+
+        //level 2: (y, d) deleted, (b, c) still supports (x, b)
+        y.dom_mut().remove_value(&"d", 2);
+        assert!(ac2001.revise(&revision, 2, &mut monitor));
+        assert_eq!(x.valid_values(), vec!["a", "b"]);
+
+        //back to level 0: nothing learned anymore
+        y.dom_mut().restore_up_to(1);
+        x.dom_mut().restore_up_to(1);
+        Revise::<Statistics, &str>::restore_up_to(&mut ac2001, 1);
+        assert!(ac2001.last_supports().is_empty());
+    }
+
+    //x == y on {1, 2}, or x != y with y = 1 (solution x = 2)
+    fn model(eq: bool) -> Csp<i32> {
+        let x = var_dom!("x".into(), SetDom::new(vec![1, 2]));
+        let y = var_dom!("y".into(), SetDom::new(if eq { vec![1, 2] } else { vec![1] }));
+        let c: Rc<dyn Constraint<i32>> = match eq {
+            true => Rc::new(Intensional::from_pred(eq!(var!(x), var!(y)))),
+            false => Rc::new(Intensional::from_pred(neq!(var!(x), var!(y)))),
+        };
+        Csp::new(HashMap::from([("x".into(), x), ("y".into(), y)]), vec![c])
+    }
+
+    //models searched then dropped by the same engine: a freed address is soon reused
+    fn successive_models<R: Revise<Statistics, i32>>(revise: R) {
+        let mut engine = Engine::new(Consistency::new(ArcOriented, revise, Statistics::default()));
+        for _ in 0..20 {
+            assert!(engine.search(&mut model(true)).is_sat());
+            let mut csp = model(false);
+            let solution = engine.search(&mut csp).solution().cloned().expect("Should be satisfiable");
+            assert_eq!(csp.is_solution(&solution), Truth::True);
+        }
+    }
+
+    #[test] //supports of a dropped csp are not reused by a later one
+    fn test_supports_across_models() {
+        successive_models(AC3rm::new());
+        successive_models(AC2001::new());
+    }
 }
@@ -0,0 +1,450 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Domain filtering consistencies between AC and SAC (coarse-grained schemes)
+    - Max-RPC: (x, a) needs, on each binary constraint c(x, y), a support (a, b) that is path consistent:
+      each third variable z constrained with x and y has a witness compatible with a and b
+    - light Max-RPC: same revision, but propagated as AC (only the arcs of the constraints of a modified variable),
+      the supports whose witness has been removed are not checked again
+    - NIC (neighbourhood inverse consistency): (x, a) extends to an assignment of N(x)
+      satisfying every constraint of the neighbourhood of x
+  Non-binary constraints are revised with the given revise function (Max-RPC, light Max-RPC).
+  Counters are the ones of the AC schemes: one revision check per revised arc (variable for NIC),
+  one constraint check per constraint evaluation, enqueue/dequeue per queue entry.
+***************************************/
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+use crate::csp::constraint::constraint::Constraint;
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::{Domain, OrdT};
+use crate::csp::event::TriggerEvent;
+use crate::csp::explanation::Literal;
+use crate::csp::variable::extvar::ExVar;
+use crate::csp::variable::vvalue::{make_assignment, vv, VValue};
+use crate::instrumentation::monitor::Monitor;
+use crate::solver::consistency::arc::Arc;
+use crate::solver::consistency::consistency::Revise;
+use crate::solver::consistency::scheme::{by_variable, triggers, Scheme};
+
+pub struct MaxRPC;
+pub struct LightMaxRPC;
+pub struct NIC;
+
+impl<M: Monitor, T: OrdT, R: Revise<M, T>> Scheme<M, T, R> for MaxRPC {
+    fn enforce(&mut self, csp: &mut Csp<T>, events: Vec<TriggerEvent>, revise: &mut R, monitor: &mut M) -> bool {
+        enforce_rpc(csp, events, revise, monitor, true)
+    }
+}
+
+impl<M: Monitor, T: OrdT, R: Revise<M, T>> Scheme<M, T, R> for LightMaxRPC {
+    fn enforce(&mut self, csp: &mut Csp<T>, events: Vec<TriggerEvent>, revise: &mut R, monitor: &mut M) -> bool {
+        enforce_rpc(csp, events, revise, monitor, false)
+    }
+}
+
+/**************************************
+            Binary network
+***************************************/
+
+//binary constraints (index in csp.constraints()) of each pair of variables, both orders
+struct Graph {
+    binary: HashMap<(String, String), Vec<usize>>,
+    neighbours: HashMap<String, BTreeSet<String>>,
+}
+
+impl Graph {
+    fn new<T: OrdT>(csp: &Csp<T>) -> Self {
+        let mut binary: HashMap<(String, String), Vec<usize>> = HashMap::new();
+        let mut neighbours: HashMap<String, BTreeSet<String>> = HashMap::new();
+        for (i, c) in csp.constraints().iter().enumerate() {
+            for x in c.scp() {
+                for y in c.scp().iter().filter(|y| y.label() != x.label()) {
+                    neighbours.entry(x.label().clone()).or_default().insert(y.label().clone());
+                    if c.scp().len() == 2 {
+                        binary.entry((x.label().clone(), y.label().clone())).or_default().push(i);
+                    }
+                }
+            }
+        }
+        Self { binary, neighbours }
+    }
+
+    fn neighbours(&self, x: &str) -> Vec<String> {
+        self.neighbours.get(x).map(|n| n.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    fn linked(&self, x: &str, y: &str) -> bool {
+        self.binary.contains_key(&(x.to_string(), y.to_string()))
+    }
+
+    //third variables of a triangle on (x, y)
+    fn thirds(&self, x: &str, y: &str) -> Vec<String> {
+        self.neighbours(x).into_iter()
+            .filter(|z| z != y && self.linked(x, z) && self.linked(y, z))
+            .collect()
+    }
+
+    //(x = a, z = v) allowed by every binary constraint between x and z
+    fn compatible<M: Monitor, T: OrdT>(&self, csp: &Csp<T>, x: &str, a: &T, z: &str, v: &T, monitor: &mut M) -> bool {
+        self.binary[&(x.to_string(), z.to_string())].iter()
+            .all(|i| allows(&csp.constraints()[*i], x, a, v, monitor))
+    }
+}
+
+//binary c allows x = a and its other variable = b
+fn allows<M: Monitor, T: OrdT>(c: &Rc<dyn Constraint<T>>, x: &str, a: &T, b: &T, monitor: &mut M) -> bool {
+    monitor.on_constraint_check();
+    let values = c.scp().iter().map(|y| if y.label() == x { a.clone() } else { b.clone() }).collect();
+    c.check_assignment(&make_assignment(c.scp(), values)).to_bool().unwrap()
+}
+
+//removed values of the given variables (reason of a removal)
+fn removed_of<T: OrdT>(csp: &Csp<T>, labels: &[String]) -> Vec<Literal<T>> {
+    let mut reason = vec![];
+    for y in labels {
+        let y = &csp.vars()[y];
+        let active = y.valid_values();
+        for b in y.dom().get_initial_values().iter().filter(|b| !active.contains(b)) {
+            reason.push(Literal::Neq(vv(y.label().clone(), b.clone())));
+        }
+    }
+    reason
+}
+
+/**************************************
+            Max-RPC
+***************************************/
+
+fn enforce_rpc<M: Monitor, T: OrdT, R: Revise<M, T>>(csp: &mut Csp<T>, events: Vec<TriggerEvent>, revise: &mut R,
+                                                     monitor: &mut M, full: bool) -> bool {
+    monitor.on_enforce_start();
+    let constraints = csp.constraints().clone();
+    let graph = Graph::new(csp);
+    let mut queue: VecDeque<(usize, String)> = VecDeque::new();
+    let mut queued: HashSet<(usize, String)> = HashSet::new();
+    let events = by_variable(&events);
+
+    for (i, c) in constraints.iter().enumerate() {
+        for x in c.scp().iter().filter(|x| !csp.past().contains(x.label())) {
+            if c.scp().iter().any(|y| y.label() != x.label() && events.get(y.label()).is_some_and(|k| triggers(revise, c, k))) {
+                push(&mut queue, &mut queued, (i, x.label().clone()), monitor);
+            }
+        }
+    }
+
+    while let Some((i, x)) = queue.pop_front() {
+        monitor.on_dequeue();
+        if monitor.interrupted() {
+            monitor.on_enforce_end();
+            return false; //interrupted (see Limiter::reached)
+        }
+        queued.remove(&(i, x.clone()));
+        let c = &constraints[i];
+        let var = csp.vars()[&x].clone();
+        let before = var.valid_values();
+        let reason = if c.scp().len() == 2 {
+            revise_rpc(csp, &graph, c, &var, monitor)
+        } else if revise.revise(&Arc { constraint: c.clone(), variable: var.clone() }, csp.level(), monitor) {
+            let others: Vec<String> = c.scp().iter().map(|y| y.label().clone()).filter(|y| *y != x).collect();
+            Some(removed_of(csp, &others))
+        } else {
+            None
+        };
+        let Some(reason) = reason else {
+            monitor.on_revise_fruitless();
+            continue;
+        };
+        let after = var.valid_values();
+        let removed = before.into_iter().filter(|a| !after.contains(a)).collect();
+        csp.explain_removals_with(c, &var, removed, reason);
+        if var.dom().is_empty() {
+            csp.explain_wipeout(c, &var);
+            monitor.on_domain_wipeout();
+            monitor.on_enforce_end();
+            monitor.on_domain_snapshot(csp);
+            return false;
+        }
+
+        //arcs of the other constraints of x
+        for (j, c2) in constraints.iter().enumerate().filter(|(j, _)| *j != i) {
+            if c2.scp().iter().any(|y| y.label() == &x) {
+                for y in c2.scp().iter().filter(|y| y.label() != &x && !csp.past().contains(y.label())) {
+                    push(&mut queue, &mut queued, (j, y.label().clone()), monitor);
+                }
+            }
+        }
+        //supports of the triangles where x is a witness
+        if full {
+            for y in graph.neighbours(&x) {
+                for z in graph.thirds(&x, &y) {
+                    if !csp.past().contains(&y) {
+                        for j in &graph.binary[&(y.clone(), z.clone())] {
+                            push(&mut queue, &mut queued, (*j, y.clone()), monitor);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    monitor.on_enforce_end();
+    monitor.on_domain_snapshot(csp);
+    true
+}
+
+fn push<M: Monitor>(queue: &mut VecDeque<(usize, String)>, queued: &mut HashSet<(usize, String)>,
+                    arc: (usize, String), monitor: &mut M) {
+    if queued.insert(arc.clone()) {
+        monitor.on_enqueue();
+        queue.push_back(arc);
+    }
+}
+
+//removes the values of x without path consistent support on c, Some(reason) if any
+fn revise_rpc<M: Monitor, T: OrdT>(csp: &Csp<T>, graph: &Graph, c: &Rc<dyn Constraint<T>>, x: &ExVar<T>,
+                                   monitor: &mut M) -> Option<Vec<Literal<T>>> {
+    monitor.on_revision_check();
+    let y = c.scp().iter().find(|y| y.label() != x.label())?.clone();
+    let thirds = graph.thirds(x.label(), y.label());
+    let doms: Vec<Vec<T>> = thirds.iter().map(|z| csp.vars()[z].valid_values()).collect();
+    let mut changed = false;
+    for a in x.valid_values() {
+        let supported = y.valid_values().iter().any(|b| {
+            allows(c, x.label(), &a, b, monitor)
+                && thirds.iter().zip(&doms).all(|(z, dom)| dom.iter().any(|v|
+                    graph.compatible(csp, x.label(), &a, z, v, monitor)
+                        && graph.compatible(csp, y.label(), b, z, v, monitor)))
+        });
+        if !supported {
+            monitor.on_value_deleted();
+            x.dom_mut().remove_value(&a, csp.level());
+            changed = true;
+        }
+    }
+    let mut involved = thirds;
+    involved.push(y.label().clone());
+    changed.then(|| removed_of(csp, &involved))
+}
+
+/**************************************
+            NIC
+***************************************/
+
+impl<M: Monitor, T: OrdT, R: Revise<M, T>> Scheme<M, T, R> for NIC {
+    fn enforce(&mut self, csp: &mut Csp<T>, events: Vec<TriggerEvent>, _revise: &mut R, monitor: &mut M) -> bool {
+        monitor.on_enforce_start();
+        let graph = Graph::new(csp);
+        let mut queue: VecDeque<String> = VecDeque::new();
+        let mut queued: HashSet<String> = HashSet::new();
+        let mut labels: Vec<String> = events.iter()
+            .filter(|e| e.kind().is_domain_change())
+            .filter_map(|e| e.variable().cloned())
+            .collect();
+        labels.sort();
+        for x in labels {
+            if queued.insert(x.clone()) {
+                monitor.on_enqueue();
+                queue.push_back(x);
+            }
+        }
+
+        //the neighbourhood of w depends on the domains of N(w)
+        while let Some(x) = queue.pop_front() {
+            monitor.on_dequeue();
+            if monitor.interrupted() {
+                monitor.on_enforce_end();
+                return false; //interrupted (see Limiter::reached)
+            }
+            queued.remove(&x);
+            let future: Vec<String> = graph.neighbours(&x).into_iter().filter(|w| !csp.past().contains(w)).collect();
+            for w in future {
+                match revise_nic(csp, &graph, &w, monitor) {
+                    Some((c, reason, removed)) => {
+                        let var = csp.vars()[&w].clone();
+                        csp.explain_removals_with(&c, &var, removed, reason);
+                        if var.dom().is_empty() {
+                            csp.explain_wipeout(&c, &var);
+                            monitor.on_domain_wipeout();
+                            monitor.on_enforce_end();
+                            monitor.on_domain_snapshot(csp);
+                            return false;
+                        }
+                        if queued.insert(w.clone()) {
+                            monitor.on_enqueue();
+                            queue.push_back(w);
+                        }
+                    }
+                    None => monitor.on_revise_fruitless(),
+                }
+            }
+        }
+        monitor.on_enforce_end();
+        monitor.on_domain_snapshot(csp);
+        true
+    }
+}
+
+type NicRemoval<T> = (Rc<dyn Constraint<T>>, Vec<Literal<T>>, Vec<T>);
+
+//removes the values of w not extending to N(w): a constraint of w, the reason and the removed values
+fn revise_nic<M: Monitor, T: OrdT>(csp: &Csp<T>, graph: &Graph, w: &str, monitor: &mut M) -> Option<NicRemoval<T>> {
+    monitor.on_revision_check();
+    let neighbours = graph.neighbours(w);
+    let mut closed: HashSet<&str> = neighbours.iter().map(|y| y.as_str()).collect();
+    closed.insert(w);
+    let local: Vec<Rc<dyn Constraint<T>>> = csp.constraints().iter()
+        .filter(|c| c.scp().iter().all(|y| closed.contains(y.label().as_str())))
+        .cloned()
+        .collect();
+    let vars: Vec<Rc<ExVar<T>>> = neighbours.iter().map(|y| csp.vars()[y].clone()).collect();
+
+    let x = csp.vars()[w].clone();
+    let mut removed = vec![];
+    for a in x.valid_values() {
+        if !extend(&mut vec![vv(w.to_string(), a.clone())], &vars, &local, monitor) {
+            monitor.on_value_deleted();
+            x.dom_mut().remove_value(&a, csp.level());
+            removed.push(a);
+        }
+    }
+    if removed.is_empty() {
+        return None;
+    }
+    let c = local.iter().find(|c| c.scp().iter().any(|y| y.label() == w))?.clone();
+    Some((c, removed_of(csp, &neighbours), removed))
+}
+
+//backtracking over the neighbourhood: the constraints are checked as soon as they are covered
+fn extend<M: Monitor, T: OrdT>(asn: &mut Vec<VValue<T>>, rest: &[Rc<ExVar<T>>], local: &[Rc<dyn Constraint<T>>],
+                               monitor: &mut M) -> bool {
+    let Some((y, rest)) = rest.split_first() else {
+        return true;
+    };
+    for b in y.valid_values() {
+        asn.push(vv(y.label().clone(), b));
+        let consistent = local.iter()
+            .filter(|c| c.scp().iter().any(|z| z.label() == y.label()) && c.is_covered(asn))
+            .all(|c| {
+                monitor.on_constraint_check();
+                c.check_assignment(asn).to_bool().unwrap()
+            });
+        if consistent && extend(asn, rest, local, monitor) {
+            asn.pop();
+            return true;
+        }
+        asn.pop();
+    }
+    false
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::Expr;
+    use crate::csp::ast::pred::Pred;
+    use std::rc::Rc;
+    use crate::{dom, lt, neq, var};
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::event::{reduced, TriggerEvent};
+    use crate::csp::variable::extvar::generate_variables;
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::revise::{AC1, AC3};
+    use crate::solver::consistency::rpc::{LightMaxRPC, MaxRPC, NIC};
+    use crate::solver::consistency::scheme::{ArcOriented, Scheme};
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::limits::SearchLimits;
+
+    //x1 != x2, x2 != x3, x1 != x3 on {1, 2}, x4 on {1, 2, 3} with x3 < x4:
+    //arc consistent, the triangle has no path consistent support
+    fn triangle() -> Csp<i32> {
+        let mut vmap = generate_variables("x", 3, &dom![1, 2]);
+        vmap.extend(generate_variables("y", 1, &dom![1, 2, 3]));
+        let constraints: Vec<Rc<dyn Constraint<i32>>> = vec![
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x1"]), var!(vmap["x2"])))),
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x2"]), var!(vmap["x3"])))),
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x1"]), var!(vmap["x3"])))),
+            Rc::new(Intensional::from_pred(lt!(var!(vmap["x3"]), var!(vmap["y1"])))),
+        ];
+        Csp::new(vmap, constraints)
+    }
+
+    fn all(csp: &Csp<i32>) -> Vec<TriggerEvent> {
+        reduced(csp.vars().keys())
+    }
+
+    #[test]
+    fn rpc_stronger_than_ac() {
+        let mut csp = triangle();
+        let events = all(&csp);
+        assert!(ArcOriented.enforce(&mut csp, events, &mut AC3, &mut Statistics::default()));
+
+        for consistent in [
+            MaxRPC.enforce(&mut triangle(), all(&csp), &mut AC3, &mut Statistics::default()),
+            LightMaxRPC.enforce(&mut triangle(), all(&csp), &mut AC3, &mut Statistics::default()),
+            NIC.enforce(&mut triangle(), all(&csp), &mut AC3, &mut Statistics::default()),
+        ] {
+            assert!(!consistent);
+        }
+    }
+
+    #[test]
+    fn rpc_filtering() {
+        //x1 < x2 < x3 on {1, 2, 3} and x1 != x3: only (1, 2, 3)
+        let model = || {
+            let vmap = generate_variables("x", 3, &dom![1, 2, 3]);
+            Csp::new(vmap.clone(), vec![
+                Rc::new(Intensional::from_pred(lt!(var!(vmap["x1"]), var!(vmap["x2"])))),
+                Rc::new(Intensional::from_pred(lt!(var!(vmap["x2"]), var!(vmap["x3"])))),
+                Rc::new(Intensional::from_pred(neq!(var!(vmap["x1"]), var!(vmap["x3"])))),
+            ])
+        };
+        let mut stats = vec![];
+        for level in 0..3 {
+            let mut csp = model();
+            let events = all(&csp);
+            let mut s = Statistics::default();
+            let consistent = match level {
+                0 => MaxRPC.enforce(&mut csp, events, &mut AC1, &mut s),
+                1 => LightMaxRPC.enforce(&mut csp, events, &mut AC1, &mut s),
+                _ => NIC.enforce(&mut csp, events, &mut AC1, &mut s),
+            };
+            assert!(consistent);
+            assert!(csp.vars().values().all(|x| x.valid_size() == 1));
+            assert_eq!(s.value_deletions, 6);
+            stats.push(s);
+        }
+        assert!(stats.iter().all(|s| s.revise_calls > 0 && s.checks > 0 && s.nb_enqueue == 0));
+    }
+
+    #[test]
+    fn rpc_search() {
+        //x1 != x2, x2 != x3, x1 < x3 on {1, 2, 3}: 3 solutions
+        let model = || {
+            let vmap = generate_variables("x", 3, &dom![1, 2, 3]);
+            Csp::new(vmap.clone(), vec![
+                Rc::new(Intensional::from_pred(neq!(var!(vmap["x1"]), var!(vmap["x2"])))),
+                Rc::new(Intensional::from_pred(neq!(var!(vmap["x2"]), var!(vmap["x3"])))),
+                Rc::new(Intensional::from_pred(lt!(var!(vmap["x1"]), var!(vmap["x3"])))),
+            ])
+        };
+        let mut engine = Engine::new(Consistency::new(MaxRPC, AC1, Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut model()).is_sat());
+        assert_eq!(engine.limiter().solutions, 3);
+
+        let mut engine = Engine::new(Consistency::new(LightMaxRPC, AC1, Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut model()).is_sat());
+        assert_eq!(engine.limiter().solutions, 3);
+
+        let mut engine = Engine::new(Consistency::new(NIC, AC1, Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut model()).is_sat());
+        assert_eq!(engine.limiter().solutions, 3);
+    }
+}
@@ -0,0 +1,353 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Singleton Arc Consistency (SAC), preprocessing pass on top of a Consistency
+    - probe (x, a): Csp::assign(x = a) then enforce the consistency from x,
+      undone through the trailing (Csp::unassign, TriggerEvent::Backtrack)
+    - a probe ending on a wipeout removes a from dom(x) at the current level
+      (explained by the decisions of the branch) and the consistency is enforced again
+    - SAC-1: every value is probed again until a whole pass removes nothing
+    - SAC-Opt: the subproblem of each probe (its v-values) is kept; when some of its v-values are removed,
+      it is loaded back (x = a, then the v-values out of it removed without propagation) and only these
+      removals are propagated, x = a is removed once its subproblem wipes out (a value is probed once)
+    - an interrupted probe (Monitor::interrupted) removes nothing, enforce returns false
+***************************************/
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::{Domain, OrdT};
+use crate::csp::event::{reduced, TriggerEvent};
+use crate::csp::variable::vvalue::vv;
+use crate::instrumentation::monitor::Monitor;
+use crate::solver::consistency::consistency::{Consistency, Revise};
+use crate::solver::consistency::scheme::Scheme;
+
+//v-values (x, a) of the current domains
+type VValues<T> = BTreeSet<(String, T)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SacVariant {
+    Sac1,
+    SacOpt,
+}
+
+pub struct Sac {
+    pub variant: SacVariant,
+    //probes, incremental updates of a subproblem (SAC-Opt) and removals of the last enforce
+    pub probes: usize,
+    pub updates: usize,
+    pub removals: usize,
+}
+
+impl Sac {
+    pub fn new(variant: SacVariant) -> Self {
+        Self { variant, probes: 0, updates: 0, removals: 0 }
+    }
+
+    //false iff the csp has no singleton arc consistent closure (see csp.explanations().conflict())
+    pub fn enforce<M, S, R, T: OrdT>(&mut self, consistency: &mut Consistency<M, S, R, T>, csp: &mut Csp<T>) -> bool
+    where
+        S: Scheme<M, T, R>,
+        R: Revise<M, T>,
+        M: Monitor,
+    {
+        self.probes = 0;
+        self.updates = 0;
+        self.removals = 0;
+        let events = reduced(csp.vars().keys());
+        if !consistency.enforce_consistency(csp, events) {
+            return false;
+        }
+        match self.variant {
+            SacVariant::Sac1 => self.sac1(consistency, csp),
+            SacVariant::SacOpt => self.sac_opt(consistency, csp),
+        }
+    }
+
+    fn sac1<M, S, R, T: OrdT>(&mut self, consistency: &mut Consistency<M, S, R, T>, csp: &mut Csp<T>) -> bool
+    where
+        S: Scheme<M, T, R>,
+        R: Revise<M, T>,
+        M: Monitor,
+    {
+        loop {
+            let mut changed = false;
+            for (x, a) in future_values(csp) {
+                if alive(csp, &x, &a) && self.probe(consistency, csp, &x, &a).is_none() {
+                    if consistency.monitor().interrupted() || !self.remove(consistency, csp, &x, a) {
+                        return false;
+                    }
+                    changed = true;
+                }
+            }
+            if !changed {
+                return true;
+            }
+        }
+    }
+
+    fn sac_opt<M, S, R, T: OrdT>(&mut self, consistency: &mut Consistency<M, S, R, T>, csp: &mut Csp<T>) -> bool
+    where
+        S: Scheme<M, T, R>,
+        R: Revise<M, T>,
+        M: Monitor,
+    {
+        //subproblem of each (x, a): v-values left by its probe, then by its updates
+        let mut left: HashMap<(String, T), VValues<T>> = HashMap::new();
+        let mut pending: VecDeque<(String, T)> = future_values(csp).into_iter().collect();
+        let mut queued: HashSet<(String, T)> = pending.iter().cloned().collect();
+
+        while let Some((x, a)) = pending.pop_front() {
+            queued.remove(&(x.clone(), a.clone()));
+            if !alive(csp, &x, &a) {
+                continue;
+            }
+            let subproblem = match left.get(&(x.clone(), a.clone())) {
+                Some(values) => self.update(consistency, csp, &x, &a, values),
+                None => self.probe(consistency, csp, &x, &a),
+            };
+            match subproblem {
+                Some(values) => {
+                    left.insert((x, a), values);
+                }
+                None if consistency.monitor().interrupted() => return false,
+                None => {
+                    let before = snapshot(csp);
+                    if !self.remove(consistency, csp, &x, a) {
+                        return false;
+                    }
+                    let after = snapshot(csp);
+                    let removed: VValues<T> = before.difference(&after).cloned().collect();
+                    for (p, values) in &left {
+                        if after.contains(p) && !queued.contains(p) && !values.is_disjoint(&removed) {
+                            queued.insert(p.clone());
+                            pending.push_back(p.clone());
+                        }
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    //v-values left by x = a, None on wipeout
+    fn probe<M, S, R, T: OrdT>(&mut self, consistency: &mut Consistency<M, S, R, T>, csp: &mut Csp<T>,
+                               x: &str, a: &T) -> Option<VValues<T>>
+    where
+        S: Scheme<M, T, R>,
+        R: Revise<M, T>,
+        M: Monitor,
+    {
+        self.probes += 1;
+        let lvl = csp.level() + 1;
+        csp.assign(vv(x.to_string(), a.clone()));
+        let events = csp.decision_events(x);
+        let consistent = consistency.enforce_consistency(csp, events);
+        let values = consistent.then(|| snapshot(csp));
+        csp.unassign();
+        consistency.notify(&TriggerEvent::Backtrack(lvl));
+        values
+    }
+
+    //subproblem of x = a loaded back from its v-values, the v-values removed since are propagated,
+    //None on wipeout
+    fn update<M, S, R, T: OrdT>(&mut self, consistency: &mut Consistency<M, S, R, T>, csp: &mut Csp<T>,
+                                x: &str, a: &T, values: &VValues<T>) -> Option<VValues<T>>
+    where
+        S: Scheme<M, T, R>,
+        R: Revise<M, T>,
+        M: Monitor,
+    {
+        self.updates += 1;
+        let current = snapshot(csp);
+        let removed: BTreeSet<&String> = values.difference(&current).map(|(y, _)| y).collect();
+        let lvl = csp.level() + 1;
+        csp.assign(vv(x.to_string(), a.clone()));
+        //the rest of the subproblem is already arc consistent: no event
+        for (y, b) in current.difference(values) {
+            csp.vars()[y].dom_mut().remove_value(b, lvl);
+        }
+        csp.vars().values().for_each(|v| { v.take_events(); });
+        //a domain of the subproblem may be emptied by the removals themselves
+        let consistent = csp.vars().values().all(|v| !v.dom().is_empty())
+            && consistency.enforce_consistency(csp, reduced(removed));
+        let subproblem = consistent.then(|| snapshot(csp));
+        csp.unassign();
+        consistency.notify(&TriggerEvent::Backtrack(lvl));
+        subproblem
+    }
+
+    //a is not singleton arc consistent: remove it and propagate, false on domain wipeout
+    fn remove<M, S, R, T: OrdT>(&mut self, consistency: &mut Consistency<M, S, R, T>, csp: &mut Csp<T>,
+                                x: &str, a: T) -> bool
+    where
+        S: Scheme<M, T, R>,
+        R: Revise<M, T>,
+        M: Monitor,
+    {
+        self.removals += 1;
+        let var = csp.vars()[x].clone();
+        var.dom_mut().remove_value(&a, csp.level());
+        consistency.monitor_mut().on_value_deleted();
+        csp.explain_bound("sac", &var, vec![a]);
+        if var.dom().is_empty() {
+            consistency.monitor_mut().on_domain_wipeout();
+            return false;
+        }
+        let events = var.take_events();
+        consistency.enforce_consistency(csp, events)
+    }
+}
+
+//v-values of the future variables, in label order
+fn future_values<T: OrdT>(csp: &Csp<T>) -> Vec<(String, T)> {
+    let mut labels: Vec<&String> = csp.vars().keys().filter(|x| !csp.past().contains(x)).collect();
+    labels.sort();
+    labels.into_iter()
+        .flat_map(|x| csp.vars()[x].valid_values().into_iter().map(move |a| (x.clone(), a)))
+        .collect()
+}
+
+fn snapshot<T: OrdT>(csp: &Csp<T>) -> VValues<T> {
+    csp.vars().iter()
+        .flat_map(|(x, v)| v.valid_values().into_iter().map(move |a| (x.clone(), a)))
+        .collect()
+}
+
+fn alive<T: OrdT>(csp: &Csp<T>, x: &str, a: &T) -> bool {
+    csp.vars()[x].valid_values().contains(a)
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::{Expr, AExpr};
+    use crate::csp::ast::pred::Pred;
+    use crate::csp::ast::formula::Formula;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use crate::{atom, base, cst, dom, eq, le, neq, or, var};
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::domain::Domain;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::event::reduced;
+    use crate::csp::variable::extvar::{generate_variables, ExVar};
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::revise::{AC1, AC3rm};
+    use crate::solver::consistency::sac::{Sac, SacVariant};
+    use crate::solver::consistency::scheme::ArcOriented;
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::limits::SearchLimits;
+
+    //x1 = 1 -> x2 = 1, x1 = 1 -> x3 = 1, x2 != x3 on {1, 2}: arc consistent, x1 = 1 is not SAC
+    fn implications() -> Csp<i32> {
+        let vmap: HashMap<String, Rc<ExVar<i32>>> = generate_variables("x", 3, &dom![1, 2]);
+        let implies = |x: &Rc<ExVar<i32>>, y: &Rc<ExVar<i32>>| Intensional::new(vec![x.clone(), y.clone()], Rc::new(or!(
+            atom!(eq!(base!(var!(x)), base!(cst!(2)))),
+            atom!(eq!(base!(var!(y)), base!(cst!(1))))
+        )));
+        Csp::new(vmap.clone(), vec![
+            Rc::new(implies(&vmap["x1"], &vmap["x2"])),
+            Rc::new(implies(&vmap["x1"], &vmap["x3"])),
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x2"]), var!(vmap["x3"])))),
+        ])
+    }
+
+    #[test]
+    fn sac_removes_more_than_ac() {
+        let mut csp = implications();
+        let mut consistency = Consistency::new(ArcOriented, AC1, Statistics::default());
+        let events = reduced(csp.vars().keys());
+        assert!(consistency.enforce_consistency(&mut csp, events));
+        assert!(csp.vars().values().all(|v| v.dom().size() == 2));
+
+        let mut sac = Sac::new(SacVariant::Sac1);
+        assert!(sac.enforce(&mut consistency, &mut csp));
+        assert_eq!(csp.vars()["x1"].valid_values(), vec![2]);
+        assert_eq!(csp.vars()["x2"].valid_values(), vec![1, 2]);
+        assert_eq!(csp.vars()["x3"].valid_values(), vec![1, 2]);
+        assert_eq!(sac.removals, 1);
+        //probes are undone: back to level 0, removal explained
+        assert_eq!(csp.level(), 0);
+        assert!(csp.explanations().why("x1", &1).is_some());
+    }
+
+    #[test]
+    fn sac_opt_fewer_probes() {
+        let mut sac1 = Sac::new(SacVariant::Sac1);
+        let mut csp = implications();
+        assert!(sac1.enforce(&mut Consistency::new(ArcOriented, AC3rm::new(), Statistics::default()), &mut csp));
+        let expected: Vec<Vec<i32>> = ["x1", "x2", "x3"].iter().map(|x| csp.vars()[*x].valid_values()).collect();
+
+        let mut opt = Sac::new(SacVariant::SacOpt);
+        let mut csp = implications();
+        assert!(opt.enforce(&mut Consistency::new(ArcOriented, AC3rm::new(), Statistics::default()), &mut csp));
+        let domains: Vec<Vec<i32>> = ["x1", "x2", "x3"].iter().map(|x| csp.vars()[*x].valid_values()).collect();
+        assert_eq!(domains, expected);
+        assert!(opt.probes < sac1.probes);
+    }
+
+    #[test]
+    fn sac_opt_updates() {
+        //x1 <= x4 and the implications x4 = 1 -> x2 = 1, x4 = 1 -> x3 = 1, x2 != x3 on {1, 2}:
+        //x4 = 1 is probed last, the subproblem of x1 = 1 is updated instead of probed again
+        let model = || {
+            let vmap: HashMap<String, Rc<ExVar<i32>>> = generate_variables("x", 4, &dom![1, 2]);
+            let implies = |x: &Rc<ExVar<i32>>, y: &Rc<ExVar<i32>>| Intensional::new(vec![x.clone(), y.clone()], Rc::new(or!(
+                atom!(eq!(base!(var!(x)), base!(cst!(2)))),
+                atom!(eq!(base!(var!(y)), base!(cst!(1))))
+            )));
+            Csp::new(vmap.clone(), vec![
+                Rc::new(Intensional::from_pred(le!(var!(vmap["x1"]), var!(vmap["x4"])))),
+                Rc::new(implies(&vmap["x4"], &vmap["x2"])),
+                Rc::new(implies(&vmap["x4"], &vmap["x3"])),
+                Rc::new(Intensional::from_pred(neq!(var!(vmap["x2"]), var!(vmap["x3"])))),
+            ])
+        };
+
+        let mut sac1 = Sac::new(SacVariant::Sac1);
+        let mut csp = model();
+        assert!(sac1.enforce(&mut Consistency::new(ArcOriented, AC3rm::new(), Statistics::default()), &mut csp));
+        let expected: Vec<Vec<i32>> = ["x1", "x2", "x3", "x4"].iter().map(|x| csp.vars()[*x].valid_values()).collect();
+        assert_eq!(expected[3], vec![2]);
+
+        let mut opt = Sac::new(SacVariant::SacOpt);
+        let mut csp = model();
+        assert!(opt.enforce(&mut Consistency::new(ArcOriented, AC3rm::new(), Statistics::default()), &mut csp));
+        let domains: Vec<Vec<i32>> = ["x1", "x2", "x3", "x4"].iter().map(|x| csp.vars()[*x].valid_values()).collect();
+        assert_eq!(domains, expected);
+        //each value probed once
+        assert_eq!((opt.probes, opt.updates, opt.removals), (8, 1, 1));
+        assert!(sac1.probes > opt.probes);
+    }
+
+    #[test]
+    fn sac_inconsistent() {
+        //x2 = x1, x3 = x1, x2 != x3: arc consistent but no solution
+        for variant in [SacVariant::Sac1, SacVariant::SacOpt] {
+            let vmap = generate_variables("x", 3, &dom![1, 2]);
+            let mut csp = Csp::new(vmap.clone(), vec![
+                Rc::new(Intensional::from_pred(eq!(var!(vmap["x1"]), var!(vmap["x2"])))),
+                Rc::new(Intensional::from_pred(eq!(var!(vmap["x1"]), var!(vmap["x3"])))),
+                Rc::new(Intensional::from_pred(neq!(var!(vmap["x2"]), var!(vmap["x3"])))),
+            ]);
+            let mut consistency = Consistency::new(ArcOriented, AC1, Statistics::default());
+            assert!(!Sac::new(variant).enforce(&mut consistency, &mut csp));
+            assert!(csp.explanations().conflict().is_some());
+        }
+    }
+
+    #[test]
+    fn sac_before_search() {
+        let mut csp = implications();
+        let mut engine = Engine::new(Consistency::new(ArcOriented, AC1, Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(Sac::new(SacVariant::SacOpt).enforce(engine.consistency_mut(), &mut csp));
+        //x1 = 2 and x2 != x3
+        assert!(engine.search(&mut csp).is_sat());
+        assert_eq!(engine.limiter().solutions, 2);
+    }
+}
@@ -1,86 +1,128 @@
 /**************************************
 - Author: Clement Poncelet
 - Desc: Propagation scheme
-        - arc oriented (Algorithm 7:gac_enforce_arc)
+        - arc oriented (Algorithm 7:gac_enforce_arc), FIFO of arcs queued once
         - variable oriented (Algorithm 9:gac_enforce_var)
   Contains unit tests for arc oriented consistency enforcement (var oriented tests are into test/var.rs)
-- Optimization:
-    - output Option<TriggerEvent>
+  The events (see csp/event.rs) trigger the arcs whose revise function subscribes to their kind,
+  the events of a revised domain are taken from it.
+  A constraint with a custom filtering (Constraint::filter) is filtered instead of revising its arcs.
 ***************************************/
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::Rc;
+use crate::csp::constraint::constraint::Constraint;
 use crate::csp::csp::Csp;
+use crate::csp::event::{EventKind, TriggerEvent};
 use crate::csp::prelude::domain::{Domain, OrdT};
 use crate::csp::prelude::extvar::ExVar;
 use crate::instrumentation::monitor::Monitor;
 use crate::solver::consistency::arc::{Arc};
 use crate::solver::consistency::consistency::Revise;
+use crate::solver::consistency::propagator::filter;
 
 pub trait Scheme <M: Monitor, T: OrdT, R: Revise<M, T>> {
-    fn enforce(&mut self, csp: &mut Csp<T>, events: Vec<String>, revise: &mut R, monitor: &mut M) -> bool;
+    fn enforce(&mut self, csp: &mut Csp<T>, events: Vec<TriggerEvent>, revise: &mut R, monitor: &mut M) -> bool;
+    //Trailing: forget the data structures updated at a level >= lvl (see Revise::restore_up_to)
+    fn restore_up_to(&mut self, _lvl: usize) {}
+}
+
+//kinds of the domain events, by variable
+pub(crate) fn by_variable(events: &[TriggerEvent]) -> HashMap<String, Vec<EventKind>> {
+    let mut kinds: HashMap<String, Vec<EventKind>> = HashMap::new();
+    for e in events {
+        if let Some(x) = e.variable() {
+            kinds.entry(x.clone()).or_default().push(e.kind());
+        }
+    }
+    kinds
+}
+
+//c is subscribed to one of kinds
+pub(crate) fn triggers<M: Monitor, T: OrdT, R: Revise<M, T>>(revise: &R, c: &Rc<dyn Constraint<T>>, kinds: &[EventKind]) -> bool {
+    kinds.iter().any(|k| revise.reacts_to(c, *k))
 }
 
 pub struct ArcOriented;
 
 impl<M: Monitor, T: OrdT, R: Revise<M, T>> Scheme<M, T, R> for ArcOriented {
-    fn enforce(&mut self, csp :&mut Csp<T>, events: Vec<String>, revise: &mut R, monitor: &mut M) -> bool {
+    fn enforce(&mut self, csp :&mut Csp<T>, events: Vec<TriggerEvent>, revise: &mut R, monitor: &mut M) -> bool {
         monitor.on_enforce_start();
-        // return next arc
-        let mut queue: Vec<Arc<T>> = vec![];
+        let events = by_variable(&events);
+        let constraints = csp.constraints().clone();
+        // return next arc (index of its constraint, variable)
+        let mut queue = ArcQueue::default();
 
-        //add a type arc ? <c,v>
         //for each arc
         //   not in past(P)
-        //   where y in scp(c) inter events and <> x
-        for c in csp.constraints() {
+        //   where y in scp(c) inter events (subscribed by c) and <> x
+        for (i, c) in constraints.iter().enumerate() {
             for x in c.scp() {
                 //arcs (c,v)
-                if !csp.past().contains(x.label()) {
-                    //not in past
-                    for y in c.scp() {
-                        if y.label() != x.label() && events.contains(&y.label()) {
-                            monitor.on_enqueue();
-                            queue.push(Arc { constraint: c.clone(), variable: x.clone() })
-                        }
-                    }
+                if !csp.past().contains(x.label()) &&
+                    c.scp().iter().any(|y| y.label() != x.label() && events.get(y.label()).is_some_and(|k| triggers(revise, c, k))) {
+                    queue.push(i, c, x, monitor);
                 }
             }
         }
 
         //Propagation
-        let mut step = 1;
-        while queue.len() > 0 {
-            println!("{}", format!("Step {} Q {}", step, queue.iter().map(|ar| ar.to_string()).collect::<Vec<_>>().join("")));
-            monitor.on_dequeue();
-            let arc_cx = queue.remove(0);
-            println!("Pick {} from Q", arc_cx);
+        while let Some((i, x)) = queue.pop(monitor) {
+            if monitor.interrupted() {
+                monitor.on_enforce_end();
+                return false; //interrupted (see Limiter::reached)
+            }
+            let c = &constraints[i];
+            if c.has_filter() {
+                let Ok(events) = filter(csp, c, monitor) else {
+                    monitor.on_domain_wipeout();
+                    monitor.on_enforce_end();
+                    monitor.on_domain_snapshot(csp);
+                    return false; //raise dom_wipeout
+                };
+                for (y, kinds) in by_variable(&events) {
+                    for (j, c2) in constraints.iter().enumerate() {
+                        if  (j != i || !c.idempotent()) &&
+                            c2.scp().iter().any(|v| *v.label() == y) &&
+                            triggers(revise, c2, &kinds) {
+                            for x in c2.scp().iter().filter(|x| *x.label() != y && !csp.past().contains(x.label())) {
+                                queue.push(j, c2, x, monitor);
+                            }
+                        }
+                    }
+                }
+                continue;
+            }
+            let arc_cx = Arc { constraint: constraints[i].clone(), variable: x };
+            let before = arc_cx.variable.valid_values();
+            arc_cx.variable.dom_mut().take_events();
             if revise.revise(&arc_cx, csp.level(), monitor) {
+                explain(csp, &arc_cx, before);
+                let kinds = arc_cx.variable.dom_mut().take_events();
                 if arc_cx.variable.dom().is_empty() {
+                    csp.explain_wipeout(&arc_cx.constraint, &arc_cx.variable);
                     monitor.on_domain_wipeout();
                     monitor.on_enforce_end();
                     monitor.on_domain_snapshot(csp);
                     return false; //raise dom_wipeout
                 }
-                for c in csp.constraints() {
+                for (j, c) in constraints.iter().enumerate() {
                     //assumption: normalized csp
-                    if  c.to_string() != arc_cx.constraint.to_string() &&
-                        c.scp().iter().any(|v| v.label() == arc_cx.variable.label()) {
+                    if  j != i &&
+                        c.scp().iter().any(|v| v.label() == arc_cx.variable.label()) &&
+                        triggers(revise, c, &kinds) {
                         //c' != c && x in Scp(c')
                         for x in c.scp() {
                             if  x.label() != arc_cx.variable.label() &&
                                 !csp.past().contains(x.label()) {
-                                monitor.on_enqueue();
                                 //x' != x && x' not in past
-                                queue.push(Arc { constraint: c.clone(), variable: x.clone() })
+                                queue.push(j, c, x, monitor);
                             }
                         }
                     }
                 }
             } else {
                 monitor.on_revise_fruitless();
-                println!("Fruitless");
             }
-            step +=1;
         }
         monitor.on_enforce_end();
         monitor.on_domain_snapshot(csp);
@@ -88,14 +130,47 @@ impl<M: Monitor, T: OrdT, R: Revise<M, T>> Scheme<M, T, R> for ArcOriented {
     }
 }
 
+//FIFO of arcs (index of the constraint, variable), an arc is queued once
+//(a single arc for a custom filtering constraint, its filter handles the whole scope)
+struct ArcQueue<T: OrdT> {
+    queue: VecDeque<(usize, Rc<ExVar<T>>)>,
+    queued: HashSet<(usize, String)>,
+}
+
+impl<T: OrdT> Default for ArcQueue<T> {
+    fn default() -> Self {
+        Self { queue: VecDeque::new(), queued: HashSet::new() }
+    }
+}
+
+impl<T: OrdT> ArcQueue<T> {
+    fn push<M: Monitor>(&mut self, i: usize, c: &Rc<dyn Constraint<T>>, x: &Rc<ExVar<T>>, monitor: &mut M) {
+        let x = if c.has_filter() { &c.scp()[0] } else { x };
+        if self.queued.insert((i, x.label().clone())) {
+            monitor.on_enqueue();
+            self.queue.push_back((i, x.clone()));
+        }
+    }
+
+    fn pop<M: Monitor>(&mut self, monitor: &mut M) -> Option<(usize, Rc<ExVar<T>>)> {
+        let (i, x) = self.queue.pop_front()?;
+        monitor.on_dequeue();
+        self.queued.remove(&(i, x.label().clone()));
+        Some((i, x))
+    }
+}
+
 pub struct VariableOriented;
 impl<M: Monitor, T: OrdT, R: Revise<M, T>> Scheme<M, T, R> for VariableOriented {
-    fn enforce(&mut self, csp: &mut Csp<T>, events: Vec<String>, revise: &mut R, monitor: &mut M) -> bool {
+    fn enforce(&mut self, csp: &mut Csp<T>, events: Vec<TriggerEvent>, revise: &mut R, monitor: &mut M) -> bool {
         monitor.on_enforce_start();
+        //kinds of the events not yet propagated, by variable
+        let mut pending = by_variable(&events);
 
-        let mut queue: Vec<Rc<ExVar<T>>> = vec![];
+        let mut queue: VecDeque<Rc<ExVar<T>>> = VecDeque::new();
         let mut stamp_var: HashMap<String, usize> = HashMap::new();
-        let mut stamp_ctr: HashMap<String, usize> = HashMap::new();
+        //stamps of the constraints by index (constraints sharing a scope share their label)
+        let mut stamp_ctr: HashMap<usize, usize> = HashMap::new();
         let mut time = 0;
 
         //lexicographical sort
@@ -104,28 +179,58 @@ impl<M: Monitor, T: OrdT, R: Revise<M, T>> Scheme<M, T, R> for VariableOriented
 
         //insert: algorithm 10
         for v in vars {
-            if events.contains(&v.label()) {
+            if pending.contains_key(v.label()) {
                 insert(&mut queue, &mut stamp_var, v, &mut time, monitor);
             }
         }
 
-        let mut step = 1;
         while queue.len() > 0 {
-            println!("{}", format!("Step {} Q {}", step, queue.iter().map(|v| v.label().clone()).collect::<Vec<_>>().join(",")));
             monitor.on_dequeue();
-            let x = queue.remove(0);
-            println!("Pick {} from Q", x);
+            let x = queue.pop_front().unwrap();
+            if monitor.interrupted() {
+                monitor.on_enforce_end();
+                return false; //interrupted (see Limiter::reached)
+            }
+            let kinds = pending.remove(x.label()).unwrap_or_default();
 
-            for c in csp.constraints() {
-                if c.scp().contains(&x) && stamp_var.get(x.label()) > stamp_ctr.get(&c.label()) {
+            for (i, c) in csp.constraints().clone().into_iter().enumerate() {
+                let triggered = c.scp().contains(&x) && stamp_var.get(x.label()) > stamp_ctr.get(&i)
+                    && triggers(revise, &c, &kinds);
+                if triggered && c.has_filter() {
+                    let Ok(events) = filter(csp, &c, monitor) else {
+                        monitor.on_domain_wipeout();
+                        monitor.on_enforce_end();
+                        monitor.on_domain_snapshot(csp);
+                        return false; //raise dom_wipeout
+                    };
+                    //a constraint not idempotent is filtered again on its own events
+                    if !c.idempotent() {
+                        time += 1;
+                        stamp_ctr.insert(i, time);
+                    }
+                    for (y, kinds) in by_variable(&events) {
+                        pending.entry(y.clone()).or_default().extend(kinds);
+                        let y = csp.vars()[&y].clone();
+                        insert(&mut queue, &mut stamp_var, y, &mut time, monitor);
+                    }
+                    if c.idempotent() {
+                        time += 1;
+                        stamp_ctr.insert(i, time);
+                    }
+                } else if triggered {
                     for y in c.scp() {
                         if !csp.past().contains(y.label()) {
                             if x != *y
                                 || c.scp().iter()
-                                .any(|z| *z != x && stamp_var.get(z.label()) > stamp_ctr.get(&c.label())) {
-                                println!("Revise <{},{}>", c.label(), y.label());
-                                if revise.revise(&Arc { constraint: c.clone(), variable: y.clone() }, csp.level(), monitor) {
+                                .any(|z| *z != x && stamp_var.get(z.label()) > stamp_ctr.get(&i)) {
+                                let arc_cy = Arc { constraint: c.clone(), variable: y.clone() };
+                                let before = y.valid_values();
+                                y.dom_mut().take_events();
+                                if revise.revise(&arc_cy, csp.level(), monitor) {
+                                    explain(csp, &arc_cy, before);
+                                    pending.entry(y.label().clone()).or_default().extend(y.dom_mut().take_events());
                                     if y.dom().is_empty() {
+                                        csp.explain_wipeout(&c, y);
                                         monitor.on_domain_wipeout();
                                         monitor.on_enforce_end();
                                         monitor.on_domain_snapshot(csp);
@@ -133,17 +238,16 @@ impl<M: Monitor, T: OrdT, R: Revise<M, T>> Scheme<M, T, R> for VariableOriented
                                     }
                                     insert(&mut queue, &mut stamp_var, y.clone(), &mut time, monitor);
                                 } else {
-                                    println!("Fruitless");
                                     monitor.on_revise_fruitless();
                                 }
                             }
                         }
                     }
+                    //stamp of the last processing of c
+                    time += 1;
+                    stamp_ctr.insert(i, time);
                 }
-                time += 1;
-                stamp_ctr.entry(c.label()).or_insert(time);
             }
-            step +=1;
         }
         monitor.on_enforce_end();
         monitor.on_domain_snapshot(csp);
@@ -151,10 +255,19 @@ impl<M: Monitor, T: OrdT, R: Revise<M, T>> Scheme<M, T, R> for VariableOriented
     }
 }
 
+//helper to record why the values of before have been removed by revising arc
+fn explain<T: OrdT>(csp: &mut Csp<T>, arc: &Arc<T>, before: Vec<T>) {
+    let after = arc.variable.valid_values();
+    let removed = before.into_iter()
+        .filter(|a| !after.contains(a))
+        .collect();
+    csp.explain_removals(&arc.constraint, &arc.variable, removed);
+}
+
 //helper to insert values into queue
-fn insert<M:Monitor, T: OrdT>(queue : &mut Vec<Rc<ExVar<T>>>, stamp_var: &mut HashMap<String, usize>, v : Rc<ExVar<T>>,  time : &mut usize, monitor: &mut M) {
+fn insert<M:Monitor, T: OrdT>(queue : &mut VecDeque<Rc<ExVar<T>>>, stamp_var: &mut HashMap<String, usize>, v : Rc<ExVar<T>>,  time : &mut usize, monitor: &mut M) {
     monitor.on_enqueue();
-    queue.push(v.clone());
+    queue.push_back(v.clone());
     *time +=1;
     if let Some (t) =stamp_var.get_mut(&v.label().clone()) {
         *t = *time
@@ -177,12 +290,17 @@ mod tests {
     use crate::csp::domain::setdom::SetDom;
     use crate::csp::domain::domain::{Domain, OrdT};
     use crate::csp::variable::extvar::ExVar;
-    use crate::{add, and, atom, base, cst, eq, lt, or, var, var_dom};
+    use crate::{add, and, atom, base, cst, eq, le, lt, neq, or, var, var_dom};
     use crate::csp::constraint::intensional::Intensional;
     use crate::csp::csp::Csp;
+    use crate::csp::event::reduced;
     use crate::instrumentation::monitor::{NoMonitor, Statistics};
-    use crate::solver::consistency::revise::AC1;
-    use crate::solver::consistency::scheme::{ArcOriented, Scheme};
+    use crate::csp::truth::Truth;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::revise::{AC1, AC3};
+    use crate::solver::consistency::scheme::{ArcOriented, Scheme, VariableOriented};
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::limits::SearchLimits;
 
     fn setup_csp<'a, T: OrdT>() -> (Csp<i32>, Rc<ExVar<i32>>, Rc<ExVar<i32>>, Rc<ExVar<i32>>) {
         let dom = SetDom::new(vec![1, 2]);
@@ -212,7 +330,7 @@ mod tests {
     #[test]
     fn stand_alone_arc() {
         let mut csp = setup_csp::<i32>();
-        let vars = csp.0.vars().keys().cloned().collect();
+        let vars = reduced(csp.0.vars().keys());
         assert!(ArcOriented.enforce(&mut csp.0, vars, &mut AC1, &mut NoMonitor));
     }
 
@@ -261,7 +379,7 @@ mod tests {
 
         let mut csp = Csp::new(vmap, vec![Rc::new(c1), Rc::new(c2), Rc::new(c3), Rc::new(c4)]);
         let mut arc_scheme = ArcOriented;
-        let vars = csp.vars().keys().cloned().collect();
+        let vars = reduced(csp.vars().keys());
         let mut stats = Statistics::default();
         arc_scheme.enforce(&mut csp, vars, &mut AC1, &mut stats); //standalone
         //Note: step 7 (c_wz, w) is not fruitless since (w,0) has no supports for c_wz
@@ -274,4 +392,25 @@ mod tests {
         println!("Lasting {} millisecond(s) in average", avg.as_millis());
         println!("{:#?}", stats.domain_histogram);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn shared_scope_variable() {
+        //x <= y and x != y on {1, 2}: both constraints are processed, one solution (1, 2)
+        let dom = SetDom::new(vec![1, 2]);
+        let x = var_dom!("x".into(), dom.snapshot());
+        let y = var_dom!("y".into(), dom);
+        let mut vmap: HashMap<String, _> = HashMap::new();
+        vmap.insert("x".into(), x.clone());
+        vmap.insert("y".into(), y.clone());
+        let mut csp = Csp::new(vmap, vec![
+            Rc::new(Intensional::from_pred(le!(var!(x), var!(y)))),
+            Rc::new(Intensional::from_pred(neq!(var!(x), var!(y)))),
+        ]);
+
+        let mut engine = Engine::new(Consistency::new(VariableOriented, AC3, Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        let solution = engine.search(&mut csp).solution().cloned().expect("Should be satisfiable");
+        assert_eq!(csp.is_solution(&solution), Truth::True);
+        assert_eq!(engine.limiter().solutions, 1);
+    }
+}
@@ -0,0 +1,231 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Tree-clustering solver on a tree decomposition
+    - each constraint is placed in a bag covering its scope
+    - each bag is solved as an extensional relation (tuples over its variables satisfying its constraints),
+      d^(w+1) tuples at most for a treewidth w
+    - the relations are joined along the tree: semi-joins from the leaves to the root, then a
+      backtrack-free top-down extension (each child tuple agrees with its parent on the separator)
+***************************************/
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use crate::csp::constraint::constraint::cartesian_product;
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::OrdT;
+use crate::csp::variable::extvar::ExVar;
+use crate::csp::variable::vvalue::{make_assignment, vv, VValue};
+use crate::instrumentation::monitor::Monitor;
+use crate::solver::decomposition::tree::{Elimination, TreeDecomposition};
+
+//allowed tuples of a bag, values in the order of vars
+struct Relation<T: OrdT> {
+    vars: Vec<String>,
+    tuples: Vec<Vec<T>>,
+}
+
+impl<T: OrdT> Relation<T> {
+    //values of the given variables in tuple
+    fn project(&self, tuple: &[T], on: &[String]) -> Vec<T> {
+        on.iter()
+            .map(|x| tuple[self.vars.iter().position(|y| y == x).unwrap()].clone())
+            .collect()
+    }
+}
+
+pub struct TreeClustering<M: Monitor> {
+    heuristic: Elimination,
+    monitor: M,
+    decomposition: Option<TreeDecomposition>,
+}
+
+impl<M: Monitor> TreeClustering<M> {
+    pub fn new(heuristic: Elimination, monitor: M) -> Self {
+        Self { heuristic, monitor, decomposition: None }
+    }
+
+    pub fn monitor(&self) -> &M {&self.monitor}
+    //decomposition used by the last solve
+    pub fn decomposition(&self) -> Option<&TreeDecomposition> { self.decomposition.as_ref() }
+    pub fn treewidth(&self) -> Option<usize> { self.decomposition.as_ref().map(|td| td.treewidth()) }
+
+    //a solution (sorted by label) on the current domains, None if there is none (csp is not modified)
+    pub fn solve<T: OrdT>(&mut self, csp: &Csp<T>) -> Option<Vec<VValue<T>>> {
+        let td = TreeDecomposition::new(csp, self.heuristic);
+        let relations = self.relations(csp, &td);
+        self.decomposition = Some(td);
+        let mut relations = relations?;
+        let td = self.decomposition.as_ref().unwrap();
+        //no variable: no bag to root the tree at
+        if td.bags.is_empty() {
+            self.monitor.on_solution();
+            return Some(vec![]);
+        }
+
+        //rooted at bag 0: parents and bags from the root to the leaves
+        let mut parent: Vec<Option<usize>> = vec![None; td.bags.len()];
+        let mut visit = vec![0];
+        let mut i = 0;
+        while i < visit.len() {
+            let b = visit[i];
+            for n in td.neighbours(b) {
+                if n != 0 && parent[n].is_none() {
+                    parent[n] = Some(b);
+                    visit.push(n);
+                }
+            }
+            i += 1;
+        }
+
+        //semi-joins from the leaves: parent tuples with a matching child tuple
+        for c in visit.iter().rev() {
+            let Some(p) = parent[*c] else { continue };
+            let separator = separator(&relations[*c], &relations[p]);
+            let keys: HashSet<Vec<T>> = relations[*c].tuples.iter()
+                .map(|t| relations[*c].project(t, &separator))
+                .collect();
+            let filtered: Vec<Vec<T>> = relations[p].tuples.iter()
+                .filter(|t| {
+                    self.monitor.on_constraint_check();
+                    keys.contains(&relations[p].project(t, &separator))
+                })
+                .cloned()
+                .collect();
+            if filtered.is_empty() {
+                self.monitor.on_domain_wipeout();
+                return None;
+            }
+            relations[p].tuples = filtered;
+        }
+
+        //backtrack-free extension from the root
+        let mut solution: HashMap<String, T> = HashMap::new();
+        for b in visit {
+            let relation = &relations[b];
+            let tuple = relation.tuples.iter()
+                .find(|t| relation.vars.iter().zip(t.iter())
+                    .all(|(x, a)| solution.get(x).is_none_or(|v| v == a)))
+                .expect("Semi-joined relations");
+            for (x, a) in relation.vars.iter().zip(tuple) {
+                solution.insert(x.clone(), a.clone());
+            }
+        }
+        self.monitor.on_solution();
+        let mut asn: Vec<VValue<T>> = solution.into_iter().map(|(x, a)| vv(x, a)).collect();
+        asn.sort_by(|a, b| a.label.cmp(&b.label));
+        Some(asn)
+    }
+
+    //relation of each bag, None if one of them is empty
+    fn relations<T: OrdT>(&mut self, csp: &Csp<T>, td: &TreeDecomposition) -> Option<Vec<Relation<T>>> {
+        let mut placed: Vec<Vec<usize>> = vec![vec![]; td.bags.len()];
+        for (i, c) in csp.constraints().iter().enumerate() {
+            let bag = td.bags.iter()
+                .position(|b| c.scp().iter().all(|x| b.contains(x.label())))
+                .expect("Scope covered by a bag");
+            placed[bag].push(i);
+        }
+
+        let mut relations = vec![];
+        for (bag, constraints) in td.bags.iter().zip(placed) {
+            let vars: Vec<Rc<ExVar<T>>> = bag.iter().map(|x| csp.vars()[x].clone()).collect();
+            let mut tuples = vec![];
+            for values in cartesian_product(&vars) {
+                let asn = make_assignment(&vars, values.clone());
+                if constraints.iter().all(|i| {
+                    self.monitor.on_constraint_check();
+                    csp.constraints()[*i].check_assignment(&asn).to_bool().unwrap()
+                }) {
+                    tuples.push(values);
+                }
+            }
+            if tuples.is_empty() {
+                self.monitor.on_domain_wipeout();
+                return None;
+            }
+            relations.push(Relation { vars: bag.iter().cloned().collect(), tuples });
+        }
+        Some(relations)
+    }
+}
+
+fn separator<T: OrdT>(a: &Relation<T>, b: &Relation<T>) -> Vec<String> {
+    a.vars.iter().filter(|x| b.vars.contains(x)).cloned().collect()
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::Expr;
+    use crate::csp::ast::pred::Pred;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use crate::{dom, lt, neq, var};
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::truth::Truth;
+    use crate::csp::variable::extvar::generate_variables;
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::decomposition::clustering::TreeClustering;
+    use crate::solver::decomposition::tree::Elimination;
+
+    #[test]
+    fn tree_clustering_colouring() {
+        //wheel: x1 linked to the cycle x2 .. x6, 3 colours
+        let vmap = generate_variables("x", 6, &dom![1, 2, 3]);
+        let mut constraints: Vec<Rc<dyn Constraint<i32>>> = vec![];
+        for i in 2..=6 {
+            let next = if i == 6 { 2 } else { i + 1 };
+            constraints.push(Rc::new(Intensional::from_pred(neq!(var!(vmap["x1"]), var!(vmap[&format!("x{}", i)])))));
+            constraints.push(Rc::new(Intensional::from_pred(neq!(
+                var!(vmap[&format!("x{}", i)]), var!(vmap[&format!("x{}", next)])))));
+        }
+        //odd cycle: the wheel needs 4 colours
+        let csp = Csp::new(vmap.clone(), constraints.clone());
+        let mut solver = TreeClustering::new(Elimination::MinFill, Statistics::default());
+        assert!(solver.solve(&csp).is_none());
+        assert_eq!(solver.treewidth(), Some(3));
+
+        let vmap = generate_variables("x", 6, &dom![1, 2, 3, 4]);
+        let csp = Csp::new(vmap.clone(), constraints.iter().map(|c| {
+            let scp = c.scp();
+            Rc::new(Intensional::from_pred(neq!(var!(vmap[scp[0].label()]), var!(vmap[scp[1].label()]))))
+                as Rc<dyn Constraint<i32>>
+        }).collect());
+        for heuristic in [Elimination::MinFill, Elimination::MinDegree] {
+            let mut solver = TreeClustering::new(heuristic, Statistics::default());
+            let solution = solver.solve(&csp).unwrap();
+            assert_eq!(csp.is_solution(&solution), Truth::True);
+            assert!(solver.decomposition().unwrap().is_valid(&csp));
+        }
+    }
+
+    #[test]
+    fn tree_clustering_empty() {
+        let csp: Csp<i32> = Csp::new(HashMap::new(), vec![]);
+        let mut solver = TreeClustering::new(Elimination::MinFill, Statistics::default());
+        assert_eq!(solver.solve(&csp), Some(vec![]));
+        assert_eq!(solver.monitor().solutions, 1);
+    }
+
+    #[test]
+    fn tree_clustering_polynomial_in_domain() {
+        //chain x1 < x2 < ... < x8 on 0..40: treewidth 1, d^2 checks per bag
+        let vmap = generate_variables("x", 8, &SetDom::new((0..40).collect()));
+        let constraints: Vec<Rc<dyn Constraint<i32>>> = (1..8)
+            .map(|i| Rc::new(Intensional::from_pred(lt!(
+                var!(vmap[&format!("x{}", i)]), var!(vmap[&format!("x{}", i + 1)])))) as Rc<dyn Constraint<i32>>)
+            .collect();
+        let csp = Csp::new(vmap, constraints);
+        let mut solver = TreeClustering::new(Elimination::MinDegree, Statistics::default());
+        let solution = solver.solve(&csp).unwrap();
+        assert_eq!(csp.is_solution(&solution), Truth::True);
+        assert_eq!(solver.treewidth(), Some(1));
+        //7 bags of 1600 tuples, semi-joins on at most 1600 tuples each
+        assert!(solver.monitor().checks <= 7 * 1600 * 2);
+    }
+}
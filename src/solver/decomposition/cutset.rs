@@ -0,0 +1,244 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Cycle-cutset solving of nearly acyclic networks
+    - cycle cutset: greedy feedback vertex set of Csp::primal_graph (variables of degree <= 1 are pruned,
+      then the variable of greatest degree joins the cutset)
+    - the assignments of the cutset are enumerated (CartesianWalker), each one is assigned on the csp,
+      the constraints linking the cutset revise the remaining forest,
+      then DAC along a rooted order of the forest and a backtrack-free assignment
+    - every cutset assignment is undone (Csp::unassign), the csp is left unchanged
+      (an empty cutset opens no level: the forest is solved on a Csp::snapshot, then restored)
+***************************************/
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::{Domain, OrdT};
+use crate::csp::domain::setdom::CartesianWalker;
+use crate::csp::truth::Truth;
+use crate::csp::variable::vvalue::{make_assignment, vv, VValue};
+use crate::instrumentation::monitor::Monitor;
+use crate::solver::consistency::arc::Arc;
+use crate::solver::consistency::consistency::Revise;
+use crate::solver::consistency::directional::{backtrack_free, dac};
+use crate::solver::consistency::revise::AC3;
+
+//greedy cycle cutset: the primal graph without these variables is a forest
+pub fn cycle_cutset<T: OrdT>(csp: &Csp<T>) -> Vec<String> {
+    let mut adjacency = adjacency(csp);
+    let mut cutset = vec![];
+    loop {
+        //variables of degree <= 1 are not on a cycle
+        while let Some(x) = adjacency.iter().find(|(_, n)| n.len() <= 1).map(|(x, _)| x.clone()) {
+            remove(&mut adjacency, &x);
+        }
+        let Some(x) = adjacency.iter()
+            .max_by(|(x, n), (y, m)| n.len().cmp(&m.len()).then(y.cmp(x)))
+            .map(|(x, _)| x.clone()) else {
+            break;
+        };
+        remove(&mut adjacency, &x);
+        cutset.push(x);
+    }
+    cutset.sort();
+    cutset
+}
+
+pub struct CycleCutset<M: Monitor> {
+    monitor: M,
+    cutset: Vec<String>,
+}
+
+impl<M: Monitor> CycleCutset<M> {
+    pub fn new(monitor: M) -> Self {
+        Self { monitor, cutset: vec![] }
+    }
+
+    pub fn monitor(&self) -> &M {&self.monitor}
+    //cutset of the last solve
+    pub fn cutset(&self) -> &[String] {&self.cutset}
+
+    //a solution (sorted by label) on the current domains, None if there is none
+    pub fn solve<T: OrdT>(&mut self, csp: &mut Csp<T>) -> Option<Vec<VValue<T>>> {
+        self.cutset = cycle_cutset(csp);
+        let order = self.order(csp);
+        let doms: Vec<Vec<T>> = self.cutset.iter().map(|x| csp.vars()[x].valid_values()).collect();
+
+        for values in CartesianWalker::new(doms) {
+            self.monitor.on_node();
+            let level = csp.level();
+            let snapshot = self.cutset.is_empty().then(|| csp.snapshot());
+            for (x, a) in self.cutset.iter().zip(&values) {
+                csp.assign(vv(x.clone(), a.clone()));
+            }
+            let solution = self.solve_forest(csp, &order, values);
+            while csp.level() > level {
+                csp.unassign();
+            }
+            if let Some(snapshot) = snapshot {
+                csp.restore(snapshot);
+            }
+            if let Some(mut solution) = solution {
+                self.monitor.on_solution();
+                solution.sort_by(|a, b| a.label.cmp(&b.label));
+                return Some(solution);
+            }
+            self.monitor.on_backtrack();
+        }
+        None
+    }
+
+    //cutset first, then each tree of the forest from its root (parents before children)
+    fn order<T: OrdT>(&self, csp: &Csp<T>) -> Vec<String> {
+        let mut adjacency = adjacency(csp);
+        for x in &self.cutset {
+            remove(&mut adjacency, x);
+        }
+        let mut order = self.cutset.clone();
+        let mut labels: Vec<&String> = adjacency.keys().collect();
+        labels.sort();
+        for root in labels {
+            if order.contains(root) {
+                continue;
+            }
+            let mut queue = VecDeque::from([root.clone()]);
+            order.push(root.clone());
+            while let Some(x) = queue.pop_front() {
+                for y in &adjacency[&x] {
+                    if !order.contains(y) {
+                        order.push(y.clone());
+                        queue.push_back(y.clone());
+                    }
+                }
+            }
+        }
+        order
+    }
+
+    //the cutset is assigned: forest filtered by the cutset, DAC, backtrack-free assignment
+    fn solve_forest<T: OrdT>(&mut self, csp: &mut Csp<T>, order: &[String], values: Vec<T>) -> Option<Vec<VValue<T>>> {
+        let vars: Vec<_> = self.cutset.iter().map(|x| csp.vars()[x].clone()).collect();
+        if csp.is_locally_consistent(&make_assignment(&vars, values)) == Truth::False {
+            return None;
+        }
+        let mut revise = AC3;
+        for c in csp.constraints().clone() {
+            let free: Vec<_> = c.scp().iter().filter(|y| !self.cutset.contains(y.label())).collect();
+            if free.len() == 1 && free.len() < c.scp().len() {
+                let y = free[0].clone();
+                let before = y.valid_values();
+                if revise.revise(&Arc { constraint: c.clone(), variable: y.clone() }, csp.level(), &mut self.monitor) {
+                    let after = y.valid_values();
+                    csp.explain_removals(&c, &y, before.into_iter().filter(|a| !after.contains(a)).collect());
+                    if y.dom().is_empty() {
+                        return None;
+                    }
+                }
+            }
+        }
+        if !dac(csp, order, &mut revise, &mut self.monitor) {
+            return None;
+        }
+        backtrack_free(csp, order, &mut self.monitor)
+    }
+}
+
+fn adjacency<T: OrdT>(csp: &Csp<T>) -> HashMap<String, BTreeSet<String>> {
+    let graph = csp.primal_graph();
+    let mut adjacency: HashMap<String, BTreeSet<String>> = csp.vars().keys()
+        .map(|x| (x.clone(), BTreeSet::new()))
+        .collect();
+    for e in graph.edge_indices() {
+        let (u, v) = graph.edge_endpoints(e).unwrap();
+        adjacency.get_mut(&graph[u]).unwrap().insert(graph[v].clone());
+        adjacency.get_mut(&graph[v]).unwrap().insert(graph[u].clone());
+    }
+    adjacency
+}
+
+fn remove(adjacency: &mut HashMap<String, BTreeSet<String>>, x: &String) {
+    if let Some(neighbours) = adjacency.remove(x) {
+        for y in neighbours {
+            adjacency.get_mut(&y).unwrap().remove(x);
+        }
+    }
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::Expr;
+    use crate::csp::ast::pred::Pred;
+    use std::rc::Rc;
+    use crate::{dom, lt, neq, var};
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::truth::Truth;
+    use crate::csp::variable::extvar::generate_variables;
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::decomposition::cutset::{cycle_cutset, CycleCutset};
+
+    //x_i != x_j for each edge (i, j), k colours
+    fn colouring(n: usize, k: i32, edges: &[(usize, usize)]) -> Csp<i32> {
+        let vmap = generate_variables("x", n, &SetDom::new((1..=k).collect()));
+        let constraints: Vec<Rc<dyn Constraint<i32>>> = edges.iter()
+            .map(|(i, j)| Rc::new(Intensional::from_pred(neq!(
+                var!(vmap[&format!("x{}", i)]), var!(vmap[&format!("x{}", j)])))) as Rc<dyn Constraint<i32>>)
+            .collect();
+        Csp::new(vmap, constraints)
+    }
+
+    #[test]
+    fn cutset_breaks_cycles() {
+        //two triangles sharing x3, and a tail x5 - x6 - x7
+        let csp = colouring(7, 3, &[(1, 2), (2, 3), (1, 3), (3, 4), (4, 5), (3, 5), (5, 6), (6, 7)]);
+        assert_eq!(cycle_cutset(&csp), vec!["x3"]);
+        let tree = colouring(4, 2, &[(1, 2), (2, 3), (2, 4)]);
+        assert!(cycle_cutset(&tree).is_empty());
+    }
+
+    #[test]
+    fn cutset_solve() {
+        let mut csp = colouring(7, 3, &[(1, 2), (2, 3), (1, 3), (3, 4), (4, 5), (3, 5), (5, 6), (6, 7)]);
+        let mut solver = CycleCutset::new(Statistics::default());
+        let solution = solver.solve(&mut csp).unwrap();
+        assert_eq!(csp.is_solution(&solution), Truth::True);
+        //the first value of x3 extends to the forest
+        assert_eq!(solver.monitor().nodes, 1);
+        assert_eq!(csp.level(), 0);
+        assert!(csp.vars().values().all(|x| x.valid_size() == 3));
+    }
+
+    #[test]
+    fn cutset_tree() {
+        //x1 < x2 < x3 on {1, 2, 3}: already a forest, filtered without any assignment
+        let vmap = generate_variables("x", 3, &dom![1, 2, 3]);
+        let mut csp = Csp::new(vmap.clone(), vec![
+            Rc::new(Intensional::from_pred(lt!(var!(vmap["x1"]), var!(vmap["x2"])))),
+            Rc::new(Intensional::from_pred(lt!(var!(vmap["x2"]), var!(vmap["x3"])))),
+        ]);
+        let mut solver = CycleCutset::new(Statistics::default());
+        let solution = solver.solve(&mut csp).unwrap();
+        assert_eq!(csp.is_solution(&solution), Truth::True);
+        assert!(solver.cutset().is_empty());
+        assert_eq!(csp.level(), 0);
+        assert!(csp.vars().values().all(|x| x.valid_size() == 3));
+        assert!(csp.explanations().of("x1").is_empty());
+    }
+
+    #[test]
+    fn cutset_unsat() {
+        //odd wheel: 3 colours are not enough
+        let mut csp = colouring(6, 3, &[(1, 2), (1, 3), (1, 4), (1, 5), (1, 6),
+            (2, 3), (3, 4), (4, 5), (5, 6), (6, 2)]);
+        let mut solver = CycleCutset::new(Statistics::default());
+        assert!(solver.solve(&mut csp).is_none());
+        assert_eq!(solver.cutset().len(), 2);
+        assert_eq!(solver.monitor().nodes, 9);
+        assert!(csp.vars().values().all(|x| x.valid_size() == 3));
+        assert!(csp.explanations().conflict().is_none());
+    }
+}
@@ -0,0 +1,3 @@
+pub mod tree;
+pub mod clustering;
+pub mod cutset;
\ No newline at end of file
@@ -0,0 +1,214 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Tree decomposition of the constraint hypergraph (through Csp::primal_graph)
+    - variables are eliminated one by one (min-fill or min-degree), the bag of x is x and its
+      current neighbours, which are then connected
+    - the bag of x is attached to the bag of its first eliminated neighbour (a forest is joined into a tree)
+    - bags included in a neighbour bag are contracted
+    - treewidth: greatest bag size - 1
+***************************************/
+use std::collections::{BTreeSet, HashMap};
+use petgraph::graph::UnGraph;
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::OrdT;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Elimination {
+    //fewest edges added between the neighbours
+    MinFill,
+    //fewest neighbours
+    MinDegree,
+}
+
+#[derive(Debug, Clone)]
+pub struct TreeDecomposition {
+    pub bags: Vec<BTreeSet<String>>,
+    //edges of the tree (indices in bags)
+    pub edges: Vec<(usize, usize)>,
+    //elimination order of the variables
+    pub order: Vec<String>,
+}
+
+impl TreeDecomposition {
+    pub fn new<T: OrdT>(csp: &Csp<T>, heuristic: Elimination) -> Self {
+        let graph = csp.primal_graph();
+        let mut adjacency: HashMap<String, BTreeSet<String>> = csp.vars().keys()
+            .map(|x| (x.clone(), BTreeSet::new()))
+            .collect();
+        for e in graph.edge_indices() {
+            let (u, v) = graph.edge_endpoints(e).unwrap();
+            adjacency.get_mut(&graph[u]).unwrap().insert(graph[v].clone());
+            adjacency.get_mut(&graph[v]).unwrap().insert(graph[u].clone());
+        }
+
+        //elimination: bag of the i-th eliminated variable
+        let mut order = vec![];
+        let mut bags = vec![];
+        while !adjacency.is_empty() {
+            let x = adjacency.keys()
+                .min_by_key(|x| (score(&adjacency, x, heuristic), (*x).clone()))
+                .unwrap()
+                .clone();
+            let neighbours = adjacency.remove(&x).unwrap();
+            for y in &neighbours {
+                let n = adjacency.get_mut(y).unwrap();
+                n.remove(&x);
+                n.extend(neighbours.iter().filter(|z| *z != y).cloned());
+            }
+            let mut bag = neighbours;
+            bag.insert(x.clone());
+            bags.push(bag);
+            order.push(x);
+        }
+
+        //parent: bag of the first eliminated neighbour, else the next bag
+        let pos: HashMap<&String, usize> = order.iter().enumerate().map(|(i, x)| (x, i)).collect();
+        let mut parent: Vec<Option<usize>> = (0..bags.len()).map(|i| {
+            bags[i].iter()
+                .map(|y| pos[y])
+                .filter(|j| *j > i)
+                .min()
+                .or((i + 1 < bags.len()).then_some(i + 1))
+        }).collect();
+
+        contract(&mut bags, &mut parent);
+        let alive: Vec<usize> = (0..bags.len()).filter(|i| !bags[*i].is_empty()).collect();
+        let index: HashMap<usize, usize> = alive.iter().enumerate().map(|(k, i)| (*i, k)).collect();
+        let edges = alive.iter()
+            .filter_map(|i| parent[*i].map(|p| (index[i], index[&p])))
+            .collect();
+        let bags = alive.into_iter().map(|i| bags[i].clone()).collect();
+        Self { bags, edges, order }
+    }
+
+    pub fn treewidth(&self) -> usize {
+        self.bags.iter().map(|b| b.len()).max().unwrap_or(1).saturating_sub(1)
+    }
+
+    //neighbour bags of bag i in the tree
+    pub fn neighbours(&self, i: usize) -> Vec<usize> {
+        self.edges.iter()
+            .filter_map(|(a, b)| if *a == i { Some(*b) } else if *b == i { Some(*a) } else { None })
+            .collect()
+    }
+
+    //every variable and constraint scope in a bag, bags of a variable connected (running intersection)
+    pub fn is_valid<T: OrdT>(&self, csp: &Csp<T>) -> bool {
+        let covered = csp.constraints().iter().all(|c|
+            self.bags.iter().any(|b| c.scp().iter().all(|x| b.contains(x.label()))));
+        let connected = csp.vars().keys().all(|x| {
+            let holding: Vec<usize> = (0..self.bags.len()).filter(|i| self.bags[*i].contains(x)).collect();
+            let inner = self.edges.iter()
+                .filter(|(a, b)| self.bags[*a].contains(x) && self.bags[*b].contains(x))
+                .count();
+            !holding.is_empty() && inner + 1 == holding.len()
+        });
+        covered && connected && self.edges.len() + 1 == self.bags.len()
+    }
+
+    //tree of bags, nodes labelled by their variables
+    pub fn graph(&self) -> UnGraph<String, String> {
+        let mut g: UnGraph<String, String> = UnGraph::new_undirected();
+        let nodes: Vec<_> = self.bags.iter()
+            .map(|b| g.add_node(b.iter().cloned().collect::<Vec<_>>().join(", ")))
+            .collect();
+        for (a, b) in &self.edges {
+            let separator = self.bags[*a].intersection(&self.bags[*b]).cloned().collect::<Vec<_>>().join(", ");
+            g.add_edge(nodes[*a], nodes[*b], separator);
+        }
+        g
+    }
+}
+
+fn score(adjacency: &HashMap<String, BTreeSet<String>>, x: &String, heuristic: Elimination) -> usize {
+    let neighbours = &adjacency[x];
+    match heuristic {
+        Elimination::MinDegree => neighbours.len(),
+        Elimination::MinFill => neighbours.iter()
+            .map(|y| neighbours.iter().filter(|z| *z > y && !adjacency[y].contains(*z)).count())
+            .sum(),
+    }
+}
+
+//contracts the tree edges whose bags are included one in the other (contracted bags are emptied)
+fn contract(bags: &mut [BTreeSet<String>], parent: &mut [Option<usize>]) {
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for c in 0..bags.len() {
+            let Some(p) = parent[c] else { continue };
+            if bags[c].is_empty() || !(bags[c].is_subset(&bags[p]) || bags[p].is_subset(&bags[c])) {
+                continue;
+            }
+            if bags[p].is_subset(&bags[c]) {
+                bags[p] = bags[c].clone();
+            }
+            bags[c].clear();
+            for q in parent.iter_mut().filter(|q| **q == Some(c)) {
+                *q = Some(p);
+            }
+            parent[c] = None;
+            changed = true;
+        }
+    }
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::Expr;
+    use crate::csp::ast::pred::Pred;
+    use std::rc::Rc;
+    use crate::{dom, neq, var};
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::variable::extvar::generate_variables;
+    use crate::solver::decomposition::tree::{Elimination, TreeDecomposition};
+
+    //x_i != x_j for each edge (i, j)
+    fn graph(n: usize, edges: &[(usize, usize)]) -> Csp<i32> {
+        let vmap = generate_variables("x", n, &dom![1, 2, 3]);
+        let constraints: Vec<Rc<dyn Constraint<i32>>> = edges.iter()
+            .map(|(i, j)| Rc::new(Intensional::from_pred(neq!(
+                var!(vmap[&format!("x{}", i)]), var!(vmap[&format!("x{}", j)])))) as Rc<dyn Constraint<i32>>)
+            .collect();
+        Csp::new(vmap, constraints)
+    }
+
+    #[test]
+    fn treewidth_of_graphs() {
+        let chain = graph(4, &[(1, 2), (2, 3), (3, 4)]);
+        let cycle = graph(4, &[(1, 2), (2, 3), (3, 4), (4, 1)]);
+        let clique = graph(4, &[(1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)]);
+        for heuristic in [Elimination::MinFill, Elimination::MinDegree] {
+            let td = TreeDecomposition::new(&chain, heuristic);
+            assert_eq!(td.treewidth(), 1);
+            assert_eq!(td.bags.len(), 3);
+            assert!(td.is_valid(&chain));
+            let td = TreeDecomposition::new(&cycle, heuristic);
+            assert_eq!(td.treewidth(), 2);
+            assert!(td.is_valid(&cycle));
+            let td = TreeDecomposition::new(&clique, heuristic);
+            assert_eq!(td.treewidth(), 3);
+            assert_eq!(td.bags.len(), 1);
+        }
+    }
+
+    #[test]
+    fn decomposition_of_disconnected_grid() {
+        //2x3 grid and an isolated edge
+        let csp = graph(8, &[(1, 2), (2, 3), (4, 5), (5, 6), (1, 4), (2, 5), (3, 6), (7, 8)]);
+        for heuristic in [Elimination::MinFill, Elimination::MinDegree] {
+            let td = TreeDecomposition::new(&csp, heuristic);
+            assert!(td.is_valid(&csp));
+            assert_eq!(td.treewidth(), 2);
+            assert_eq!(td.order.len(), 8);
+            assert_eq!(td.graph().edge_count(), td.bags.len() - 1);
+        }
+    }
+}
@@ -0,0 +1,263 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Min-conflicts local search over complete assignments
+    - a complete assignment is kept, violations are checked with Constraint::check_assignment
+    - each step moves a variable of a violated constraint to its min-conflict value
+    - random walk: with probability p, a random value instead
+    - tabu: the previous value of a moved variable is forbidden for tabu_tenure steps
+      (unless it leads to a new best assignment)
+    - restarts: new random assignment after max_steps steps without solution
+***************************************/
+
+/**************************************
+            Parameters
+***************************************/
+use std::collections::HashMap;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::OrdT;
+use crate::csp::truth::Truth;
+use crate::csp::variable::vvalue::{make_assignment, vv, VValue};
+use crate::instrumentation::monitor::Monitor;
+
+#[derive(Debug, Clone)]
+pub struct MinConflictsParams {
+    //steps of each run
+    pub max_steps: usize,
+    //runs after the first one
+    pub restarts: usize,
+    //probability of a random move
+    pub random_walk: f64,
+    pub tabu_tenure: usize,
+    pub seed: u64,
+}
+
+impl Default for MinConflictsParams {
+    fn default() -> Self {
+        Self { max_steps: 1000, restarts: 10, random_walk: 0.1, tabu_tenure: 2, seed: 0 }
+    }
+}
+
+//best complete assignment found (sorted by label) and its number of violated constraints
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalOutcome<T: OrdT> {
+    pub assignment: Vec<VValue<T>>,
+    pub violations: usize,
+}
+
+impl<T: OrdT> LocalOutcome<T> {
+    pub fn is_solution(&self) -> bool { self.violations == 0 }
+}
+
+/**************************************
+            MinConflicts
+***************************************/
+
+pub struct MinConflicts<M: Monitor> {
+    params: MinConflictsParams,
+    rng: StdRng,
+    monitor: M,
+}
+
+impl<M: Monitor> MinConflicts<M> {
+    pub fn new(params: MinConflictsParams, monitor: M) -> Self {
+        let rng = StdRng::seed_from_u64(params.seed);
+        Self { params, rng, monitor }
+    }
+
+    pub fn monitor(&self) -> &M {&self.monitor}
+
+    //values are taken from the current domains (csp is not modified)
+    pub fn solve<T: OrdT>(&mut self, csp: &Csp<T>) -> LocalOutcome<T> {
+        let mut labels: Vec<_> = csp.vars().keys().cloned().collect();
+        labels.sort();
+        //constraints involving each variable
+        let mut involving: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, c) in csp.constraints().iter().enumerate() {
+            for x in c.scp() {
+                involving.entry(x.label().clone()).or_default().push(i);
+            }
+        }
+
+        let mut best: Option<LocalOutcome<T>> = None;
+        for _ in 0..=self.params.restarts {
+            let outcome = self.run(csp, &labels, &involving);
+            if best.as_ref().is_none_or(|b| outcome.violations < b.violations) {
+                best = Some(outcome);
+            }
+            if best.as_ref().is_some_and(|b| b.is_solution()) {
+                self.monitor.on_solution();
+                break;
+            }
+        }
+        best.expect("At least one run")
+    }
+
+    //one run from a random assignment
+    fn run<T: OrdT>(&mut self, csp: &Csp<T>, labels: &[String],
+                    involving: &HashMap<String, Vec<usize>>) -> LocalOutcome<T> {
+        let domains: HashMap<&String, Vec<T>> = labels.iter()
+            .map(|l| (l, csp.vars()[l].valid_values()))
+            .collect();
+        let mut values: HashMap<String, T> = labels.iter()
+            .map(|l| (l.clone(), domains[l].choose(&mut self.rng).expect("Domain wipeout").clone()))
+            .collect();
+        //(label, value) -> step until which the value is tabu
+        let mut tabu: HashMap<(String, T), usize> = HashMap::new();
+
+        let mut best = LocalOutcome { assignment: snapshot(labels, &values), violations: usize::MAX };
+        for step in 0..self.params.max_steps {
+            let violated: Vec<usize> = (0..csp.e())
+                .filter(|i| is_violated(csp, *i, &values))
+                .collect();
+            if violated.len() < best.violations {
+                best = LocalOutcome { assignment: snapshot(labels, &values), violations: violated.len() };
+            }
+            if violated.is_empty() {
+                break;
+            }
+
+            //variables of violated constraints
+            let mut conflicting: Vec<&String> = violated.iter()
+                .flat_map(|i| csp.constraints()[*i].scp().iter().map(|x| x.label()))
+                .collect();
+            conflicting.sort();
+            conflicting.dedup();
+            let x = (*conflicting.choose(&mut self.rng).unwrap()).clone();
+            let current = values[&x].clone();
+            let others: Vec<T> = domains[&x].iter().filter(|a| **a != current).cloned().collect();
+            if others.is_empty() {
+                continue;
+            }
+
+            self.monitor.on_node();
+            let next = if self.rng.gen_bool(self.params.random_walk) {
+                others.choose(&mut self.rng).unwrap().clone()
+            } else {
+                let here = conflicts(csp, &involving[&x], &values);
+                let scored: Vec<(T, usize)> = others.into_iter()
+                    .map(|a| {
+                        values.insert(x.clone(), a.clone());
+                        let n = conflicts(csp, &involving[&x], &values);
+                        (a, n)
+                    })
+                    .collect();
+                values.insert(x.clone(), current.clone());
+                //aspiration: a tabu value leading to a new best assignment is allowed
+                let allowed: Vec<&(T, usize)> = scored.iter()
+                    .filter(|(a, n)| tabu.get(&(x.clone(), a.clone())).is_none_or(|until| *until <= step)
+                        || violated.len() + n < best.violations + here)
+                    .collect();
+                match allowed.iter().map(|(_, n)| *n).min() {
+                    Some(min) => {
+                        let ties: Vec<_> = allowed.into_iter().filter(|(_, n)| *n == min).collect();
+                        ties.choose(&mut self.rng).unwrap().0.clone()
+                    }
+                    None => scored.choose(&mut self.rng).unwrap().0.clone(),
+                }
+            };
+            tabu.insert((x.clone(), current), step + 1 + self.params.tabu_tenure);
+            values.insert(x, next);
+        }
+        //the last move (or the initial assignment if there is no step) has not been scored
+        let violations = conflicts(csp, &(0..csp.e()).collect::<Vec<_>>(), &values);
+        if violations < best.violations {
+            best = LocalOutcome { assignment: snapshot(labels, &values), violations };
+        }
+        best
+    }
+}
+
+//assignment sorted by label
+fn snapshot<T: OrdT>(labels: &[String], values: &HashMap<String, T>) -> Vec<VValue<T>> {
+    labels.iter().map(|l| vv(l.clone(), values[l].clone())).collect()
+}
+
+fn is_violated<T: OrdT>(csp: &Csp<T>, i: usize, values: &HashMap<String, T>) -> bool {
+    let c = &csp.constraints()[i];
+    let tuple = c.scp().iter().map(|x| values[x.label()].clone()).collect();
+    c.check_assignment(&make_assignment(c.scp(), tuple)) != Truth::True
+}
+
+//number of violated constraints among the given ones
+fn conflicts<T: OrdT>(csp: &Csp<T>, constraints: &[usize], values: &HashMap<String, T>) -> usize {
+    constraints.iter().filter(|i| is_violated(csp, **i, values)).count()
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::Expr;
+    use crate::csp::ast::pred::Pred;
+    use std::rc::Rc;
+    use crate::{neq, var};
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::truth::Truth;
+    use crate::csp::variable::extvar::generate_variables;
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::local::minconflicts::{MinConflicts, MinConflictsParams};
+
+    //k-colouring of the complete graph K_n
+    fn clique(n: usize, k: i32) -> Csp<i32> {
+        let vmap = generate_variables("x", n, &SetDom::new((1..=k).collect()));
+        let mut constraints: Vec<Rc<dyn Constraint<i32>>> = vec![];
+        for i in 1..=n {
+            for j in (i + 1)..=n {
+                constraints.push(Rc::new(Intensional::from_pred(neq!(
+                    var!(vmap[&format!("x{}", i)]), var!(vmap[&format!("x{}", j)])))));
+            }
+        }
+        Csp::new(vmap, constraints)
+    }
+
+    #[test]
+    fn min_conflicts_solution() {
+        let csp = clique(6, 6);
+        let mut ls = MinConflicts::new(MinConflictsParams::default(), Statistics::default());
+        let outcome = ls.solve(&csp);
+        assert!(outcome.is_solution());
+        assert_eq!(outcome.assignment.len(), 6);
+        assert_eq!(csp.is_solution(&outcome.assignment), Truth::True);
+        assert_eq!(ls.monitor().solutions, 1);
+    }
+
+    #[test]
+    fn min_conflicts_best_assignment() {
+        //K4 with 3 colours: at least one violated constraint
+        let csp = clique(4, 3);
+        let params = MinConflictsParams { max_steps: 200, restarts: 2, ..MinConflictsParams::default() };
+        let outcome = MinConflicts::new(params, Statistics::default()).solve(&csp);
+        assert_eq!(outcome.violations, 1);
+        assert_eq!(csp.is_solution(&outcome.assignment), Truth::False);
+    }
+
+    #[test]
+    fn min_conflicts_seeded() {
+        let csp = clique(5, 5);
+        let params = MinConflictsParams { seed: 42, ..MinConflictsParams::default() };
+        let first = MinConflicts::new(params.clone(), Statistics::default()).solve(&csp);
+        let second = MinConflicts::new(params, Statistics::default()).solve(&csp);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn min_conflicts_no_step() {
+        //the random assignment itself is scored
+        let csp = clique(4, 4);
+        let params = MinConflictsParams { max_steps: 0, restarts: 0, ..MinConflictsParams::default() };
+        let outcome = MinConflicts::new(params, Statistics::default()).solve(&csp);
+        assert_eq!(outcome.assignment.len(), 4);
+        let violated = csp.constraints().iter()
+            .filter(|c| c.check_assignment(&outcome.assignment) != Truth::True)
+            .count();
+        assert_eq!(outcome.violations, violated);
+    }
+}
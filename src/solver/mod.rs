@@ -1 +1,4 @@
-pub mod consistency;
\ No newline at end of file
+pub mod consistency;
+pub mod search;
+pub mod local;
+pub mod decomposition;
\ No newline at end of file
@@ -0,0 +1,189 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Conflict-directed backjumping (CBJ) on top of the MAC engine
+    - a failed subtree returns its conflict set: the levels of the decisions explaining it
+      (from csp.explanations(), recorded by the propagation schemes)
+    - a level absent from the conflict set of its subtree is jumped over
+***************************************/
+
+/**************************************
+            CBJ
+***************************************/
+use std::collections::BTreeSet;
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::OrdT;
+use crate::csp::variable::vvalue::vv;
+use crate::instrumentation::monitor::Monitor;
+use crate::solver::consistency::consistency::Revise;
+use crate::solver::consistency::scheme::Scheme;
+use crate::solver::search::engine::Engine;
+use crate::solver::search::limits::{Limit, SearchOutcome};
+
+impl<M, S, R, T:OrdT> Engine<M, S, R, T>
+where
+    S: Scheme<M, T, R>,
+    R: Revise<M, T>,
+    M: Monitor
+{
+    //first solution found under the limits, the csp is restored at its initial level
+    pub fn solve_cbj(&mut self, csp: &mut Csp<T>) -> SearchOutcome<T> {
+        let root = csp.level();
+        self.begin();
+        let mut solution = None;
+        let result = match self.propagate_root(csp) {
+            Ok(true) => self.cbj(csp),
+            Ok(false) => Ok(Some(BTreeSet::new())),
+            Err(limit) => Err(limit),
+        };
+        let stop = match result {
+            Ok(None) => {
+                self.monitor_mut().on_solution();
+                self.limiter_mut().on_solution();
+                solution = Some(self.solution(csp));
+                None
+            }
+            Ok(Some(_)) => None,
+            Err(limit) => Some(limit),
+        };
+        self.undo_to(csp, root);
+        self.end();
+        SearchOutcome::new(stop, solution)
+    }
+
+    //Ok(Some(conflict set)) if the subtree has no solution, Ok(None) on a solution, Err(limit) if a limit is reached
+    fn cbj(&mut self, csp: &mut Csp<T>) -> Result<Option<BTreeSet<usize>>, Limit> {
+        self.check()?;
+        let x = match self.select(csp) {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let level = csp.level() + 1;
+        //values of x already removed by previous decisions
+        let mut conflict_set = csp.explanations().culprits(x.label());
+
+        for a in self.values(&x) {
+            let result = if self.decide(csp, vv(x.label().clone(), a)) {
+                self.cbj(csp)?
+            } else {
+                self.fail()?;
+                Some(csp.explanations().conflict()
+                    .map(|c| c.decisions.clone())
+                    .unwrap_or_default())
+            };
+            match result {
+                None => return Ok(None),
+                Some(cs) => {
+                    self.undo(csp);
+                    self.monitor_mut().on_backtrack();
+                    if !cs.contains(&level) {
+                        //x = a is not responsible: jump over level
+                        return Ok(Some(cs));
+                    }
+                    conflict_set.extend(cs.into_iter().filter(|l| *l != level));
+                }
+            }
+        }
+        let to = conflict_set.iter().max().cloned().unwrap_or(0);
+        if to + 1 < level - 1 {
+            self.monitor_mut().on_backjump(level - 1, to);
+        }
+        Ok(Some(conflict_set))
+    }
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::Expr;
+    use crate::csp::ast::pred::Pred;
+    use std::collections::{BTreeSet, HashMap};
+    use std::rc::Rc;
+    use crate::{dom, neq, var, var_dom};
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::variable::extvar::ExVar;
+    use crate::csp::variable::vvalue::vv;
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::revise::AC1;
+    use crate::solver::consistency::scheme::ArcOriented;
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::heuristic::{Lexico, MinVal};
+    use crate::solver::search::limits::{Limit, SearchLimits, SearchOutcome};
+
+    //a: decides the values left to x, y, z (pairwise different, unsat on 2 values)
+    //b, c: irrelevant to the failure
+    fn pigeons(holes: Vec<i32>) -> Csp<i32> {
+        let mut vmap = HashMap::new();
+        for l in ["a", "b", "c"] {
+            vmap.insert(String::from(l), var_dom!(String::from(l), dom![1, 2, 3]));
+        }
+        for l in ["x", "y", "z"] {
+            vmap.insert(String::from(l), var_dom!(String::from(l), SetDom::new(holes.clone())));
+        }
+        let mut constraints: Vec<Rc<dyn Constraint<i32>>> = vec![
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["b"]), var!(vmap["c"])))),
+        ];
+        for (l1, l2) in [("a", "x"), ("a", "y"), ("a", "z"), ("x", "y"), ("x", "z"), ("y", "z")] {
+            constraints.push(Rc::new(Intensional::from_pred(neq!(var!(vmap[l1]), var!(vmap[l2])))));
+        }
+        Csp::new(vmap, constraints)
+    }
+
+    fn engine() -> Engine<Statistics, ArcOriented, AC1, i32> {
+        Engine::with_heuristics(Consistency::new(ArcOriented, AC1, Statistics::default()),
+                                Box::new(Lexico), Box::new(MinVal))
+    }
+
+    #[test]
+    fn wipeout_is_explained() {
+        let mut csp = pigeons(vec![1, 2, 3]);
+        let mut engine = engine();
+        assert!(engine.decide(&mut csp, vv("a".into(), 1)));
+        assert!(engine.decide(&mut csp, vv("b".into(), 1)));
+        //x = 2 -> y = z = 3 -> wipeout, because of a (level 1) and x (level 3)
+        assert!(!engine.decide(&mut csp, vv("x".into(), 2)));
+        let conflict = csp.explanations().conflict().expect("Should be explained");
+        assert_eq!(conflict.decisions, BTreeSet::from([1, 3]));
+        //(y, 1) removed by c_ay because of a = 1
+        let e = csp.explanations().why("y", &1).unwrap();
+        assert_eq!(e.constraint, Some(String::from("c_ay")));
+        assert_eq!(e.decisions, BTreeSet::from([1]));
+
+        engine.undo(&mut csp);
+        assert!(csp.explanations().conflict().is_none());
+    }
+
+    #[test]
+    fn cbj_jumps_over_irrelevant_levels() {
+        let mut csp = pigeons(vec![1, 2, 3]);
+        let mut engine_cbj = engine();
+        assert!(engine_cbj.solve_cbj(&mut csp).is_unsat());
+
+        let mut engine_bt = engine();
+        assert!(engine_bt.solve(&mut csp).is_unsat());
+
+        assert!(engine_cbj.monitor().backjumps > 0);
+        assert!(engine_cbj.monitor().nodes < engine_bt.monitor().nodes);
+    }
+
+    #[test]
+    fn cbj_solution() {
+        //one more hole
+        let mut csp = pigeons(vec![1, 2, 3, 4]);
+        let mut engine = engine();
+        let solution = engine.solve_cbj(&mut csp).solution().cloned().expect("Should be satisfiable");
+        assert_eq!(solution.len(), 6);
+        assert_eq!(solution[0], vv("a".into(), 1));
+        assert_eq!(csp.level(), 0);
+
+        engine.set_limits(SearchLimits { nodes: Some(2), ..SearchLimits::default() });
+        assert_eq!(engine.solve_cbj(&mut csp), SearchOutcome::Unknown(Limit::Nodes, None));
+        assert_eq!(csp.level(), 0);
+    }
+}
@@ -0,0 +1,422 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Search engine, backtracking search maintaining a consistency (MAC)
+    - decide: Csp::assign + consistency enforcement on the assigned variable
+    - undo: Csp::unassign (restores domains and explanations of the level)
+    - solve: depth-first search (d-way branching) for a first solution
+    - search: exploration Strategy (see strategy.rs)
+  Every entry point runs under the SearchLimits (see limits.rs) and returns a SearchOutcome:
+  limits are checked between nodes, the timeout and the cancellation token also inside the
+  propagation (the monitor holds the interrupt of the running search, see begin).
+  Exploration strategies (see cbj.rs, learning.rs) are built on top of decide/undo.
+***************************************/
+
+/**************************************
+            Engine
+***************************************/
+use std::rc::Rc;
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::{Domain, OrdT};
+use crate::csp::event::{reduced, TriggerEvent};
+use crate::csp::variable::extvar::ExVar;
+use crate::csp::variable::vvalue::{vv, VValue};
+use crate::instrumentation::monitor::Monitor;
+use crate::solver::consistency::consistency::{Consistency, Revise};
+use crate::solver::consistency::scheme::Scheme;
+use crate::solver::search::heuristic::{MinDom, MinVal, ValHeuristic, VarHeuristic};
+use crate::solver::search::limits::{Limit, Limiter, SearchLimits, SearchOutcome};
+use crate::solver::search::strategy::{Dfs, Strategy};
+
+pub struct Engine<M, S, R, T:OrdT>
+where
+    S: Scheme<M, T, R>,
+    R: Revise<M, T>,
+    M: Monitor,
+{
+    consistency: Consistency<M, S, R, T>,
+    var_heuristic: Box<dyn VarHeuristic<T>>,
+    val_heuristic: Box<dyn ValHeuristic<T>>,
+    limiter: Limiter,
+}
+
+impl<M, S, R, T:OrdT> Engine<M, S, R, T>
+where
+    S: Scheme<M, T, R>,
+    R: Revise<M, T>,
+    M: Monitor
+{
+    //dom heuristic, smallest value first
+    pub fn new(consistency: Consistency<M, S, R, T>) -> Self {
+        Self::with_heuristics(consistency, Box::new(MinDom), Box::new(MinVal))
+    }
+
+    pub fn with_heuristics(consistency: Consistency<M, S, R, T>,
+                           var_heuristic: Box<dyn VarHeuristic<T>>,
+                           val_heuristic: Box<dyn ValHeuristic<T>>) -> Self {
+        Self { consistency, var_heuristic, val_heuristic, limiter: Limiter::default() }
+    }
+
+    //limits of the next searches (default: first solution)
+    pub fn set_limits(&mut self, limits: SearchLimits) {
+        self.limiter = Limiter::new(limits);
+    }
+
+    pub fn limiter(&self) -> &Limiter {&self.limiter}
+    pub fn limiter_mut(&mut self) -> &mut Limiter {&mut self.limiter}
+
+    pub fn monitor(&self) -> &M {self.consistency.monitor()}
+    pub fn monitor_mut(&mut self) -> &mut M {self.consistency.monitor_mut()}
+    //preprocessing passes (e.g. SAC) run on the maintained consistency
+    pub fn consistency_mut(&mut self) -> &mut Consistency<M, S, R, T> {&mut self.consistency}
+
+    //--- --- ---   Primitives  --- --- ---
+
+    //initial propagation (all variables are reduced)
+    pub fn propagate(&mut self, csp: &mut Csp<T>) -> bool {
+        csp.take_events();
+        let events = reduced(csp.vars().keys());
+        self.consistency.enforce_consistency(csp, events)
+    }
+
+    //enforce consistency from the given events (current level), false on domain wipeout
+    pub fn enforce(&mut self, csp: &mut Csp<T>, events: Vec<TriggerEvent>) -> bool {
+        self.consistency.enforce_consistency(csp, events)
+    }
+
+    //new level: x = a then enforce consistency, false on domain wipeout
+    pub fn decide(&mut self, csp: &mut Csp<T>, vvalue: VValue<T>) -> bool {
+        self.monitor_mut().on_node();
+        self.limiter.on_node();
+        let x = vvalue.label.clone();
+        csp.assign(vvalue);
+        let events = csp.decision_events(&x);
+        self.consistency.enforce_consistency(csp, events)
+    }
+
+    //removes values of x at the current level (explained by the decisions of the branch)
+    //then enforce consistency, false on domain wipeout
+    pub fn restrict(&mut self, csp: &mut Csp<T>, name: &str, x: &ExVar<T>, removed: Vec<T>) -> bool {
+        for a in &removed {
+            self.monitor_mut().on_value_deleted();
+            x.dom_mut().remove_value(a, csp.level());
+        }
+        csp.explain_bound(name, x, removed);
+        if x.dom().is_empty() {
+            self.monitor_mut().on_domain_wipeout();
+            return false;
+        }
+        let events = x.take_events();
+        self.enforce(csp, events)
+    }
+
+    //back to the previous level
+    pub fn undo(&mut self, csp: &mut Csp<T>) {
+        let lvl = csp.level();
+        csp.unassign();
+        self.consistency.notify(&TriggerEvent::Backtrack(lvl));
+    }
+
+    //back to level lvl
+    pub fn undo_to(&mut self, csp: &mut Csp<T>, lvl: usize) {
+        while csp.level() > lvl {
+            self.undo(csp);
+        }
+    }
+
+    pub fn select(&self, csp: &Csp<T>) -> Option<Rc<ExVar<T>>> {
+        self.var_heuristic.select(csp)
+    }
+
+    pub fn values(&self, x: &ExVar<T>) -> Vec<T> {
+        self.val_heuristic.order(x)
+    }
+
+    //current instantiation, sorted by label (expects singleton domains)
+    pub fn solution(&self, csp: &Csp<T>) -> Vec<VValue<T>> {
+        let mut labels: Vec<_> = csp.vars().keys().cloned().collect();
+        labels.sort();
+        labels.into_iter()
+            .map(|l| {
+                let value = csp.vars()[&l].value().expect("Variable not instantiated");
+                vv(l, value)
+            })
+            .collect()
+    }
+
+    //--- --- ---   Depth-first search  --- --- ---
+
+    //first solution found, the csp is restored at its initial level
+    pub fn solve(&mut self, csp: &mut Csp<T>) -> SearchOutcome<T> {
+        let root = csp.level();
+        self.begin();
+        let mut solution = None;
+        let found = self.propagate_root(csp).and_then(|consistent| if consistent { self.dfs(csp) } else { Ok(false) });
+        let stop = match found {
+            Ok(true) => {
+                self.monitor_mut().on_solution();
+                self.limiter.on_solution();
+                solution = Some(self.solution(csp));
+                None
+            }
+            Ok(false) => None,
+            Err(limit) => Some(limit),
+        };
+        self.undo_to(csp, root);
+        self.end();
+        SearchOutcome::new(stop, solution)
+    }
+
+    fn dfs(&mut self, csp: &mut Csp<T>) -> Result<bool, Limit> {
+        self.check()?;
+        let x = match self.select(csp) {
+            Some(x) => x,
+            None => return Ok(true),
+        };
+        for a in self.values(&x) {
+            let found = if self.decide(csp, vv(x.label().clone(), a)) {
+                self.dfs(csp)?
+            } else {
+                self.fail()?;
+                false
+            };
+            if found {
+                return Ok(true);
+            }
+            self.undo(csp);
+            self.monitor_mut().on_backtrack();
+        }
+        Ok(false)
+    }
+
+    //--- --- ---   Limited search  --- --- ---
+
+    //Err(limit) if a limit is reached
+    pub fn check(&self) -> Result<(), Limit> {
+        self.limiter.reached().map_or(Ok(()), Err)
+    }
+
+    //failed decision (counted by the limiter), Err(limit) if a limit is now reached
+    //(an interrupted propagation fails too: the limit is reported instead of the failure)
+    pub fn fail(&mut self) -> Result<(), Limit> {
+        self.limiter.on_failure();
+        self.check()
+    }
+
+    //new search: counters and clock reset, interrupt handed to the monitor
+    pub fn begin(&mut self) {
+        self.limiter.start();
+        let interrupt = self.limiter.interrupt();
+        self.monitor_mut().set_interrupt(Some(interrupt));
+    }
+
+    //search over: the propagation is no longer interrupted
+    pub fn end(&mut self) {
+        self.monitor_mut().set_interrupt(None);
+    }
+
+    //initial propagation of a search: Ok(false) on wipeout, Err(limit) if interrupted
+    pub fn propagate_root(&mut self, csp: &mut Csp<T>) -> Result<bool, Limit> {
+        if self.propagate(csp) {
+            Ok(true)
+        } else {
+            self.check().map(|_| false)
+        }
+    }
+
+    //undo the current level after exploring it
+    pub fn backtrack(&mut self, csp: &mut Csp<T>) {
+        self.undo(csp);
+        self.monitor_mut().on_backtrack();
+    }
+
+    //all variables are assigned: keep the solution, Err(limit) if a limit is now reached
+    pub fn record(&mut self, csp: &Csp<T>, best: &mut Option<Vec<VValue<T>>>) -> Result<(), Limit> {
+        self.monitor_mut().on_solution();
+        self.limiter.on_solution();
+        *best = Some(self.solution(csp));
+        self.check()
+    }
+
+    //depth-first search stopped by the limits
+    pub fn search(&mut self, csp: &mut Csp<T>) -> SearchOutcome<T> {
+        self.search_with(csp, &mut Dfs)
+    }
+
+    //the csp is restored at its initial level
+    //Sat carries the last solution found (Limit::Solutions only stops the enumeration)
+    pub fn search_with<St>(&mut self, csp: &mut Csp<T>, strategy: &mut St) -> SearchOutcome<T>
+    where
+        St: Strategy<M, S, R, T> + ?Sized
+    {
+        let root = csp.level();
+        self.begin();
+        let mut best = None;
+        let stop = match self.propagate_root(csp) {
+            Ok(true) => strategy.explore(self, csp, &mut best).err(),
+            Ok(false) => None,
+            Err(limit) => Some(limit),
+        };
+        self.undo_to(csp, root);
+        self.end();
+        SearchOutcome::new(stop, best)
+    }
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::Expr;
+    use crate::csp::ast::pred::Pred;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use crate::{dom, eq, lt, neq, var, var_dom};
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::domain::Domain;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::truth::Truth;
+    use crate::csp::variable::extvar::{generate_variables, ExVar};
+    use crate::csp::variable::vvalue::{make_assignment, vv, VValue};
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::revise::{AC1, AC2001, AC3rm};
+    use crate::solver::consistency::scheme::{ArcOriented, VariableOriented};
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::limits::{Limit, SearchLimits, SearchOutcome};
+    use crate::instrumentation::monitor::Monitor;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn satisfies(csp: &Csp<i32>, solution: &[VValue<i32>]) -> bool {
+        csp.constraints().iter().all(|c| {
+            let values = c.scp().iter()
+                .map(|x| solution.iter().find(|vv| vv.label == *x.label()).unwrap().value)
+                .collect();
+            c.check_assignment(&make_assignment(c.scp(), values)) == Truth::True
+        })
+    }
+
+    fn setup_csp() -> Csp<i32> {
+        let x = var_dom!("x".into(), dom![1, 2, 3]);
+        let y = var_dom!("y".into(), dom![1, 2, 3]);
+        let z = var_dom!("z".into(), dom![1, 2, 3]);
+        let mut vmap = HashMap::new();
+        vmap.insert("x".into(), x.clone());
+        vmap.insert("y".into(), y.clone());
+        vmap.insert("z".into(), z.clone());
+
+        let c1 = Intensional::from_pred(lt!(var!(x), var!(y)));
+        let c2 = Intensional::from_pred(neq!(var!(y), var!(z)));
+        let c3 = Intensional::from_pred(eq!(var!(x), var!(z)));
+        Csp::new(vmap, vec![Rc::new(c1), Rc::new(c2), Rc::new(c3)])
+    }
+
+    #[test]
+    fn decide_and_undo() {
+        let mut csp = setup_csp();
+        let mut engine = Engine::new(Consistency::new(ArcOriented, AC1, Statistics::default()));
+        assert!(engine.decide(&mut csp, vv("x".into(), 2)));
+        assert_eq!(csp.level(), 1);
+        assert_eq!(csp.vars()["y"].valid_values(), vec![3]);
+        assert_eq!(csp.vars()["z"].valid_values(), vec![2]);
+
+        engine.undo(&mut csp);
+        assert_eq!(csp.level(), 0);
+        assert!(csp.vars().values().all(|v| v.dom().size() == 3));
+        assert!(csp.explanations().of("y").is_empty());
+    }
+
+    #[test]
+    fn solve_arc_oriented() {
+        let mut csp = setup_csp();
+        let mut engine = Engine::new(Consistency::new(ArcOriented, AC1, Statistics::default()));
+        let solution = engine.solve(&mut csp).solution().cloned().expect("Should be satisfiable");
+        assert!(satisfies(&csp, &solution));
+        assert_eq!(engine.monitor().solutions, 1);
+        //restored
+        assert_eq!(csp.level(), 0);
+    }
+
+    #[test]
+    fn solve_var_oriented_unsat() {
+        //3-colouring of K4
+        let vmap = generate_variables("x", 4, &dom![1, 2, 3]);
+        let mut constraints: Vec<Rc<dyn Constraint<i32>>> = vec![];
+        for i in 1..5 {
+            for j in (i + 1)..5 {
+                constraints.push(Rc::new(Intensional::from_pred(neq!(
+                    var!(vmap[&format!("x{}", i)]), var!(vmap[&format!("x{}", j)])))));
+            }
+        }
+        let mut csp = Csp::new(vmap, constraints);
+        let mut engine = Engine::new(Consistency::new(VariableOriented, AC1, Statistics::default()));
+        assert!(engine.solve(&mut csp).is_unsat());
+        assert!(engine.monitor().backtracks > 0);
+
+        assert!(engine.search(&mut csp).is_unsat());
+        engine.set_limits(SearchLimits { nodes: Some(1), ..SearchLimits::default() });
+        assert_eq!(engine.search(&mut csp), SearchOutcome::Unknown(Limit::Nodes, None));
+        assert_eq!(csp.level(), 0);
+    }
+
+    #[test]
+    fn search_limits() {
+        //x < y, y != z, x = z: (1,2,1), (1,3,1), (2,3,2)
+        let mut csp = setup_csp();
+        let mut engine = Engine::new(Consistency::new(ArcOriented, AC1, Statistics::default()));
+        let outcome = engine.search(&mut csp);
+        assert!(satisfies(&csp, outcome.solution().unwrap()));
+        assert_eq!(engine.limiter().solutions, 1);
+
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut csp).is_sat());
+        assert_eq!(engine.limiter().solutions, 3);
+
+        engine.set_limits(SearchLimits { solutions: None, failures: Some(0), ..SearchLimits::none() });
+        match engine.search(&mut csp) {
+            SearchOutcome::Unknown(Limit::Failures, _) => {}
+            outcome => panic!("Unexpected outcome {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn search_all_revise() {
+        //same solutions with the supports kept across backtracks (AC2001 restored, AC3rm residues)
+        let mut csp = setup_csp();
+        let mut engine = Engine::new(Consistency::new(ArcOriented, AC2001::new(), Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut csp).is_sat());
+        assert_eq!(engine.limiter().solutions, 3);
+        assert_eq!(csp.level(), 0);
+
+        let mut engine = Engine::new(Consistency::new(VariableOriented, AC3rm::new(), Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+        assert!(engine.search(&mut csp).is_sat());
+        assert_eq!(engine.limiter().solutions, 3);
+    }
+
+    #[test]
+    fn limits_in_propagation() {
+        //raised token: the root propagation is interrupted before its first revision
+        let mut csp = setup_csp();
+        let mut engine = Engine::new(Consistency::new(ArcOriented, AC1, Statistics::default()));
+        let stop = Arc::new(AtomicBool::new(true));
+        engine.set_limits(SearchLimits { stop: Some(stop.clone()), ..SearchLimits::default() });
+        assert_eq!(engine.solve(&mut csp), SearchOutcome::Unknown(Limit::Cancelled, None));
+        assert_eq!(engine.monitor().revise_calls, 0);
+        assert_eq!(engine.search(&mut csp), SearchOutcome::Unknown(Limit::Cancelled, None));
+        assert_eq!(csp.level(), 0);
+        assert!(csp.vars().values().all(|x| x.dom().size() == 3));
+
+        //the interrupt is removed at the end of the search
+        stop.store(false, Ordering::Relaxed);
+        assert!(engine.solve(&mut csp).is_sat());
+        assert!(!engine.monitor().interrupted());
+        engine.set_limits(SearchLimits { nodes: Some(1), ..SearchLimits::default() });
+        assert_eq!(engine.solve(&mut csp), SearchOutcome::Unknown(Limit::Nodes, None));
+    }
+}
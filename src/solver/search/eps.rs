@@ -0,0 +1,302 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Embarrassingly Parallel Search (EPS)
+    - decompose: depth-bounded search enumerating the consistent decision prefixes
+      (Csp::assign + enforcement through Engine::decide)
+    - the prefixes are independent subproblems, solved by a pool of threads,
+      each one with its own copy of the model and its own engine
+    - results are merged: solution count, solutions, best objective value
+***************************************/
+
+/**************************************
+            Subproblems
+***************************************/
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::OrdT;
+use crate::csp::variable::vvalue::{vv, VValue};
+use crate::instrumentation::monitor::Monitor;
+use crate::solver::consistency::consistency::Revise;
+use crate::solver::consistency::scheme::Scheme;
+use crate::solver::search::engine::Engine;
+use crate::solver::search::limits::{Limit, SearchLimits, SearchOutcome};
+use crate::solver::search::objective::{BranchAndBound, Objective, SharedBound};
+use crate::solver::search::strategy::Strategy;
+
+//depth-first search keeping every solution
+struct Enumerate<T: OrdT> {
+    keep: bool,
+    solutions: Vec<Vec<VValue<T>>>,
+}
+
+impl<M, S, R, T:OrdT> Strategy<M, S, R, T> for Enumerate<T>
+where
+    S: Scheme<M, T, R>,
+    R: Revise<M, T>,
+    M: Monitor
+{
+    fn explore(&mut self, engine: &mut Engine<M, S, R, T>, csp: &mut Csp<T>,
+               best: &mut Option<Vec<VValue<T>>>) -> Result<(), Limit> {
+        engine.check()?;
+        let x = match engine.select(csp) {
+            Some(x) => x,
+            None => {
+                let result = engine.record(csp, best);
+                if self.keep {
+                    self.solutions.push(best.clone().unwrap());
+                }
+                return result;
+            }
+        };
+        for a in engine.values(&x) {
+            let result = if engine.decide(csp, vv(x.label().clone(), a)) {
+                self.explore(engine, csp, best)
+            } else {
+                engine.fail()
+            };
+            engine.backtrack(csp);
+            result?;
+        }
+        Ok(())
+    }
+}
+
+/**************************************
+            EPS
+***************************************/
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpsResult<T: OrdT> {
+    pub subproblems: usize,
+    pub count: usize,
+    //every solution (only if kept, see Eps::keep_solutions)
+    pub solutions: Vec<Vec<VValue<T>>>,
+    //best solution when optimising, last solution found otherwise
+    pub best: Option<Vec<VValue<T>>>,
+    //false if a limit stopped a subproblem
+    pub complete: bool,
+}
+
+pub struct Eps {
+    //number of decisions of the prefixes
+    pub depth: usize,
+    pub threads: usize,
+    pub keep_solutions: bool,
+    //limits of each subproblem search
+    pub limits: SearchLimits,
+}
+
+impl Eps {
+    pub fn new(depth: usize, threads: usize) -> Self {
+        Self { depth, threads, keep_solutions: false, limits: SearchLimits::none() }
+    }
+
+    //consistent decision prefixes of length depth (or shorter, when they already are solutions)
+    //the csp is restored at its initial level
+    pub fn decompose<M, S, R, T:OrdT>(&self, engine: &mut Engine<M, S, R, T>, csp: &mut Csp<T>) -> Vec<Vec<VValue<T>>>
+    where
+        S: Scheme<M, T, R>,
+        R: Revise<M, T>,
+        M: Monitor
+    {
+        let root = csp.level();
+        let mut prefixes = vec![];
+        if engine.propagate(csp) {
+            self.split(engine, csp, &mut vec![], &mut prefixes);
+        }
+        engine.undo_to(csp, root);
+        prefixes
+    }
+
+    fn split<M, S, R, T:OrdT>(&self, engine: &mut Engine<M, S, R, T>, csp: &mut Csp<T>,
+                              prefix: &mut Vec<VValue<T>>, prefixes: &mut Vec<Vec<VValue<T>>>)
+    where
+        S: Scheme<M, T, R>,
+        R: Revise<M, T>,
+        M: Monitor
+    {
+        let x = match engine.select(csp) {
+            Some(x) if prefix.len() < self.depth => x,
+            _ => {
+                prefixes.push(prefix.clone());
+                return;
+            }
+        };
+        for a in engine.values(&x) {
+            let vvalue = vv(x.label().clone(), a);
+            if engine.decide(csp, vvalue.clone()) {
+                prefix.push(vvalue);
+                self.split(engine, csp, prefix, prefixes);
+                prefix.pop();
+            }
+            engine.undo(csp);
+        }
+    }
+
+    //counts (and keeps, if asked) every solution
+    pub fn solve_all<M, S, R, T, B, E>(&self, build: B, make: E) -> EpsResult<T>
+    where
+        S: Scheme<M, T, R>,
+        R: Revise<M, T>,
+        M: Monitor,
+        T: OrdT + Send + Sync,
+        B: Fn() -> Csp<T> + Sync,
+        E: Fn() -> Engine<M, S, R, T> + Sync
+    {
+        self.run(build, make, None)
+    }
+
+    //best solution, subproblems share their bound
+    pub fn optimise<M, S, R, T, B, E>(&self, build: B, make: E, objective: Objective) -> EpsResult<T>
+    where
+        S: Scheme<M, T, R>,
+        R: Revise<M, T>,
+        M: Monitor,
+        T: OrdT + Send + Sync,
+        B: Fn() -> Csp<T> + Sync,
+        E: Fn() -> Engine<M, S, R, T> + Sync
+    {
+        self.run(build, make, Some((objective, Arc::new(Mutex::new(None)))))
+    }
+
+    fn run<M, S, R, T, B, E>(&self, build: B, make: E, objective: Option<(Objective, SharedBound<T>)>) -> EpsResult<T>
+    where
+        S: Scheme<M, T, R>,
+        R: Revise<M, T>,
+        M: Monitor,
+        T: OrdT + Send + Sync,
+        B: Fn() -> Csp<T> + Sync,
+        E: Fn() -> Engine<M, S, R, T> + Sync
+    {
+        //subproblems are computed on a copy of the model, then shared between threads as decisions
+        let prefixes = self.decompose(&mut make(), &mut build());
+        let next = AtomicUsize::new(0);
+        let result = Mutex::new(EpsResult {
+            subproblems: prefixes.len(), count: 0, solutions: vec![], best: None, complete: true
+        });
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads.max(1) {
+                scope.spawn(|| {
+                    let mut csp = build();
+                    let mut engine = make();
+                    engine.set_limits(self.limits.clone());
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= prefixes.len() {
+                            break;
+                        }
+                        let root = csp.level();
+                        let consistent = prefixes[i].iter().all(|vvalue| engine.decide(&mut csp, vvalue.clone()));
+                        let (outcome, solutions) = match (&objective, consistent) {
+                            (_, false) => (SearchOutcome::Unsat, vec![]),
+                            (None, true) => {
+                                let mut all = Enumerate { keep: self.keep_solutions, solutions: vec![] };
+                                (engine.search_with(&mut csp, &mut all), all.solutions)
+                            }
+                            (Some((o, shared)), true) => {
+                                let mut bnb = BranchAndBound::with_shared(o.clone(), shared.clone());
+                                (engine.search_with(&mut csp, &mut bnb), vec![])
+                            }
+                        };
+                        let count = if consistent { engine.limiter().solutions } else { 0 };
+                        engine.undo_to(&mut csp, root);
+
+                        let mut result = result.lock().expect("EPS poisoned");
+                        result.count += count;
+                        result.solutions.extend(solutions);
+                        result.complete &= !matches!(outcome, SearchOutcome::Unknown(..));
+                        if let Some(s) = outcome.solution() {
+                            let better = match (&objective, &result.best) {
+                                (Some((o, _)), Some(b)) => o.improves(&o.value(s), &o.value(b)),
+                                _ => true,
+                            };
+                            if better {
+                                result.best = Some(s.clone());
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        result.into_inner().expect("EPS poisoned")
+    }
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::Expr;
+    use crate::csp::ast::pred::Pred;
+    use std::collections::HashSet;
+    use std::rc::Rc;
+    use crate::{dom, lt, neq, var};
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::truth::Truth;
+    use crate::csp::variable::extvar::generate_variables;
+    use crate::instrumentation::monitor::NoMonitor;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::revise::AC1;
+    use crate::solver::consistency::scheme::ArcOriented;
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::eps::Eps;
+    use crate::solver::search::objective::Objective;
+
+    //x1 != x2, x2 != x3, x3 < x4 on {1, 2, 3}
+    fn model() -> Csp<i32> {
+        let vmap = generate_variables("x", 4, &dom![1, 2, 3]);
+        let constraints: Vec<Rc<dyn Constraint<i32>>> = vec![
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x1"]), var!(vmap["x2"])))),
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x2"]), var!(vmap["x3"])))),
+            Rc::new(Intensional::from_pred(lt!(var!(vmap["x3"]), var!(vmap["x4"])))),
+        ];
+        Csp::new(vmap, constraints)
+    }
+
+    fn engine() -> Engine<NoMonitor, ArcOriented, AC1, i32> {
+        Engine::new(Consistency::new(ArcOriented, AC1, NoMonitor))
+    }
+
+    #[test]
+    fn eps_decompose() {
+        let eps = Eps::new(2, 1);
+        let mut csp = model();
+        let prefixes = eps.decompose(&mut engine(), &mut csp);
+        assert!(prefixes.iter().all(|p| p.len() == 2));
+        assert_eq!(prefixes.len(), prefixes.iter().collect::<HashSet<_>>().len());
+        assert_eq!(csp.level(), 0);
+    }
+
+    #[test]
+    fn eps_count() {
+        //x3 < x4: (1,2), (1,3), (2,3); x2 != x3: 2 values; x1 != x2: 2 values
+        let mut eps = Eps::new(2, 3);
+        eps.keep_solutions = true;
+        let result = eps.solve_all(model, engine);
+        assert_eq!(result.count, 12);
+        assert_eq!(result.solutions.len(), 12);
+        assert!(result.complete);
+        assert!(result.subproblems > 1);
+        let csp = model();
+        assert!(result.solutions.iter().all(|s| csp.is_solution(s) == Truth::True));
+
+        //same count without decomposition
+        assert_eq!(Eps::new(0, 1).solve_all(model, engine).count, 12);
+    }
+
+    #[test]
+    fn eps_optimise() {
+        let objective = Objective::Maximize("x3".into());
+        let result = Eps::new(2, 2).optimise(model, engine, objective.clone());
+        assert_eq!(objective.value(result.best.as_ref().unwrap()), 2);
+        assert!(result.complete);
+    }
+}
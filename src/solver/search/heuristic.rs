@@ -0,0 +1,180 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Search heuristics
+    - Variable ordering: Lexico | MinDom (dom) | DomDeg (dom/deg)
+    - Value ordering: MinVal | MaxVal | RandomVal (seeded)
+  Only future variables (not in past(P)) are candidates.
+***************************************/
+
+/**************************************
+            Variable ordering
+***************************************/
+use std::cell::RefCell;
+use std::rc::Rc;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::{Domain, OrdT};
+use crate::csp::variable::extvar::ExVar;
+
+pub trait VarHeuristic<T: OrdT> {
+    //next variable to instantiate, None if all variables are in past(P)
+    fn select(&self, csp: &Csp<T>) -> Option<Rc<ExVar<T>>>;
+}
+
+//future variables sorted by label
+fn future<T: OrdT>(csp: &Csp<T>) -> Vec<Rc<ExVar<T>>> {
+    let mut vars: Vec<_> = csp.vars().values()
+        .filter(|v| !csp.past().contains(v.label()))
+        .cloned()
+        .collect();
+    vars.sort_by(|a, b| a.label().cmp(b.label()));
+    vars
+}
+
+//number of constraints involving x and at least another future variable
+fn future_degree<T: OrdT>(csp: &Csp<T>, x: &ExVar<T>) -> usize {
+    csp.constraints().iter()
+        .filter(|c| c.scp().iter().any(|y| y.label() == x.label()))
+        .filter(|c| c.scp().iter().any(|y| y.label() != x.label() && !csp.past().contains(y.label())))
+        .count()
+}
+
+pub struct Lexico;
+
+impl<T: OrdT> VarHeuristic<T> for Lexico {
+    fn select(&self, csp: &Csp<T>) -> Option<Rc<ExVar<T>>> {
+        future(csp).into_iter().next()
+    }
+}
+
+pub struct MinDom;
+
+impl<T: OrdT> VarHeuristic<T> for MinDom {
+    fn select(&self, csp: &Csp<T>) -> Option<Rc<ExVar<T>>> {
+        //min_by_key keeps the first minimum (lexicographic tie-break)
+        future(csp).into_iter().min_by_key(|v| v.dom().size())
+    }
+}
+
+pub struct DomDeg;
+
+impl<T: OrdT> VarHeuristic<T> for DomDeg {
+    fn select(&self, csp: &Csp<T>) -> Option<Rc<ExVar<T>>> {
+        let mut best: Option<(Rc<ExVar<T>>, f64)> = None;
+        for v in future(csp) {
+            let ratio = v.dom().size() as f64 / (future_degree(csp, &v) + 1) as f64;
+            if best.as_ref().is_none_or(|(_, r)| ratio < *r) {
+                best = Some((v, ratio));
+            }
+        }
+        best.map(|(v, _)| v)
+    }
+}
+
+/**************************************
+            Value ordering
+***************************************/
+
+pub trait ValHeuristic<T: OrdT> {
+    //values of dom(x) in the order they are tried
+    fn order(&self, x: &ExVar<T>) -> Vec<T>;
+}
+
+pub struct MinVal;
+
+impl<T: OrdT> ValHeuristic<T> for MinVal {
+    fn order(&self, x: &ExVar<T>) -> Vec<T> {
+        let mut values = x.valid_values();
+        values.sort();
+        values
+    }
+}
+
+pub struct MaxVal;
+
+impl<T: OrdT> ValHeuristic<T> for MaxVal {
+    fn order(&self, x: &ExVar<T>) -> Vec<T> {
+        let mut values = x.valid_values();
+        values.sort_by(|a, b| b.cmp(a));
+        values
+    }
+}
+
+pub struct RandomVal {
+    rng: RefCell<StdRng>,
+}
+
+impl RandomVal {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: RefCell::new(StdRng::seed_from_u64(seed)) }
+    }
+}
+
+impl<T: OrdT> ValHeuristic<T> for RandomVal {
+    fn order(&self, x: &ExVar<T>) -> Vec<T> {
+        let mut values = x.valid_values();
+        values.sort();
+        values.shuffle(&mut *self.rng.borrow_mut());
+        values
+    }
+}
+
+/**************************************
+            Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::Expr;
+    use crate::csp::ast::pred::Pred;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use crate::{dom, neq, var, var_dom};
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::variable::extvar::ExVar;
+    use crate::csp::variable::vvalue::vv;
+    use crate::solver::search::heuristic::{DomDeg, Lexico, MaxVal, MinDom, MinVal, RandomVal, ValHeuristic, VarHeuristic};
+
+    fn setup_csp() -> Csp<i32> {
+        let x = var_dom!("x".into(), dom![1, 2, 3]);
+        let y = var_dom!("y".into(), dom![1, 2]);
+        let z = var_dom!("z".into(), dom![1, 2]);
+        let mut vmap = HashMap::new();
+        vmap.insert("x".into(), x.clone());
+        vmap.insert("y".into(), y.clone());
+        vmap.insert("z".into(), z.clone());
+
+        let c1 = Intensional::from_pred(neq!(var!(x), var!(z)));
+        let c2 = Intensional::from_pred(neq!(var!(y), var!(z)));
+        Csp::new(vmap, vec![Rc::new(c1), Rc::new(c2)])
+    }
+
+    #[test]
+    fn var_heuristics() {
+        let mut csp = setup_csp();
+        assert_eq!(Lexico.select(&csp).unwrap().label(), "x");
+        assert_eq!(MinDom.select(&csp).unwrap().label(), "y");
+        //z has two neighbours
+        assert_eq!(DomDeg.select(&csp).unwrap().label(), "z");
+
+        csp.assign(vv("z".into(), 1));
+        assert_eq!(MinDom.select(&csp).unwrap().label(), "y");
+        assert_eq!(Lexico.select(&csp).unwrap().label(), "x");
+    }
+
+    #[test]
+    fn val_heuristics() {
+        let csp = setup_csp();
+        let x = csp.vars().get("x").unwrap();
+        assert_eq!(MinVal.order(x), vec![1, 2, 3]);
+        assert_eq!(MaxVal.order(x), vec![3, 2, 1]);
+        let mut random = RandomVal::new(7).order(x);
+        assert_eq!(random, RandomVal::new(7).order(x));
+        random.sort();
+        assert_eq!(random, vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,268 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Nogood learning on top of the MAC engine (lazy clause generation style)
+    - removals are explained by literals [x = v] / [x != v] (see csp::explanation)
+    - analyse: a wipeout is resolved to its first unique implication point (1UIP)
+    - the learned nogood is added to the csp as a new constraint,
+      search backjumps to its assertion level and asserts the negation of the UIP
+***************************************/
+
+/**************************************
+            Learning
+***************************************/
+use std::collections::HashSet;
+use std::rc::Rc;
+use crate::csp::constraint::constraint::Constraint;
+use crate::csp::constraint::nogood::Nogood;
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::{Domain, OrdT};
+use crate::csp::explanation::Literal;
+use crate::csp::variable::vvalue::vv;
+use crate::instrumentation::monitor::Monitor;
+use crate::solver::consistency::consistency::Revise;
+use crate::solver::consistency::scheme::Scheme;
+use crate::solver::search::engine::Engine;
+use crate::solver::search::limits::{Limit, SearchOutcome};
+
+pub struct Learned<T: OrdT> {
+    //conjunction of literals leading to the conflict
+    pub literals: Vec<Literal<T>>,
+    //the only literal of the conflict level
+    pub uip: Literal<T>,
+    //highest level of the other literals (0 if none)
+    pub assertion_level: usize,
+}
+
+impl<M, S, R, T:OrdT + 'static> Engine<M, S, R, T>
+where
+    S: Scheme<M, T, R>,
+    R: Revise<M, T>,
+    M: Monitor
+{
+    //first solution found under the limits, the csp is restored at its initial level
+    //(learned nogoods are kept as constraints of the csp)
+    pub fn solve_learning(&mut self, csp: &mut Csp<T>) -> SearchOutcome<T> {
+        let root = csp.level();
+        self.begin();
+        let mut solution = None;
+        let found = self.propagate_root(csp)
+            .and_then(|consistent| if consistent { self.learn(csp, root) } else { Ok(false) });
+        let stop = match found {
+            Ok(true) => {
+                self.monitor_mut().on_solution();
+                self.limiter_mut().on_solution();
+                solution = Some(self.solution(csp));
+                None
+            }
+            Ok(false) => None,
+            Err(limit) => Some(limit),
+        };
+        self.undo_to(csp, root);
+        self.end();
+        SearchOutcome::new(stop, solution)
+    }
+
+    //Ok(false) if the csp has no solution, Err(limit) if a limit is reached
+    fn learn(&mut self, csp: &mut Csp<T>, root: usize) -> Result<bool, Limit> {
+        while let Some(x) = self.select(csp) {
+            self.check()?;
+            let a = self.values(&x).into_iter().next().expect("Domain wipeout");
+            let mut consistent = self.decide(csp, vv(x.label().clone(), a));
+            while !consistent {
+                //an interrupted propagation has no conflict to analyse
+                self.fail()?;
+                if csp.level() <= root {
+                    return Ok(false);
+                }
+                let learned = self.analyse(csp);
+                let from = csp.level();
+                let to = learned.assertion_level.max(root);
+                self.undo_to(csp, to);
+                self.monitor_mut().on_backtrack();
+                if to + 1 < from {
+                    self.monitor_mut().on_backjump(from, to);
+                }
+                consistent = self.assert_nogood(csp, learned);
+            }
+        }
+        Ok(true)
+    }
+
+    //first UIP of the last conflict: the latest literal of the conflict level is
+    //replaced by its reason until a single literal of this level remains
+    pub fn analyse(&self, csp: &Csp<T>) -> Learned<T> {
+        let expl = csp.explanations();
+        let level = csp.level();
+        let conflict = expl.conflict().expect("No conflict to analyse");
+        //literals of level 0 (or initial facts) always hold
+        let position = |l: &Literal<T>| expl.position(l).filter(|(lvl, _)| *lvl > 0);
+
+        //dom(w) = {} : every [w != a] holds
+        let w = &csp.vars()[&conflict.variable];
+        let mut clause: HashSet<Literal<T>> = w.dom().get_initial_values().iter()
+            .map(|a| Literal::Neq(vv(w.label().clone(), a.clone())))
+            .filter(|l| position(l).is_some())
+            .collect();
+
+        loop {
+            let latest = clause.iter()
+                .filter_map(|l| position(l).filter(|(lvl, _)| *lvl == level).map(|(_, s)| (s, l)))
+                .collect::<Vec<_>>();
+            if latest.len() <= 1 {
+                break;
+            }
+            let p = latest.into_iter().max_by_key(|(s, _)| *s).unwrap().1.clone();
+            clause.remove(&p);
+            for q in expl.reason(&p).cloned().unwrap_or_default() {
+                if position(&q).is_some() {
+                    clause.insert(q);
+                }
+            }
+        }
+
+        let mut literals: Vec<_> = clause.into_iter().collect();
+        literals.sort_by_key(|l| position(l).unwrap().1);
+        let uip = literals.iter()
+            .find(|l| position(l).unwrap().0 == level)
+            .cloned()
+            .expect("No literal at the conflict level");
+        let assertion_level = literals.iter()
+            .filter(|l| **l != uip)
+            .map(|l| position(l).unwrap().0)
+            .max()
+            .unwrap_or(0);
+        Learned { literals, uip, assertion_level }
+    }
+
+    //add the nogood and enforce the negation of its UIP (at the current level)
+    fn assert_nogood(&mut self, csp: &mut Csp<T>, learned: Learned<T>) -> bool {
+        let vars: Vec<_> = csp.vars().values().cloned().collect();
+        let nogood: Rc<dyn Constraint<T>> = Rc::new(Nogood::new(&vars, learned.literals.clone()));
+        csp.add_constraint(nogood.clone());
+        self.monitor_mut().on_nogood(learned.literals.len());
+
+        let x = csp.vars()[learned.uip.label()].clone();
+        let removed: Vec<T> = x.valid_values().into_iter()
+            .filter(|b| match &learned.uip {
+                Literal::Eq(vv) => *b == vv.value,
+                Literal::Neq(vv) => *b != vv.value,
+            })
+            .collect();
+        for b in &removed {
+            self.monitor_mut().on_value_deleted();
+            x.dom_mut().remove_value(b, csp.level());
+        }
+        let reason = learned.literals.into_iter()
+            .filter(|l| *l != learned.uip)
+            .collect();
+        csp.explain_removals_with(&nogood, &x, removed, reason);
+        if x.dom().is_empty() {
+            csp.explain_wipeout(&nogood, &x);
+            self.monitor_mut().on_domain_wipeout();
+            return false;
+        }
+        let events = x.take_events();
+        self.enforce(csp, events)
+    }
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::Expr;
+    use crate::csp::ast::pred::Pred;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use crate::{dom, neq, var, var_dom};
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::explanation::Literal;
+    use crate::csp::variable::extvar::ExVar;
+    use crate::csp::variable::vvalue::vv;
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::revise::AC1;
+    use crate::solver::consistency::scheme::ArcOriented;
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::heuristic::{Lexico, MinVal};
+    use crate::solver::search::limits::{Limit, SearchLimits, SearchOutcome};
+
+    //a: decides the values left to x, y, z (pairwise different)
+    //b, c: irrelevant to the failure
+    fn pigeons(holes: Vec<i32>) -> Csp<i32> {
+        let mut vmap = HashMap::new();
+        for l in ["a", "b", "c"] {
+            vmap.insert(String::from(l), var_dom!(String::from(l), dom![1, 2, 3]));
+        }
+        for l in ["x", "y", "z"] {
+            vmap.insert(String::from(l), var_dom!(String::from(l), SetDom::new(holes.clone())));
+        }
+        let mut constraints: Vec<Rc<dyn Constraint<i32>>> = vec![
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["b"]), var!(vmap["c"])))),
+        ];
+        for (l1, l2) in [("a", "x"), ("a", "y"), ("a", "z"), ("x", "y"), ("x", "z"), ("y", "z")] {
+            constraints.push(Rc::new(Intensional::from_pred(neq!(var!(vmap[l1]), var!(vmap[l2])))));
+        }
+        Csp::new(vmap, constraints)
+    }
+
+    fn engine() -> Engine<Statistics, ArcOriented, AC1, i32> {
+        Engine::with_heuristics(Consistency::new(ArcOriented, AC1, Statistics::default()),
+                                Box::new(Lexico), Box::new(MinVal))
+    }
+
+    #[test]
+    fn first_uip() {
+        let mut csp = pigeons(vec![1, 2, 3]);
+        let mut engine = engine();
+        assert!(engine.decide(&mut csp, vv("a".into(), 1)));
+        assert!(engine.decide(&mut csp, vv("b".into(), 1)));
+        assert!(!engine.decide(&mut csp, vv("x".into(), 2)));
+
+        //(x, 1) is already removed at level 1: [x != 3] dominates the decision x = 2
+        //not([x != 1] and [y != 1] and [z != 1] and [x != 3]), asserting x = 3 at level 1
+        let learned = engine.analyse(&csp);
+        assert_eq!(learned.uip, Literal::Neq(vv("x".into(), 3)));
+        assert_eq!(learned.assertion_level, 1);
+        assert_eq!(learned.literals.len(), 4);
+        assert!(learned.literals.contains(&Literal::Neq(vv("x".into(), 1))));
+        assert!(learned.literals.contains(&Literal::Neq(vv("y".into(), 1))));
+        assert!(learned.literals.contains(&Literal::Neq(vv("z".into(), 1))));
+    }
+
+    #[test]
+    fn learning_unsat() {
+        let mut csp = pigeons(vec![1, 2, 3]);
+        let e = csp.e();
+        let mut engine_ng = engine();
+        assert!(engine_ng.solve_learning(&mut csp).is_unsat());
+        assert!(engine_ng.monitor().nogoods > 0);
+        assert!(engine_ng.monitor().backjumps > 0);
+        assert_eq!(csp.e(), e + engine_ng.monitor().nogoods);
+
+        let mut engine_bt = engine();
+        assert!(engine_bt.solve(&mut pigeons(vec![1, 2, 3])).is_unsat());
+        assert!(engine_ng.monitor().nodes < engine_bt.monitor().nodes);
+    }
+
+    #[test]
+    fn learning_solution() {
+        let mut csp = pigeons(vec![1, 2, 3, 4]);
+        let mut engine = engine();
+        let solution = engine.solve_learning(&mut csp).solution().cloned().expect("Should be satisfiable");
+        let values: Vec<i32> = solution.iter().map(|vv| vv.value).collect();
+        //a, b, c, x, y, z
+        assert_ne!(values[1], values[2]);
+        assert!(values[3..].iter().all(|v| *v != values[0]));
+        assert!(values[3] != values[4] && values[3] != values[5] && values[4] != values[5]);
+        assert_eq!(csp.level(), 0);
+
+        engine.set_limits(SearchLimits { nodes: Some(2), ..SearchLimits::default() });
+        assert_eq!(engine.solve_learning(&mut pigeons(vec![1, 2, 3, 4])), SearchOutcome::Unknown(Limit::Nodes, None));
+    }
+}
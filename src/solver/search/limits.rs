@@ -0,0 +1,231 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Search limits and outcome
+    - SearchLimits: timeout, max nodes, max failures, max solutions, cancellation token
+    - Limiter: counters of the running search, checked by the engine at each node
+    - Interrupt: deadline and cancellation token of the running search, polled by the propagation
+      schemes through the monitor (see Monitor::interrupted), a long propagation stops early
+    - SearchOutcome: Sat | Unsat | Unknown (limit reached, with the best solution so far)
+***************************************/
+
+/**************************************
+            Limits
+***************************************/
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use crate::csp::domain::domain::OrdT;
+use crate::csp::variable::vvalue::VValue;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Time,
+    Nodes,
+    Failures,
+    Solutions,
+    //bound of a discrepancy search (see strategy.rs)
+    Discrepancies,
+    //iterations of a large neighbourhood search (see lns.rs)
+    Iterations,
+    Cancelled,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Time => write!(f, "time limit"),
+            Limit::Nodes => write!(f, "node limit"),
+            Limit::Failures => write!(f, "failure limit"),
+            Limit::Solutions => write!(f, "solution limit"),
+            Limit::Discrepancies => write!(f, "discrepancy limit"),
+            Limit::Iterations => write!(f, "iteration limit"),
+            Limit::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+//None: no limit
+#[derive(Debug, Clone)]
+pub struct SearchLimits {
+    pub timeout: Option<Duration>,
+    pub nodes: Option<usize>,
+    //failed decisions (domain wipeouts)
+    pub failures: Option<usize>,
+    pub solutions: Option<usize>,
+    //raised from outside (e.g. another thread) to stop the search
+    pub stop: Option<Arc<AtomicBool>>,
+}
+
+impl Default for SearchLimits {
+    //stops at the first solution
+    fn default() -> Self {
+        Self { timeout: None, nodes: None, failures: None, solutions: Some(1), stop: None }
+    }
+}
+
+impl SearchLimits {
+    //no limit at all (enumerates every solution)
+    pub fn none() -> Self {
+        Self { solutions: None, ..Self::default() }
+    }
+}
+
+/**************************************
+            Limiter
+***************************************/
+
+#[derive(Debug, Clone, Default)]
+pub struct Limiter {
+    limits: SearchLimits,
+    start: Option<Instant>,
+    pub nodes: usize,
+    pub failures: usize,
+    pub solutions: usize,
+}
+
+impl Limiter {
+    pub fn new(limits: SearchLimits) -> Self {
+        Self { limits, ..Self::default() }
+    }
+
+    pub fn limits(&self) -> &SearchLimits { &self.limits }
+
+    //resets the counters and the clock
+    pub fn start(&mut self) {
+        self.start = Some(Instant::now());
+        self.nodes = 0;
+        self.failures = 0;
+        self.solutions = 0;
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.map(|s| s.elapsed()).unwrap_or_default()
+    }
+
+    pub fn on_node(&mut self) { self.nodes += 1; }
+    pub fn on_failure(&mut self) { self.failures += 1; }
+    pub fn on_solution(&mut self) { self.solutions += 1; }
+
+    //deadline (from the start of the search) and cancellation token, for the monitor
+    pub fn interrupt(&self) -> Interrupt {
+        Interrupt {
+            deadline: self.limits.timeout.map(|t| self.start.unwrap_or_else(Instant::now) + t),
+            stop: self.limits.stop.clone(),
+        }
+    }
+
+    //first limit reached, if any
+    pub fn reached(&self) -> Option<Limit> {
+        let over = |limit: Option<usize>, count: usize| limit.is_some_and(|l| count >= l);
+        if self.limits.stop.as_ref().is_some_and(|s| s.load(Ordering::Relaxed)) {
+            Some(Limit::Cancelled)
+        } else if over(self.limits.solutions, self.solutions) {
+            Some(Limit::Solutions)
+        } else if self.limits.timeout.is_some_and(|t| self.elapsed() >= t) {
+            Some(Limit::Time)
+        } else if over(self.limits.nodes, self.nodes) {
+            Some(Limit::Nodes)
+        } else if over(self.limits.failures, self.failures) {
+            Some(Limit::Failures)
+        } else {
+            None
+        }
+    }
+}
+
+/**************************************
+            Interrupt
+***************************************/
+
+#[derive(Debug, Clone, Default)]
+pub struct Interrupt {
+    deadline: Option<Instant>,
+    stop: Option<Arc<AtomicBool>>,
+}
+
+impl Interrupt {
+    //the timeout is over or the token is raised (Limiter::reached reports the same limit)
+    pub fn is_raised(&self) -> bool {
+        self.stop.as_ref().is_some_and(|s| s.load(Ordering::Relaxed))
+            || self.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+}
+
+/**************************************
+            Outcome
+***************************************/
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchOutcome<T: OrdT> {
+    //search completed (or solution limit reached), last solution found
+    Sat(Vec<VValue<T>>),
+    //search completed without solution
+    Unsat,
+    //stopped by a limit, best solution so far
+    Unknown(Limit, Option<Vec<VValue<T>>>),
+}
+
+impl<T: OrdT> SearchOutcome<T> {
+    //stop: limit which interrupted the search (None if completed)
+    pub fn new(stop: Option<Limit>, best: Option<Vec<VValue<T>>>) -> Self {
+        match (stop, best) {
+            (None, None) => SearchOutcome::Unsat,
+            (None | Some(Limit::Solutions), Some(s)) => SearchOutcome::Sat(s),
+            (Some(limit), best) => SearchOutcome::Unknown(limit, best),
+        }
+    }
+
+    pub fn solution(&self) -> Option<&Vec<VValue<T>>> {
+        match self {
+            SearchOutcome::Sat(s) => Some(s),
+            SearchOutcome::Unsat => None,
+            SearchOutcome::Unknown(_, best) => best.as_ref(),
+        }
+    }
+
+    pub fn is_sat(&self) -> bool { matches!(self, SearchOutcome::Sat(_)) }
+    pub fn is_unsat(&self) -> bool { matches!(self, SearchOutcome::Unsat) }
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+    use crate::csp::variable::vvalue::vv;
+    use crate::solver::search::limits::{Limit, Limiter, SearchLimits, SearchOutcome};
+
+    #[test]
+    fn limiter_reached() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut limiter = Limiter::new(SearchLimits {
+            nodes: Some(2), stop: Some(stop.clone()), ..SearchLimits::none()
+        });
+        limiter.start();
+        limiter.on_node();
+        assert_eq!(limiter.reached(), None);
+        limiter.on_node();
+        assert_eq!(limiter.reached(), Some(Limit::Nodes));
+        stop.store(true, Ordering::Relaxed);
+        assert_eq!(limiter.reached(), Some(Limit::Cancelled));
+
+        let mut limiter = Limiter::new(SearchLimits { timeout: Some(Duration::ZERO), ..SearchLimits::none() });
+        limiter.start();
+        assert_eq!(limiter.reached(), Some(Limit::Time));
+    }
+
+    #[test]
+    fn outcome() {
+        let s = vec![vv("x".into(), 1)];
+        assert!(SearchOutcome::<i32>::new(None, None).is_unsat());
+        assert!(SearchOutcome::new(Some(Limit::Solutions), Some(s.clone())).is_sat());
+        assert_eq!(SearchOutcome::new(Some(Limit::Time), Some(s.clone())),
+                   SearchOutcome::Unknown(Limit::Time, Some(s.clone())));
+        assert_eq!(SearchOutcome::<i32>::new(Some(Limit::Nodes), None).solution(), None);
+    }
+}
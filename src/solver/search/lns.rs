@@ -0,0 +1,317 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Large Neighbourhood Search (LNS) for optimisation
+    - incumbent: first solution of the engine, then best solution found
+    - each iteration relaxes a neighbourhood (pluggable Neighbourhood) and fixes the other
+      variables to their incumbent value, the fragment is re-solved by a BranchAndBound
+      search under the fragment limits
+    - accepted incumbents are reported through Monitor::on_improvement
+    - the limits of the engine are restored once the LNS is over, each search gets at most the time left
+      to the LNS timeout
+  The objective variable is always relaxed.
+***************************************/
+
+/**************************************
+            Neighbourhoods
+***************************************/
+use std::collections::{BTreeSet, VecDeque};
+use std::time::{Duration, Instant};
+use petgraph::graph::NodeIndex;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::OrdT;
+use crate::csp::variable::vvalue::VValue;
+use crate::instrumentation::monitor::Monitor;
+use crate::solver::consistency::consistency::Revise;
+use crate::solver::consistency::scheme::Scheme;
+use crate::solver::search::engine::Engine;
+use crate::solver::search::limits::{Limit, SearchLimits, SearchOutcome};
+use crate::solver::search::objective::{BranchAndBound, Objective};
+
+pub trait Neighbourhood<T: OrdT> {
+    //labels of the variables to relax
+    fn relax(&mut self, csp: &Csp<T>, incumbent: &[VValue<T>], rng: &mut StdRng) -> BTreeSet<String>;
+}
+
+//size variables picked at random
+pub struct RandomNeighbourhood {
+    pub size: usize,
+}
+
+impl<T: OrdT> Neighbourhood<T> for RandomNeighbourhood {
+    fn relax(&mut self, csp: &Csp<T>, _incumbent: &[VValue<T>], rng: &mut StdRng) -> BTreeSet<String> {
+        let mut labels: Vec<_> = csp.vars().keys().cloned().collect();
+        labels.sort();
+        labels.choose_multiple(rng, self.size).cloned().collect()
+    }
+}
+
+//about size variables sharing constraints: breadth-first walk on Csp::dual_graph
+//from a random constraint, collecting the scopes of the visited constraints
+pub struct ConstraintNeighbourhood {
+    pub size: usize,
+}
+
+impl<T: OrdT> Neighbourhood<T> for ConstraintNeighbourhood {
+    fn relax(&mut self, csp: &Csp<T>, _incumbent: &[VValue<T>], rng: &mut StdRng) -> BTreeSet<String> {
+        let mut relaxed = BTreeSet::new();
+        if csp.e() == 0 {
+            return relaxed;
+        }
+        //node i of the dual graph is constraint i
+        let g = csp.dual_graph();
+        let start = NodeIndex::new(rng.gen_range(0..csp.e()));
+        let mut visited = BTreeSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(n) = queue.pop_front() {
+            if relaxed.len() >= self.size {
+                break;
+            }
+            relaxed.extend(csp.constraints()[n.index()].scp().iter().map(|x| x.label().clone()));
+            let mut next: Vec<_> = g.neighbors(n).filter(|m| !visited.contains(m)).collect();
+            next.sort();
+            next.shuffle(rng);
+            for m in next {
+                visited.insert(m);
+                queue.push_back(m);
+            }
+        }
+        relaxed
+    }
+}
+
+/**************************************
+            LNS
+***************************************/
+
+#[derive(Debug, Clone)]
+pub struct LnsParams {
+    pub iterations: usize,
+    //whole LNS run
+    pub timeout: Option<Duration>,
+    //limits of each fragment search (and of the first solution)
+    pub fragment: SearchLimits,
+    pub seed: u64,
+}
+
+impl Default for LnsParams {
+    fn default() -> Self {
+        Self {
+            iterations: 100,
+            timeout: None,
+            fragment: SearchLimits { failures: Some(100), ..SearchLimits::none() },
+            seed: 0,
+        }
+    }
+}
+
+pub struct Lns<T: OrdT> {
+    objective: Objective,
+    neighbourhood: Box<dyn Neighbourhood<T>>,
+    params: LnsParams,
+    rng: StdRng,
+}
+
+impl<T: OrdT> Lns<T> {
+    pub fn new(objective: Objective, neighbourhood: Box<dyn Neighbourhood<T>>, params: LnsParams) -> Self {
+        let rng = StdRng::seed_from_u64(params.seed);
+        Self { objective, neighbourhood, params, rng }
+    }
+
+    //Sat: optimal (a fragment relaxing every variable was fully explored)
+    //Unknown(Limit::Time | Limit::Iterations, incumbent) otherwise
+    //the csp is restored at its initial level
+    pub fn solve<M, S, R>(&mut self, engine: &mut Engine<M, S, R, T>, csp: &mut Csp<T>) -> SearchOutcome<T>
+    where
+        S: Scheme<M, T, R>,
+        R: Revise<M, T>,
+        M: Monitor
+    {
+        let limits = engine.limiter().limits().clone();
+        let outcome = self.run(engine, csp);
+        engine.set_limits(limits);
+        outcome
+    }
+
+    //fragment limits, the timeout bounded by the time left to the LNS
+    fn fragment(&self, start: Instant) -> SearchLimits {
+        let left = self.params.timeout.map(|t| t.saturating_sub(start.elapsed()));
+        let timeout = match (self.params.fragment.timeout, left) {
+            (Some(t), Some(l)) => Some(t.min(l)),
+            (t, l) => t.or(l),
+        };
+        SearchLimits { timeout, ..self.params.fragment.clone() }
+    }
+
+    fn run<M, S, R>(&mut self, engine: &mut Engine<M, S, R, T>, csp: &mut Csp<T>) -> SearchOutcome<T>
+    where
+        S: Scheme<M, T, R>,
+        R: Revise<M, T>,
+        M: Monitor
+    {
+        let start = Instant::now();
+        let root = csp.level();
+        engine.set_limits(SearchLimits { solutions: Some(1), ..self.fragment(start) });
+        let mut incumbent = match engine.search(csp) {
+            SearchOutcome::Sat(s) => s,
+            outcome => return outcome,
+        };
+        engine.monitor_mut().on_improvement();
+
+        for _ in 0..self.params.iterations {
+            if self.params.timeout.is_some_and(|t| start.elapsed() >= t) {
+                return SearchOutcome::Unknown(Limit::Time, Some(incumbent));
+            }
+            let mut relaxed = self.neighbourhood.relax(csp, &incumbent, &mut self.rng);
+            relaxed.insert(self.objective.label().clone());
+            let whole = relaxed.len() == csp.n();
+
+            //fix the other variables to their incumbent values
+            let mut consistent = true;
+            for vvalue in incumbent.iter().filter(|vv| !relaxed.contains(&vv.label)) {
+                if !engine.decide(csp, vvalue.clone()) {
+                    consistent = false;
+                    break;
+                }
+            }
+            let outcome = if consistent {
+                let bound = self.objective.value(&incumbent);
+                engine.set_limits(self.fragment(start));
+                engine.search_with(csp, &mut BranchAndBound::with_bound(self.objective.clone(), bound).quiet())
+            } else {
+                SearchOutcome::Unknown(Limit::Failures, None)
+            };
+            engine.undo_to(csp, root);
+
+            if let Some(s) = outcome.solution() {
+                //improving by construction (bound)
+                incumbent = s.clone();
+                engine.monitor_mut().on_improvement();
+            }
+            if whole && !matches!(outcome, SearchOutcome::Unknown(..)) {
+                return SearchOutcome::Sat(incumbent);
+            }
+        }
+        SearchOutcome::Unknown(Limit::Iterations, Some(incumbent))
+    }
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::Expr;
+    use crate::csp::ast::pred::Pred;
+    use std::rc::Rc;
+    use std::time::{Duration, Instant};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::{lt, neq, var};
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::variable::extvar::generate_variables;
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::revise::AC1;
+    use crate::solver::consistency::scheme::ArcOriented;
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::limits::{Limit, SearchLimits, SearchOutcome};
+    use crate::solver::search::lns::{ConstraintNeighbourhood, Lns, LnsParams, Neighbourhood, RandomNeighbourhood};
+    use crate::solver::search::objective::Objective;
+
+    //x1 < x2 < ... < xn
+    fn chain(n: usize) -> Csp<i32> {
+        let vmap = generate_variables("x", n, &SetDom::new((1..=2 * n as i32).collect()));
+        let constraints: Vec<Rc<dyn Constraint<i32>>> = (1..n)
+            .map(|i| Rc::new(Intensional::from_pred(lt!(
+                var!(vmap[&format!("x{}", i)]), var!(vmap[&format!("x{}", i + 1)])))) as Rc<dyn Constraint<i32>>)
+            .collect();
+        Csp::new(vmap, constraints)
+    }
+
+    #[test]
+    fn neighbourhoods() {
+        let csp = chain(6);
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(Neighbourhood::<i32>::relax(&mut RandomNeighbourhood { size: 3 }, &csp, &[], &mut rng).len(), 3);
+        //a path of connected variables
+        let relaxed = Neighbourhood::<i32>::relax(&mut ConstraintNeighbourhood { size: 3 }, &csp, &[], &mut rng);
+        assert!(relaxed.len() >= 3);
+        let mut index: Vec<usize> = relaxed.iter().map(|l| l[1..].parse().unwrap()).collect();
+        index.sort();
+        assert!(index.windows(2).all(|w| w[1] == w[0] + 1));
+    }
+
+    #[test]
+    fn lns_improves_incumbent() {
+        //first solution x_i = i, x6 can reach 12
+        let mut csp = chain(6);
+        let mut engine = Engine::new(Consistency::new(ArcOriented, AC1, Statistics::default()));
+        let objective = Objective::Maximize("x6".into());
+        let mut lns = Lns::new(objective.clone(), Box::new(RandomNeighbourhood { size: 2 }), LnsParams::default());
+        let outcome = lns.solve(&mut engine, &mut csp);
+
+        let best = outcome.solution().expect("Should find a solution");
+        assert_eq!(objective.value(best), 12);
+        assert!(engine.monitor().improvements > 1);
+        assert_eq!(csp.level(), 0);
+        assert!(!matches!(outcome, SearchOutcome::Unsat));
+    }
+
+    #[test]
+    fn lns_restores_limits() {
+        let mut csp = chain(4);
+        let mut engine = Engine::new(Consistency::new(ArcOriented, AC1, Statistics::default()));
+        engine.set_limits(SearchLimits { nodes: Some(50), ..SearchLimits::none() });
+        let params = LnsParams { iterations: 5, ..LnsParams::default() };
+        let mut lns = Lns::new(Objective::Maximize("x4".into()), Box::new(RandomNeighbourhood { size: 2 }), params);
+        lns.solve(&mut engine, &mut csp);
+        //one improvement per accepted incumbent (first solution included)
+        assert!(engine.monitor().improvements >= 1);
+        assert!(engine.monitor().improvements <= 6);
+        assert_eq!(engine.limiter().limits().nodes, Some(50));
+        assert_eq!(engine.limiter().limits().solutions, None);
+    }
+
+    #[test]
+    fn lns_whole_relaxation_is_optimal() {
+        let mut csp = chain(3);
+        let mut engine = Engine::new(Consistency::new(ArcOriented, AC1, Statistics::default()));
+        let objective = Objective::Minimize("x3".into());
+        let mut lns = Lns::new(objective.clone(), Box::new(RandomNeighbourhood { size: 3 }), LnsParams::default());
+        match lns.solve(&mut engine, &mut csp) {
+            SearchOutcome::Sat(s) => assert_eq!(objective.value(&s), 3),
+            outcome => panic!("Unexpected outcome {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn lns_timeout_bounds_fragments() {
+        //x1 .. x9 pairwise different and greater than x10 on 1..10: proving x10 = 1 optimal is a pigeonhole
+        let vmap = generate_variables("x", 10, &SetDom::new((1..=10).collect()));
+        let mut constraints: Vec<Rc<dyn Constraint<i32>>> = vec![];
+        for i in 1..10 {
+            for j in (i + 1)..10 {
+                constraints.push(Rc::new(Intensional::from_pred(neq!(
+                    var!(vmap[&format!("x{}", i)]), var!(vmap[&format!("x{}", j)])))));
+            }
+            constraints.push(Rc::new(Intensional::from_pred(lt!(var!(vmap["x10"]), var!(vmap[&format!("x{}", i)])))));
+        }
+        let mut csp = Csp::new(vmap, constraints);
+        let mut engine = Engine::new(Consistency::new(ArcOriented, AC1, Statistics::default()));
+        //fragments without limits, relaxing every variable
+        let params = LnsParams { timeout: Some(Duration::from_millis(50)), fragment: SearchLimits::none(),
+            ..LnsParams::default() };
+        let mut lns = Lns::new(Objective::Maximize("x10".into()), Box::new(RandomNeighbourhood { size: 10 }), params);
+        let start = Instant::now();
+        let outcome = lns.solve(&mut engine, &mut csp);
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(matches!(outcome, SearchOutcome::Unknown(Limit::Time, Some(_))));
+    }
+}
@@ -0,0 +1,10 @@
+pub mod heuristic;
+pub mod engine;
+pub mod cbj;
+pub mod learning;
+pub mod limits;
+pub mod strategy;
+pub mod objective;
+pub mod lns;
+pub mod portfolio;
+pub mod eps;
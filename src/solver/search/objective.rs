@@ -0,0 +1,234 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Optimisation
+    - Objective: minimise or maximise the value of a variable
+    - BranchAndBound: depth-first strategy, each solution tightens the bound of the objective
+      (values not improving the incumbent are removed at each node, see Engine::restrict)
+    - SharedBound: bound exchanged between concurrent searches (see portfolio.rs)
+  With SearchLimits::none(), Sat carries an optimal solution,
+  Unknown the best solution found before the limit.
+***************************************/
+
+/**************************************
+            Objective
+***************************************/
+use std::sync::{Arc, Mutex};
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::OrdT;
+use crate::csp::variable::vvalue::{vv, VValue};
+use crate::instrumentation::monitor::Monitor;
+use crate::solver::consistency::consistency::Revise;
+use crate::solver::consistency::scheme::Scheme;
+use crate::solver::search::engine::Engine;
+use crate::solver::search::limits::Limit;
+use crate::solver::search::strategy::Strategy;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Objective {
+    Minimize(String),
+    Maximize(String),
+}
+
+impl Objective {
+    //objective variable
+    pub fn label(&self) -> &String {
+        match self {
+            Objective::Minimize(l) | Objective::Maximize(l) => l,
+        }
+    }
+
+    pub fn value<T: OrdT>(&self, solution: &[VValue<T>]) -> T {
+        solution.iter()
+            .find(|vv| vv.label == *self.label())
+            .map(|vv| vv.value.clone())
+            .expect("Objective variable not in solution")
+    }
+
+    //a strictly better than b
+    pub fn improves<T: OrdT>(&self, a: &T, b: &T) -> bool {
+        match self {
+            Objective::Minimize(_) => a < b,
+            Objective::Maximize(_) => a > b,
+        }
+    }
+}
+
+/**************************************
+            Branch and Bound
+***************************************/
+
+//best objective value found by any search sharing it
+pub type SharedBound<T> = Arc<Mutex<Option<T>>>;
+
+pub struct BranchAndBound<T: OrdT> {
+    objective: Objective,
+    //objective value of the incumbent
+    bound: Option<T>,
+    shared: Option<SharedBound<T>>,
+    //improvements reported through Monitor::on_improvement
+    report: bool,
+}
+
+impl<T: OrdT> BranchAndBound<T> {
+    pub fn new(objective: Objective) -> Self {
+        Self { objective, bound: None, shared: None, report: true }
+    }
+
+    //only solutions strictly better than bound are searched
+    pub fn with_bound(objective: Objective, bound: T) -> Self {
+        Self { objective, bound: Some(bound), shared: None, report: true }
+    }
+
+    //the bound is read from and published to shared
+    pub fn with_shared(objective: Objective, shared: SharedBound<T>) -> Self {
+        Self { objective, bound: None, shared: Some(shared), report: true }
+    }
+
+    //improvements are left to the caller, which reports the ones it accepts (see lns.rs)
+    pub fn quiet(mut self) -> Self {
+        self.report = false;
+        self
+    }
+
+    pub fn bound(&self) -> Option<&T> { self.bound.as_ref() }
+
+    //keeps the best of the local and the shared bounds
+    fn sync(&mut self) {
+        if let Some(shared) = &self.shared {
+            let mut shared = shared.lock().expect("Shared bound poisoned");
+            match (&self.bound, shared.as_ref()) {
+                (Some(local), Some(other)) if self.objective.improves(other, local) => self.bound = Some(other.clone()),
+                (None, Some(other)) => self.bound = Some(other.clone()),
+                (Some(local), Some(other)) if self.objective.improves(local, other) => *shared = Some(local.clone()),
+                (Some(local), None) => *shared = Some(local.clone()),
+                _ => {}
+            }
+        }
+    }
+
+    //removes the objective values not improving the bound, false on wipeout
+    //(nothing is removed at level 0, where removals could not be undone)
+    fn prune<M, S, R>(&mut self, engine: &mut Engine<M, S, R, T>, csp: &mut Csp<T>) -> bool
+    where
+        S: Scheme<M, T, R>,
+        R: Revise<M, T>,
+        M: Monitor
+    {
+        self.sync();
+        let bound = match &self.bound {
+            Some(b) if csp.level() > 0 => b,
+            _ => return true,
+        };
+        let x = csp.vars()[self.objective.label()].clone();
+        let removed: Vec<T> = x.valid_values().into_iter()
+            .filter(|a| !self.objective.improves(a, bound))
+            .collect();
+        removed.is_empty() || engine.restrict(csp, "objective", &x, removed)
+    }
+}
+
+impl<M, S, R, T:OrdT> Strategy<M, S, R, T> for BranchAndBound<T>
+where
+    S: Scheme<M, T, R>,
+    R: Revise<M, T>,
+    M: Monitor
+{
+    fn explore(&mut self, engine: &mut Engine<M, S, R, T>, csp: &mut Csp<T>,
+               best: &mut Option<Vec<VValue<T>>>) -> Result<(), Limit> {
+        engine.check()?;
+        if !self.prune(engine, csp) {
+            return engine.fail();
+        }
+        let x = match engine.select(csp) {
+            Some(x) => x,
+            None => {
+                let solution = engine.solution(csp);
+                self.bound = Some(self.objective.value(&solution));
+                self.sync();
+                if self.report {
+                    engine.monitor_mut().on_improvement();
+                }
+                return engine.record(csp, best);
+            }
+        };
+        for a in engine.values(&x) {
+            let result = if engine.decide(csp, vv(x.label().clone(), a)) {
+                self.explore(engine, csp, best)
+            } else {
+                engine.fail()
+            };
+            engine.backtrack(csp);
+            result?;
+        }
+        Ok(())
+    }
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::Expr;
+    use crate::csp::ast::pred::Pred;
+    use std::rc::Rc;
+    use crate::{dom, lt, neq, var};
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::variable::extvar::generate_variables;
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::revise::AC1;
+    use crate::solver::consistency::scheme::ArcOriented;
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::limits::{SearchLimits, SearchOutcome};
+    use crate::solver::search::objective::{BranchAndBound, Objective};
+
+    //x1 < x2 < x3, x1 != 1
+    fn chain() -> Csp<i32> {
+        let vmap = generate_variables("x", 3, &dom![1, 2, 3, 4, 5]);
+        let one = generate_variables("one", 1, &dom![1]);
+        let constraints: Vec<Rc<dyn Constraint<i32>>> = vec![
+            Rc::new(Intensional::from_pred(lt!(var!(vmap["x1"]), var!(vmap["x2"])))),
+            Rc::new(Intensional::from_pred(lt!(var!(vmap["x2"]), var!(vmap["x3"])))),
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x1"]), var!(one["one1"])))),
+        ];
+        let mut vars = vmap.clone();
+        vars.extend(one);
+        Csp::new(vars, constraints)
+    }
+
+    #[test]
+    fn objective_improves() {
+        let min = Objective::Minimize("x".into());
+        assert!(min.improves(&1, &2));
+        assert!(!min.improves(&2, &2));
+        assert!(Objective::Maximize("x".into()).improves(&3, &2));
+    }
+
+    #[test]
+    fn branch_and_bound_optimum() {
+        let mut engine = Engine::new(Consistency::new(ArcOriented, AC1, Statistics::default()));
+        engine.set_limits(SearchLimits::none());
+
+        let objective = Objective::Maximize("x3".into());
+        let mut csp = chain();
+        let outcome = engine.search_with(&mut csp, &mut BranchAndBound::new(objective.clone()));
+        assert!(outcome.is_sat());
+        assert_eq!(objective.value(outcome.solution().unwrap()), 5);
+
+        //x1 != 1 and x1 < x2 < x3: x3 >= 4
+        let objective = Objective::Minimize("x3".into());
+        let outcome = engine.search_with(&mut csp, &mut BranchAndBound::new(objective.clone()));
+        assert_eq!(objective.value(outcome.solution().unwrap()), 4);
+        assert!(engine.monitor().improvements >= 2);
+        assert_eq!(csp.level(), 0);
+
+        //nothing better than 4
+        let outcome = engine.search_with(&mut csp, &mut BranchAndBound::with_bound(objective, 4));
+        assert_eq!(outcome, SearchOutcome::Unsat);
+    }
+}
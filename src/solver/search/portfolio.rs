@@ -0,0 +1,317 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Parallel portfolio
+    - Worker: a named search configuration (scheme, revise, heuristics, seed, strategy...)
+      whose engine is built inside its thread
+    - Portfolio: runs every worker on its own copy of the model (built per thread,
+      Csp is not Send) and stops them as soon as one of them concludes
+    - solve: first Sat / Unsat wins
+    - optimise: branch and bound workers share their bound (SharedBound),
+      the best solution wins
+    - the workers share an internal stop token, raised by the first worker to conclude
+      or when the caller's token is (the caller's token is only read)
+***************************************/
+
+/**************************************
+            Worker
+***************************************/
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::OrdT;
+use crate::instrumentation::monitor::Monitor;
+use crate::solver::consistency::consistency::Revise;
+use crate::solver::consistency::scheme::Scheme;
+use crate::solver::search::engine::Engine;
+use crate::solver::search::limits::{Limit, SearchLimits, SearchOutcome};
+use crate::solver::search::objective::{BranchAndBound, Objective, SharedBound};
+
+//what a worker has to do on its copy of the model
+pub struct Job<T: OrdT> {
+    //including the internal stop token of the portfolio
+    pub limits: SearchLimits,
+    //optimisation: objective and bound shared by the workers
+    pub objective: Option<(Objective, SharedBound<T>)>,
+}
+
+type Run<T> = Box<dyn Fn(&mut Csp<T>, &Job<T>) -> SearchOutcome<T> + Send + Sync>;
+
+pub struct Worker<T: OrdT> {
+    pub name: String,
+    run: Run<T>,
+}
+
+impl<T: OrdT + 'static> Worker<T> {
+    //engine built by make: depth-first search, or branch and bound when optimising
+    pub fn new<M, S, R, F>(name: &str, make: F) -> Self
+    where
+        S: Scheme<M, T, R>,
+        R: Revise<M, T>,
+        M: Monitor,
+        F: Fn() -> Engine<M, S, R, T> + Send + Sync + 'static
+    {
+        Self::custom(name, move |csp, job| {
+            let mut engine = make();
+            match &job.objective {
+                None => {
+                    engine.set_limits(job.limits.clone());
+                    engine.search(csp)
+                }
+                Some((objective, shared)) => {
+                    engine.set_limits(SearchLimits { solutions: None, ..job.limits.clone() });
+                    engine.search_with(csp, &mut BranchAndBound::with_shared(objective.clone(), shared.clone()))
+                }
+            }
+        })
+    }
+
+    //any search honouring job.limits, and optimising job.objective when set
+    //(a Sat outcome ends the portfolio)
+    pub fn custom<F>(name: &str, run: F) -> Self
+    where
+        F: Fn(&mut Csp<T>, &Job<T>) -> SearchOutcome<T> + Send + Sync + 'static
+    {
+        Self { name: name.to_string(), run: Box::new(run) }
+    }
+}
+
+/**************************************
+            Portfolio
+***************************************/
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortfolioOutcome<T: OrdT> {
+    pub outcome: SearchOutcome<T>,
+    //configuration which concluded (or found the best solution)
+    pub winner: Option<String>,
+}
+
+pub struct Portfolio<T: OrdT> {
+    workers: Vec<Worker<T>>,
+    limits: SearchLimits,
+}
+
+impl<T: OrdT + Send + 'static> Portfolio<T> {
+    //limits of each worker (a stop token cancels the whole portfolio)
+    pub fn new(limits: SearchLimits) -> Self {
+        Self { workers: Vec::new(), limits }
+    }
+
+    pub fn add(&mut self, worker: Worker<T>) {
+        self.workers.push(worker);
+    }
+
+    pub fn workers(&self) -> usize { self.workers.len() }
+
+    //satisfaction: the first worker reaching Sat or Unsat stops the others
+    pub fn solve<F>(&self, build: F) -> PortfolioOutcome<T>
+    where
+        F: Fn() -> Csp<T> + Sync
+    {
+        let outcomes = self.run(&build, None);
+        let winner = outcomes.iter()
+            .find(|(_, o)| !matches!(o, SearchOutcome::Unknown(..)))
+            .or_else(|| outcomes.iter().find(|(_, o)| o.solution().is_some()))
+            .or(outcomes.first());
+        PortfolioOutcome {
+            outcome: winner.map(|(_, o)| o.clone()).unwrap_or(SearchOutcome::Unknown(Limit::Cancelled, None)),
+            winner: winner.map(|(n, _)| n.clone()),
+        }
+    }
+
+    //optimisation: the search ends when a worker completes (its bound is optimal)
+    pub fn optimise<F>(&self, build: F, objective: Objective) -> PortfolioOutcome<T>
+    where
+        F: Fn() -> Csp<T> + Sync
+    {
+        let shared: SharedBound<T> = Arc::new(Mutex::new(None));
+        let outcomes = self.run(&build, Some((objective.clone(), shared)));
+
+        //first worker with the best solution
+        let mut best: Option<&(String, SearchOutcome<T>)> = None;
+        for o in outcomes.iter().filter(|(_, o)| o.solution().is_some()) {
+            let value = objective.value(o.1.solution().unwrap());
+            if best.is_none_or(|b| objective.improves(&value, &objective.value(b.1.solution().unwrap()))) {
+                best = Some(o);
+            }
+        }
+        let completed = outcomes.iter().any(|(_, o)| !matches!(o, SearchOutcome::Unknown(..)));
+        let solution = best.map(|(_, o)| o.solution().unwrap().clone());
+        let outcome = match (completed, solution) {
+            (true, Some(s)) => SearchOutcome::Sat(s),
+            (true, None) => SearchOutcome::Unsat,
+            (false, s) => {
+                let limit = outcomes.iter()
+                    .find_map(|(_, o)| match o {
+                        SearchOutcome::Unknown(l, _) if *l != Limit::Cancelled => Some(*l),
+                        _ => None,
+                    })
+                    .unwrap_or(Limit::Cancelled);
+                SearchOutcome::Unknown(limit, s)
+            }
+        };
+        let winner = best.or_else(|| outcomes.iter().find(|(_, o)| !matches!(o, SearchOutcome::Unknown(..))));
+        PortfolioOutcome { outcome, winner: winner.map(|(n, _)| n.clone()) }
+    }
+
+    //outcomes in completion order
+    fn run<F>(&self, build: &F, objective: Option<(Objective, SharedBound<T>)>) -> Vec<(String, SearchOutcome<T>)>
+    where
+        F: Fn() -> Csp<T> + Sync
+    {
+        let cancelled = self.limits.stop.as_ref().is_some_and(|cancel| cancel.load(Ordering::Relaxed));
+        let stop = Arc::new(AtomicBool::new(cancelled));
+        let job = Job { limits: SearchLimits { stop: Some(stop.clone()), ..self.limits.clone() }, objective };
+        let outcomes = Mutex::new(Vec::new());
+        let done = AtomicBool::new(false);
+        thread::scope(|scope| {
+            let workers: Vec<_> = self.workers.iter().map(|worker| {
+                let (job, stop, outcomes) = (&job, &stop, &outcomes);
+                scope.spawn(move || {
+                    let mut csp = build();
+                    let outcome = (worker.run)(&mut csp, job);
+                    if !matches!(outcome, SearchOutcome::Unknown(..)) {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                    outcomes.lock().expect("Portfolio poisoned").push((worker.name.clone(), outcome));
+                })
+            }).collect();
+            //link: the caller's token raises the internal one while the workers run
+            if let Some(cancel) = &self.limits.stop {
+                let (stop, done) = (&stop, &done);
+                scope.spawn(move || {
+                    while !done.load(Ordering::Relaxed) {
+                        if cancel.load(Ordering::Relaxed) {
+                            stop.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                        thread::sleep(Duration::from_millis(1));
+                    }
+                });
+            }
+            let joined: Vec<_> = workers.into_iter().map(|w| w.join()).collect();
+            done.store(true, Ordering::Relaxed);
+            for result in joined {
+                if let Err(panic) = result {
+                    std::panic::resume_unwind(panic);
+                }
+            }
+        });
+        outcomes.into_inner().expect("Portfolio poisoned")
+    }
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::Expr;
+    use crate::csp::ast::pred::Pred;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use crate::{lt, neq, var};
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::truth::Truth;
+    use crate::csp::variable::extvar::generate_variables;
+    use crate::instrumentation::monitor::NoMonitor;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::revise::AC1;
+    use crate::solver::consistency::scheme::{ArcOriented, VariableOriented};
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::heuristic::{DomDeg, Lexico, MinVal, RandomVal};
+    use crate::solver::search::limits::{Limit, SearchLimits, SearchOutcome};
+    use crate::solver::search::objective::{BranchAndBound, Objective};
+    use crate::solver::search::portfolio::{Portfolio, Worker};
+    use crate::solver::search::strategy::Lds;
+
+    //k-colouring of K_n, plus x1 < xn (same scope as x1 != xn)
+    fn clique(n: usize, k: i32) -> Csp<i32> {
+        let vmap = generate_variables("x", n, &SetDom::new((1..=k).collect()));
+        let mut constraints: Vec<Rc<dyn Constraint<i32>>> = vec![];
+        for i in 1..=n {
+            for j in (i + 1)..=n {
+                constraints.push(Rc::new(Intensional::from_pred(neq!(
+                    var!(vmap[&format!("x{}", i)]), var!(vmap[&format!("x{}", j)])))));
+            }
+        }
+        constraints.push(Rc::new(Intensional::from_pred(lt!(var!(vmap["x1"]), var!(vmap[&format!("x{}", n)])))));
+        Csp::new(vmap, constraints)
+    }
+
+    fn portfolio() -> Portfolio<i32> {
+        let mut portfolio = Portfolio::new(SearchLimits::default());
+        portfolio.add(Worker::new("arc-ac1-lexico", || Engine::with_heuristics(
+            Consistency::new(ArcOriented, AC1, NoMonitor), Box::new(Lexico), Box::new(MinVal))));
+        portfolio.add(Worker::new("var-ac1-domdeg-random", || Engine::with_heuristics(
+            Consistency::new(VariableOriented, AC1, NoMonitor), Box::new(DomDeg), Box::new(RandomVal::new(1)))));
+        portfolio.add(Worker::custom("arc-ac1-lds", |csp, job| {
+            let mut engine = Engine::new(Consistency::new(ArcOriented, AC1, NoMonitor));
+            match &job.objective {
+                None => {
+                    engine.set_limits(job.limits.clone());
+                    engine.search_with(csp, &mut Lds::default())
+                }
+                Some((objective, shared)) => {
+                    engine.set_limits(SearchLimits { solutions: None, ..job.limits.clone() });
+                    engine.search_with(csp, &mut BranchAndBound::with_shared(objective.clone(), shared.clone()))
+                }
+            }
+        }));
+        portfolio
+    }
+
+    #[test]
+    fn portfolio_solve() {
+        let portfolio = portfolio();
+        let result = portfolio.solve(|| clique(4, 4));
+        let solution = result.outcome.solution().expect("Should be satisfiable");
+        assert_eq!(clique(4, 4).is_solution(solution), Truth::True);
+        assert!(result.winner.is_some());
+
+        let result = portfolio.solve(|| clique(4, 3));
+        assert_eq!(result.outcome, SearchOutcome::Unsat);
+    }
+
+    #[test]
+    fn portfolio_optimise() {
+        let portfolio = portfolio();
+        let objective = Objective::Maximize("x1".into());
+        let result = portfolio.optimise(|| clique(4, 5), objective.clone());
+        assert!(result.outcome.is_sat());
+        //x1 < x4
+        assert_eq!(objective.value(result.outcome.solution().unwrap()), 4);
+    }
+
+    #[test]
+    fn portfolio_limits() {
+        let mut portfolio = Portfolio::new(SearchLimits { nodes: Some(1), ..SearchLimits::default() });
+        portfolio.add(Worker::new("arc-ac1", || Engine::new(Consistency::new(ArcOriented, AC1, NoMonitor))));
+        let result = portfolio.solve(|| clique(6, 5));
+        assert_eq!(result.outcome, SearchOutcome::Unknown(Limit::Nodes, None));
+        assert_eq!(portfolio.workers(), 1);
+    }
+
+    #[test]
+    fn portfolio_stop_token() {
+        //a worker concluding does not raise the caller's token
+        let cancel = Arc::new(AtomicBool::new(false));
+        let mut portfolio = portfolio();
+        portfolio.limits.stop = Some(cancel.clone());
+        assert!(portfolio.solve(|| clique(4, 4)).outcome.is_sat());
+        assert!(!cancel.load(Ordering::Relaxed));
+        assert!(portfolio.solve(|| clique(4, 4)).outcome.is_sat());
+
+        //the caller's token stops every worker
+        cancel.store(true, Ordering::Relaxed);
+        let result = portfolio.solve(|| clique(6, 5));
+        assert_eq!(result.outcome, SearchOutcome::Unknown(Limit::Cancelled, None));
+    }
+}
@@ -0,0 +1,299 @@
+/**************************************
+- Author: Clement Poncelet
+- Desc: Exploration strategies of the search tree (see Engine::search_with)
+    - Dfs: depth-first search (d-way branching)
+    - Lds: Limited Discrepancy Search, iteration k visits the leaves with exactly k discrepancies
+    - Dds: Depth-bounded Discrepancy Search, iteration i takes its last discrepancy at depth i-1
+  A discrepancy is a branch not following the value heuristic (any value but the first).
+  Strategies use the engine primitives (decide, backtrack, record, check) so limits,
+  monitors and consistency apply the same way to each of them.
+***************************************/
+
+/**************************************
+            Strategy
+***************************************/
+use crate::csp::csp::Csp;
+use crate::csp::domain::domain::OrdT;
+use crate::csp::variable::vvalue::{vv, VValue};
+use crate::instrumentation::monitor::Monitor;
+use crate::solver::consistency::consistency::Revise;
+use crate::solver::consistency::scheme::Scheme;
+use crate::solver::search::engine::Engine;
+use crate::solver::search::limits::Limit;
+
+pub trait Strategy<M, S, R, T:OrdT>
+where
+    S: Scheme<M, T, R>,
+    R: Revise<M, T>,
+    M: Monitor
+{
+    //explores the subtree of the current (consistent) node, solutions are recorded in best
+    //Err(limit) as soon as a limit is reached, Ok once the subtree is explored
+    fn explore(&mut self, engine: &mut Engine<M, S, R, T>, csp: &mut Csp<T>,
+               best: &mut Option<Vec<VValue<T>>>) -> Result<(), Limit>;
+}
+
+/**************************************
+            DFS
+***************************************/
+
+pub struct Dfs;
+
+impl<M, S, R, T:OrdT> Strategy<M, S, R, T> for Dfs
+where
+    S: Scheme<M, T, R>,
+    R: Revise<M, T>,
+    M: Monitor
+{
+    fn explore(&mut self, engine: &mut Engine<M, S, R, T>, csp: &mut Csp<T>,
+               best: &mut Option<Vec<VValue<T>>>) -> Result<(), Limit> {
+        engine.check()?;
+        let x = match engine.select(csp) {
+            Some(x) => x,
+            None => return engine.record(csp, best),
+        };
+        for a in engine.values(&x) {
+            let result = if engine.decide(csp, vv(x.label().clone(), a)) {
+                self.explore(engine, csp, best)
+            } else {
+                engine.fail()
+            };
+            engine.backtrack(csp);
+            result?;
+        }
+        Ok(())
+    }
+}
+
+/**************************************
+            LDS
+***************************************/
+
+//max_discrepancies: None, iterates until the tree is fully explored
+#[derive(Debug, Default)]
+pub struct Lds {
+    pub max_discrepancies: Option<usize>,
+    //a branch was cut by the current iteration
+    pruned: bool,
+}
+
+impl Lds {
+    pub fn new(max_discrepancies: Option<usize>) -> Self {
+        Self { max_discrepancies, pruned: false }
+    }
+
+    fn probe<M, S, R, T:OrdT>(&mut self, engine: &mut Engine<M, S, R, T>, csp: &mut Csp<T>,
+                              best: &mut Option<Vec<VValue<T>>>, left: usize) -> Result<(), Limit>
+    where
+        S: Scheme<M, T, R>,
+        R: Revise<M, T>,
+        M: Monitor
+    {
+        engine.check()?;
+        let x = match engine.select(csp) {
+            Some(x) => x,
+            //leaves with fewer discrepancies were visited by previous iterations
+            None if left == 0 => return engine.record(csp, best),
+            None => return Ok(()),
+        };
+        for (i, a) in engine.values(&x).into_iter().enumerate() {
+            let cost = usize::from(i > 0);
+            if cost > left {
+                self.pruned = true;
+                break;
+            }
+            let result = if engine.decide(csp, vv(x.label().clone(), a)) {
+                self.probe(engine, csp, best, left - cost)
+            } else {
+                engine.fail()
+            };
+            engine.backtrack(csp);
+            result?;
+        }
+        Ok(())
+    }
+}
+
+impl<M, S, R, T:OrdT> Strategy<M, S, R, T> for Lds
+where
+    S: Scheme<M, T, R>,
+    R: Revise<M, T>,
+    M: Monitor
+{
+    fn explore(&mut self, engine: &mut Engine<M, S, R, T>, csp: &mut Csp<T>,
+               best: &mut Option<Vec<VValue<T>>>) -> Result<(), Limit> {
+        let mut k = 0;
+        loop {
+            self.pruned = false;
+            self.probe(engine, csp, best, k)?;
+            if !self.pruned {
+                return Ok(());
+            }
+            if self.max_discrepancies.is_some_and(|max| k >= max) {
+                return Err(Limit::Discrepancies);
+            }
+            k += 1;
+        }
+    }
+}
+
+/**************************************
+            DDS
+***************************************/
+
+//max_depth: None, iterates until the tree is fully explored
+#[derive(Debug, Default)]
+pub struct Dds {
+    pub max_depth: Option<usize>,
+    //a branch was cut below the discrepancy depth
+    pruned: bool,
+}
+
+impl Dds {
+    pub fn new(max_depth: Option<usize>) -> Self {
+        Self { max_depth, pruned: false }
+    }
+
+    //iteration i: any value above depth i-1, a discrepancy at depth i-1, the heuristic below
+    fn probe<M, S, R, T:OrdT>(&mut self, engine: &mut Engine<M, S, R, T>, csp: &mut Csp<T>,
+                              best: &mut Option<Vec<VValue<T>>>, depth: usize, i: usize) -> Result<(), Limit>
+    where
+        S: Scheme<M, T, R>,
+        R: Revise<M, T>,
+        M: Monitor
+    {
+        engine.check()?;
+        let x = match engine.select(csp) {
+            Some(x) => x,
+            //the discrepancy depth has not been reached: leaf of a previous iteration
+            None if depth >= i => return engine.record(csp, best),
+            None => return Ok(()),
+        };
+        let values = engine.values(&x);
+        let values = if depth + 1 < i {
+            values
+        } else if depth + 1 == i {
+            values.into_iter().skip(1).collect()
+        } else {
+            self.pruned |= values.len() > 1;
+            values.into_iter().take(1).collect()
+        };
+        for a in values {
+            let result = if engine.decide(csp, vv(x.label().clone(), a)) {
+                self.probe(engine, csp, best, depth + 1, i)
+            } else {
+                engine.fail()
+            };
+            engine.backtrack(csp);
+            result?;
+        }
+        Ok(())
+    }
+}
+
+impl<M, S, R, T:OrdT> Strategy<M, S, R, T> for Dds
+where
+    S: Scheme<M, T, R>,
+    R: Revise<M, T>,
+    M: Monitor
+{
+    fn explore(&mut self, engine: &mut Engine<M, S, R, T>, csp: &mut Csp<T>,
+               best: &mut Option<Vec<VValue<T>>>) -> Result<(), Limit> {
+        let mut i = 0;
+        loop {
+            self.pruned = false;
+            self.probe(engine, csp, best, 0, i)?;
+            if !self.pruned {
+                return Ok(());
+            }
+            if self.max_depth.is_some_and(|max| i >= max) {
+                return Err(Limit::Discrepancies);
+            }
+            i += 1;
+        }
+    }
+}
+
+/**************************************
+        Unit Tests
+***************************************/
+
+#[cfg(test)]
+mod tests {
+    use crate::csp::ast::expr::Expr;
+    use crate::csp::ast::pred::Pred;
+    use std::rc::Rc;
+    use crate::{dom, neq, var};
+    use crate::csp::constraint::constraint::Constraint;
+    use crate::csp::constraint::intensional::Intensional;
+    use crate::csp::csp::Csp;
+    use crate::csp::domain::setdom::SetDom;
+    use crate::csp::variable::extvar::generate_variables;
+    use crate::csp::variable::vvalue::{vv, VValue};
+    use crate::instrumentation::monitor::Statistics;
+    use crate::solver::consistency::consistency::Consistency;
+    use crate::solver::consistency::revise::AC1;
+    use crate::solver::consistency::scheme::ArcOriented;
+    use crate::solver::search::engine::Engine;
+    use crate::solver::search::heuristic::{Lexico, MaxVal};
+    use crate::solver::search::limits::{Limit, SearchLimits, SearchOutcome};
+    use crate::solver::search::strategy::{Dds, Dfs, Lds, Strategy};
+
+    type Eng = Engine<Statistics, ArcOriented, AC1, i32>;
+
+    //x1..x3 in {1, 2, 3}, x1 != x2, x2 != x3
+    fn chain() -> Csp<i32> {
+        let vmap = generate_variables("x", 3, &dom![1, 2, 3]);
+        let constraints: Vec<Rc<dyn Constraint<i32>>> = vec![
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x1"]), var!(vmap["x2"])))),
+            Rc::new(Intensional::from_pred(neq!(var!(vmap["x2"]), var!(vmap["x3"])))),
+        ];
+        Csp::new(vmap, constraints)
+    }
+
+    fn setup_engine(limits: SearchLimits) -> Eng {
+        let mut engine = Engine::with_heuristics(Consistency::new(ArcOriented, AC1, Statistics::default()),
+                                                 Box::new(Lexico), Box::new(MaxVal));
+        engine.set_limits(limits);
+        engine
+    }
+
+    fn first(strategy: &mut dyn Strategy<Statistics, ArcOriented, AC1, i32>) -> Vec<VValue<i32>> {
+        let mut engine = setup_engine(SearchLimits::default());
+        engine.search_with(&mut chain(), strategy).solution().cloned().unwrap()
+    }
+
+    #[test]
+    fn strategies_enumerate_every_solution() {
+        //3 * 2 * 2 solutions, each leaf visited exactly once
+        for strategy in [&mut Dfs as &mut dyn Strategy<_, _, _, _>, &mut Lds::default(), &mut Dds::default()] {
+            let mut engine = setup_engine(SearchLimits::none());
+            assert!(engine.search_with(&mut chain(), strategy).is_sat());
+            assert_eq!(engine.limiter().solutions, 12);
+            assert_eq!(engine.monitor().solutions, 12);
+        }
+    }
+
+    #[test]
+    fn heuristic_branch_first() {
+        //MaxVal: x1 = 3, x2 = 2, x3 = 3 for every strategy
+        let expected = vec![vv("x1".into(), 3), vv("x2".into(), 2), vv("x3".into(), 3)];
+        assert_eq!(first(&mut Dfs), expected);
+        assert_eq!(first(&mut Lds::default()), expected);
+        assert_eq!(first(&mut Dds::default()), expected);
+    }
+
+    #[test]
+    fn discrepancy_bounds() {
+        //0 discrepancy: only the heuristic branch
+        let mut engine = setup_engine(SearchLimits::none());
+        let outcome = engine.search_with(&mut chain(), &mut Lds::new(Some(0)));
+        assert!(matches!(outcome, SearchOutcome::Unknown(Limit::Discrepancies, Some(_))));
+        assert_eq!(engine.limiter().solutions, 1);
+
+        //discrepancies at depth 0 only: x1 in {3, 2, 1}
+        let mut engine = setup_engine(SearchLimits::none());
+        engine.search_with(&mut chain(), &mut Dds::new(Some(1)));
+        assert_eq!(engine.limiter().solutions, 3);
+    }
+}